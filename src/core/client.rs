@@ -1,13 +1,14 @@
 use super::{
 	client_state::{ClientState, CLIENT_STATES},
 	destroy_queue,
+	permissions::{Capability, Permissions},
 	scenegraph::Scenegraph,
 };
 use crate::{
 	core::{registry::OwnedRegistry, task},
-	nodes::{audio, data, drawable, fields, hmd, input, items, root::Root, spatial, Node},
+	nodes::{audio, data, debug, drawable, fields, hmd, input, items, root::Root, spatial, Node},
 };
-use color_eyre::eyre::{eyre, Result};
+use color_eyre::eyre::{ensure, eyre, Result};
 use lazy_static::lazy_static;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
@@ -16,7 +17,12 @@ use stardust_xr::{
 	messenger::{self, MessageSenderHandle},
 	schemas::flex::serialize,
 };
-use std::{fs, iter::FromIterator, path::PathBuf, sync::Arc};
+use std::{
+	fs,
+	iter::FromIterator,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 use tokio::{net::UnixStream, task::JoinHandle};
 use tracing::info;
 
@@ -36,6 +42,7 @@ lazy_static! {
 		root: OnceCell::new(),
 		base_resource_prefixes: Default::default(),
 		state: Arc::new(ClientState::default()),
+		permissions: Permissions::unrestricted(),
 	});
 }
 
@@ -65,6 +72,7 @@ pub struct Client {
 	pub root: OnceCell<Arc<Root>>,
 	pub base_resource_prefixes: Mutex<Vec<PathBuf>>,
 	pub state: Arc<ClientState>,
+	pub permissions: Permissions,
 }
 impl Client {
 	pub fn from_connection(connection: UnixStream) -> Result<Arc<Self>> {
@@ -85,6 +93,7 @@ impl Client {
 			.as_ref()
 			.and_then(state)
 			.unwrap_or_else(|| Arc::new(ClientState::default()));
+		let permissions = Permissions::for_exe(exe.as_deref());
 
 		let client = CLIENTS.add(Client {
 			pid,
@@ -100,6 +109,7 @@ impl Client {
 			root: OnceCell::new(),
 			base_resource_prefixes: Default::default(),
 			state,
+			permissions,
 		});
 		let _ = client.scenegraph.client.set(Arc::downgrade(&client));
 		let _ = client.root.set(Root::create(&client)?);
@@ -111,6 +121,7 @@ impl Client {
 		data::create_interface(&client)?;
 		items::create_interface(&client)?;
 		input::create_interface(&client)?;
+		debug::create_interface(&client)?;
 
 		client
 			.root
@@ -174,6 +185,9 @@ impl Client {
 		Ok(client)
 	}
 
+	pub fn get_exe(&self) -> Option<&Path> {
+		self.exe.as_deref()
+	}
 	pub fn get_cmdline(&self) -> Option<Vec<String>> {
 		let pid = self.pid?;
 		let exe_proc_path = format!("/proc/{pid}/exe");
@@ -195,6 +209,18 @@ impl Client {
 		Some(ClientState::from_deserialized(self, internal))
 	}
 
+	pub fn ensure_permission(&self, capability: Capability) -> Result<()> {
+		ensure!(
+			self.permissions.allows(capability),
+			"Permission denied: {} does not have the \"{capability}\" capability, grant it in the stardust permissions file",
+			self.exe
+				.as_ref()
+				.map(|exe| exe.display().to_string())
+				.unwrap_or_else(|| "this client".to_string()),
+		);
+		Ok(())
+	}
+
 	#[inline]
 	pub fn get_node(&self, name: &'static str, path: &str) -> Result<Arc<Node>> {
 		self.scenegraph
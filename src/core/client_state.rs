@@ -1,10 +1,24 @@
 use super::client::{get_env, Client};
-use crate::nodes::{spatial::Spatial, Node};
+use crate::nodes::{root::connection_environment, spatial::Spatial, Node};
+#[cfg(feature = "wayland")]
+use crate::wayland::xdg_activation::launch_activation_token;
+use color_eyre::eyre::Result;
 use glam::Mat4;
 use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
-use std::{io::Write, path::PathBuf, sync::Arc};
+use std::{
+	io::Write,
+	os::unix::process::CommandExt,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+	sync::Arc,
+	time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+/// Saved states older than this are from sessions nobody is coming back to
+const STALE_STATE_AGE: Duration = Duration::from_secs(60 * 60 * 24 * 7);
 
 lazy_static::lazy_static! {
 	pub static ref CLIENT_STATES: Mutex<FxHashMap<String, Arc<ClientState>>> = Default::default();
@@ -24,6 +38,37 @@ impl LaunchInfo {
 			env: get_env(client.pid?).ok()?,
 		})
 	}
+
+	/// Relaunch the client with the environment it had before, pointed at this server and its saved state.
+	pub fn launch(&self, token: &str) -> std::io::Result<()> {
+		let Some((program, args)) = self.cmdline.split_first() else {
+			return Err(std::io::ErrorKind::InvalidInput.into());
+		};
+		let mut command = Command::new(program);
+		command.args(args);
+		command.current_dir(&self.cwd);
+		command.env_clear();
+		command.envs(&self.env);
+		command.envs(connection_environment());
+		command.env("STARDUST_STARTUP_TOKEN", token);
+		#[cfg(feature = "wayland")]
+		if let Some(activation_token) = launch_activation_token(token) {
+			command.env("XDG_ACTIVATION_TOKEN", activation_token);
+		}
+
+		command.stdin(Stdio::null());
+		command.stdout(Stdio::null());
+		command.stderr(Stdio::null());
+		unsafe {
+			command.pre_exec(|| {
+				nix::unistd::setsid()
+					.map(|_| ())
+					.map_err(|_| std::io::ErrorKind::Other.into())
+			})
+		};
+		command.spawn()?;
+		Ok(())
+	}
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,6 +103,10 @@ impl ClientState {
 		CLIENT_STATES.lock().insert(token.clone(), Arc::new(self));
 		token
 	}
+	pub fn from_file(path: &Path) -> Result<Self> {
+		let file = std::fs::read(path)?;
+		Ok(stardust_xr::schemas::flex::flexbuffers::from_slice(&file)?)
+	}
 	pub fn to_file(self) {
 		let project_dirs = directories::ProjectDirs::from("", "", "stardust").unwrap();
 		let state_dir = project_dirs.state_dir().unwrap();
@@ -89,6 +138,55 @@ impl ClientState {
 		}
 	}
 }
+
+/// Relaunch every client saved at the last shutdown so it can pick its state back up through its startup token.
+/// State files are consumed in the process (they'll be saved again on the next shutdown), and ones that are too old or unreadable get deleted.
+pub fn restore_saved_clients() {
+	let Some(project_dirs) = directories::ProjectDirs::from("", "", "stardust") else {
+		return;
+	};
+	let Some(state_dir) = project_dirs.state_dir() else {
+		return;
+	};
+	let Ok(state_files) = std::fs::read_dir(state_dir) else {
+		return;
+	};
+	for state_file in state_files.filter_map(|entry| entry.ok()) {
+		let path = state_file.path();
+		if !path.is_file() {
+			continue;
+		}
+		let is_stale = state_file
+			.metadata()
+			.and_then(|metadata| metadata.modified())
+			.ok()
+			.and_then(|modified| SystemTime::now().duration_since(modified).ok())
+			.map(|age| age > STALE_STATE_AGE)
+			.unwrap_or(false);
+		let state = (!is_stale).then(|| ClientState::from_file(&path));
+		if let Err(e) = std::fs::remove_file(&path) {
+			warn!(?path, ?e, "Couldn't remove saved client state");
+		}
+
+		let mut state = match state {
+			None => continue,
+			Some(Err(e)) => {
+				warn!(?path, ?e, "Invalid saved client state");
+				continue;
+			}
+			Some(Ok(state)) => state,
+		};
+		let Some(launch_info) = state.launch_info.take() else {
+			continue;
+		};
+		let token = state.token();
+		match launch_info.launch(&token) {
+			Ok(()) => info!(cmdline = ?launch_info.cmdline, "Restored client"),
+			Err(e) => warn!(cmdline = ?launch_info.cmdline, ?e, "Couldn't restore client"),
+		}
+	}
+}
+
 impl Default for ClientState {
 	fn default() -> Self {
 		Self {
@@ -0,0 +1,99 @@
+use parking_lot::{const_mutex, Mutex};
+use std::{io::Write, os::fd::OwnedFd, sync::Arc};
+use tracing::warn;
+
+#[cfg(feature = "wayland")]
+use smithay::reexports::wayland_server::{protocol::wl_data_source::WlDataSource, Resource};
+
+/// Shared by Stardust and Wayland clients, so whatever either side copies the other can paste.
+static SELECTION: Mutex<Option<Selection>> = const_mutex(None);
+
+#[derive(Debug, Clone)]
+pub enum Selection {
+	/// Set by a Stardust client, which sends the data for every mime type up front
+	Stardust(Arc<Vec<(String, Vec<u8>)>>),
+	/// Set by a Wayland client, which writes the data itself whenever it's asked for
+	#[cfg(feature = "wayland")]
+	Wayland(WlDataSource),
+}
+impl Selection {
+	pub fn mime_types(&self) -> Vec<String> {
+		match self {
+			Selection::Stardust(contents) => contents
+				.iter()
+				.map(|(mime_type, _)| mime_type.clone())
+				.collect(),
+			#[cfg(feature = "wayland")]
+			Selection::Wayland(source) => crate::wayland::data_device::source_mime_types(source),
+		}
+	}
+	/// Write the data for `mime_type` into `fd`, closing it once everything is written.
+	pub fn send(&self, mime_type: String, fd: OwnedFd) {
+		match self {
+			Selection::Stardust(contents) => {
+				let Some((_, data)) = contents.iter().find(|(m, _)| m == &mime_type) else {
+					return;
+				};
+				let data = data.clone();
+				// the reader may take its time, so don't block on it
+				std::thread::spawn(move || {
+					if let Err(error) = std::fs::File::from(fd).write_all(&data) {
+						warn!(?error, mime_type, "Couldn't send clipboard contents");
+					}
+				});
+			}
+			#[cfg(feature = "wayland")]
+			Selection::Wayland(source) => {
+				if source.is_alive() {
+					use std::os::fd::AsFd;
+					source.send(mime_type, fd.as_fd());
+				}
+			}
+		}
+	}
+	fn is_valid(&self) -> bool {
+		match self {
+			Selection::Stardust(_) => true,
+			#[cfg(feature = "wayland")]
+			Selection::Wayland(source) => source.is_alive(),
+		}
+	}
+	/// Let whoever set this know it's not the selection anymore
+	fn cancel(&self) {
+		match self {
+			Selection::Stardust(_) => (),
+			#[cfg(feature = "wayland")]
+			Selection::Wayland(source) => {
+				if source.is_alive() {
+					source.cancelled();
+				}
+			}
+		}
+	}
+}
+impl PartialEq for Selection {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Selection::Stardust(a), Selection::Stardust(b)) => Arc::ptr_eq(a, b),
+			#[cfg(feature = "wayland")]
+			(Selection::Wayland(a), Selection::Wayland(b)) => a == b,
+			#[cfg(feature = "wayland")]
+			_ => false,
+		}
+	}
+}
+
+pub fn get_selection() -> Option<Selection> {
+	SELECTION.lock().clone().filter(Selection::is_valid)
+}
+pub fn set_selection(selection: Option<Selection>) {
+	let old_selection = std::mem::replace(&mut *SELECTION.lock(), selection.clone());
+	if let Some(old_selection) = old_selection {
+		if Some(&old_selection) != selection.as_ref() {
+			old_selection.cancel();
+		}
+	}
+
+	#[cfg(feature = "wayland")]
+	crate::wayland::data_device::selection_changed();
+}
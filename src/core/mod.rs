@@ -1,11 +1,14 @@
 pub mod client;
 pub mod client_state;
+pub mod clipboard;
 pub mod delta;
 pub mod destroy_queue;
 pub mod eventloop;
 pub mod idl_utils;
 pub mod node_collections;
+pub mod permissions;
 pub mod registry;
 pub mod resource;
 pub mod scenegraph;
+pub mod shm;
 pub mod task;
@@ -0,0 +1,172 @@
+use directories::ProjectDirs;
+use lazy_static::lazy_static;
+use rustc_hash::FxHashSet;
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
+use tracing::{info, warn};
+
+lazy_static! {
+	static ref POLICY: Option<Policy> = Policy::load();
+}
+
+/// Things that affect the whole environment or other clients, so the user may not want every client doing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+	/// Replace the sky texture or light
+	SkyTex,
+	/// Register as the UI for a type of item, which sees every item of that type
+	ItemUI,
+	/// Create zones that can capture other clients' spatials
+	Zone,
+	/// Create input methods that send input to every client, over stardust or Wayland's input-method-v2
+	InputMethod,
+	/// Inspect every client's scenegraph through `/debug`
+	Debug,
+	/// Read and replace the clipboard shared with Wayland clients
+	Clipboard,
+	/// Copy what other Wayland clients are showing through wlr-screencopy
+	ScreenCapture,
+}
+impl Capability {
+	pub const ALL: [Capability; 7] = [
+		Capability::SkyTex,
+		Capability::ItemUI,
+		Capability::Zone,
+		Capability::InputMethod,
+		Capability::Debug,
+		Capability::Clipboard,
+		Capability::ScreenCapture,
+	];
+	pub fn name(&self) -> &'static str {
+		match self {
+			Capability::SkyTex => "sky_tex",
+			Capability::ItemUI => "item_ui",
+			Capability::Zone => "zone",
+			Capability::InputMethod => "input_method",
+			Capability::Debug => "debug",
+			Capability::Clipboard => "clipboard",
+			Capability::ScreenCapture => "screen_capture",
+		}
+	}
+}
+impl FromStr for Capability {
+	type Err = ();
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Capability::ALL
+			.into_iter()
+			.find(|capability| capability.name() == s)
+			.ok_or(())
+	}
+}
+impl Display for Capability {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+#[derive(Debug)]
+enum ClientMatcher {
+	Any,
+	Path(PathBuf),
+	Name(String),
+}
+impl ClientMatcher {
+	fn matches(&self, exe: Option<&Path>) -> bool {
+		match self {
+			ClientMatcher::Any => true,
+			ClientMatcher::Path(path) => exe == Some(path.as_path()),
+			ClientMatcher::Name(name) => exe
+				.and_then(|exe| exe.file_name())
+				.map(|exe_name| exe_name.to_string_lossy() == name.as_str())
+				.unwrap_or(false),
+		}
+	}
+}
+
+/// Parsed from `permissions` in the stardust config dir. Each line is a client followed by the capabilities it gets:
+/// ```text
+/// # absolute exe path, exe file name, or * for every client
+/// /usr/bin/protostar item_ui input_method
+/// flatland item_ui
+/// * zone
+/// ```
+/// If the file doesn't exist every client can do everything.
+#[derive(Debug, Default)]
+struct Policy {
+	rules: Vec<(ClientMatcher, Vec<Capability>)>,
+}
+impl Policy {
+	fn load() -> Option<Self> {
+		let path = ProjectDirs::from("", "", "stardust")?
+			.config_dir()
+			.join("permissions");
+		let policy_string = std::fs::read_to_string(&path).ok()?;
+		info!(path = ?path.display(), "Loaded client permission policy");
+		Some(Self::parse(&policy_string))
+	}
+	fn parse(policy_string: &str) -> Self {
+		let mut policy = Policy::default();
+		for (line_number, line) in policy_string.lines().enumerate() {
+			let line = line.split('#').next().unwrap_or_default();
+			let mut words = line.split_whitespace();
+			let Some(client) = words.next() else {
+				continue;
+			};
+			let matcher = match client {
+				"*" => ClientMatcher::Any,
+				path if path.starts_with('/') => ClientMatcher::Path(PathBuf::from(path)),
+				name => ClientMatcher::Name(name.to_string()),
+			};
+			let capabilities = words
+				.filter_map(|word| match word.parse() {
+					Ok(capability) => Some(capability),
+					Err(_) => {
+						warn!(
+							line = line_number + 1,
+							capability = word,
+							"Unknown capability in permission policy"
+						);
+						None
+					}
+				})
+				.collect();
+			policy.rules.push((matcher, capabilities));
+		}
+		policy
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Permissions {
+	/// `None` means there's no policy restricting this client
+	granted: Option<FxHashSet<Capability>>,
+}
+impl Permissions {
+	pub fn unrestricted() -> Self {
+		Permissions { granted: None }
+	}
+	pub fn for_exe(exe: Option<&Path>) -> Self {
+		let Some(policy) = POLICY.as_ref() else {
+			return Self::unrestricted();
+		};
+		let granted = policy
+			.rules
+			.iter()
+			.filter(|(matcher, _)| matcher.matches(exe))
+			.flat_map(|(_, capabilities)| capabilities.iter().copied())
+			.collect();
+		Permissions {
+			granted: Some(granted),
+		}
+	}
+
+	pub fn allows(&self, capability: Capability) -> bool {
+		self.granted
+			.as_ref()
+			.map(|granted| granted.contains(&capability))
+			.unwrap_or(true)
+	}
+}
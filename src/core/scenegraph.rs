@@ -50,6 +50,11 @@ impl Scenegraph {
 		Some(node)
 	}
 
+	/// Every node in this scenegraph, without resolving aliases.
+	pub fn get_nodes(&self) -> Vec<Arc<Node>> {
+		self.nodes.lock().values().cloned().collect()
+	}
+
 	pub fn remove_node(&self, path: &str) -> Option<Arc<Node>> {
 		debug!(path, "Remove node");
 		self.nodes.lock().remove(path)
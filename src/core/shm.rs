@@ -0,0 +1,45 @@
+use std::{
+	ffi::CString,
+	fs::File,
+	io::{self, Read, Seek, Write},
+	os::fd::{FromRawFd, OwnedFd},
+};
+
+/// Put the contents in a new memfd so they can be sent to clients in a message, which can mmap it.
+pub fn memfd_with(name: &str, contents: &[u8]) -> io::Result<OwnedFd> {
+	let name = CString::new(name)?;
+	let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+	if fd < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let mut file = unsafe { File::from_raw_fd(fd) };
+	file.write_all(contents)?;
+	Ok(file.into())
+}
+
+/// The largest file a client can send in a message, big enough for models with embedded textures.
+pub const MAX_FD_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Read all of a file or memfd a client sent, from the start no matter where its offset was left.
+/// Anything that isn't a regular file (like `/dev/zero` or a pipe) or is over `MAX_FD_SIZE` is rejected before reading.
+pub fn read_fd(fd: OwnedFd) -> io::Result<Vec<u8>> {
+	let mut file = File::from(fd);
+	let metadata = file.metadata()?;
+	if !metadata.file_type().is_file() {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"Sent fd is not a regular file or memfd",
+		));
+	}
+	let len = metadata.len();
+	if len > MAX_FD_SIZE {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("Sent file is {len} bytes, over the {MAX_FD_SIZE} byte limit"),
+		));
+	}
+	file.rewind()?;
+	let mut contents = Vec::with_capacity(len as usize);
+	file.take(len).read_to_end(&mut contents)?;
+	Ok(contents)
+}
@@ -5,10 +5,10 @@ mod objects;
 mod wayland;
 
 use crate::core::client::CLIENTS;
-use crate::core::client_state::ClientState;
+use crate::core::client_state::{restore_saved_clients, ClientState};
 use crate::core::destroy_queue;
 use crate::nodes::items::camera;
-use crate::nodes::{audio, drawable, hmd, input};
+use crate::nodes::{audio, drawable, fields, hmd, input, spatial};
 use crate::objects::input::eye_pointer::EyePointer;
 use crate::objects::input::mouse_pointer::MousePointer;
 use crate::objects::input::sk_controller::SkController;
@@ -22,10 +22,11 @@ use directories::ProjectDirs;
 use once_cell::sync::OnceCell;
 use stardust_xr::server;
 use std::os::unix::process::CommandExt;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
-use std::time::Duration;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use stereokit::{
 	named_colors::BLACK, DepthMode, DisplayMode, Handed, LogLevel, StereoKitMultiThread,
 	TextureFormat, TextureType,
@@ -56,11 +57,21 @@ struct CliArgs {
 	/// Run a script when ready for clients to connect. If this is not set the script at $HOME/.config/stardust/startup will be ran if it exists.
 	#[clap(id = "PATH", short = 'e', long = "execute-startup-script", action)]
 	startup_script: Option<PathBuf>,
+
+	/// Run without StereoKit, so no rendering, audio, wayland or XR input. Useful for testing clients and CI.
+	#[clap(long, action)]
+	headless: bool,
+
+	/// Add a virtual output for Wayland panels as NAME=WIDTHxHEIGHT@SCALE, can be given more than once. If this is not set the outputs in $HOME/.config/stardust/outputs will be used if it exists. Panels start on the first output.
+	#[cfg(feature = "wayland")]
+	#[clap(id = "OUTPUT", long = "output", action = clap::ArgAction::Append)]
+	outputs: Vec<wayland::output::OutputConfig>,
 }
 
 static STARDUST_INSTANCE: OnceCell<String> = OnceCell::new();
 static SK_MULTITHREAD: OnceCell<Sk> = OnceCell::new();
 static STOP_NOTIFIER: Notify = Notify::const_new();
+const HEADLESS_FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
 
 struct EventLoopInfo {
 	tokio_handle: Handle,
@@ -68,13 +79,6 @@ struct EventLoopInfo {
 }
 
 fn main() {
-	ctrlc::set_handler(|| {
-		if atty::isnt(atty::Stream::Stdout) {
-			STOP_NOTIFIER.notify_waiters()
-		}
-	})
-	.unwrap();
-
 	let registry = tracing_subscriber::registry();
 
 	#[cfg(feature = "profile_app")]
@@ -98,6 +102,20 @@ fn main() {
 	}
 	let cli_args = Arc::new(CliArgs::parse());
 
+	let headless = cli_args.headless;
+	ctrlc::set_handler(move || {
+		// StereoKit handles the terminal's ctrl+c itself, but there's no StereoKit when headless
+		if headless || atty::isnt(atty::Stream::Stdout) {
+			STOP_NOTIFIER.notify_waiters()
+		}
+	})
+	.unwrap();
+
+	if headless {
+		run_headless(project_dirs, cli_args);
+		return;
+	}
+
 	let sk = stereokit::Settings {
 		app_name: "Stardust XR".to_string(),
 		display_preference: if cli_args.flatscreen {
@@ -194,68 +212,23 @@ fn main() {
 		.then(|| PlaySpace::new().ok())
 		.flatten();
 
-	let (info_sender, info_receiver) = oneshot::channel::<EventLoopInfo>();
-	let event_thread = std::thread::Builder::new()
-		.name("event_loop".to_owned())
-		.spawn(move || event_loop(info_sender))
-		.unwrap();
-	let event_loop_info = info_receiver.blocking_recv().unwrap();
+	let (event_thread, event_loop_info) = start_event_loop();
 	let _tokio_handle = event_loop_info.tokio_handle.enter();
 
 	#[cfg(feature = "wayland")]
-	let mut wayland = wayland::Wayland::new().expect("Could not initialize wayland");
+	let mut wayland =
+		wayland::Wayland::new(wayland::output::OutputConfig::load(&cli_args.outputs))
+			.expect("Could not initialize wayland");
 	info!("Stardust ready!");
 
-	let mut startup_child = (|| {
-		let project_dirs = project_dirs.as_ref()?;
-		let startup_script_path = cli_args
-			.startup_script
-			.clone()
-			.and_then(|p| p.canonicalize().ok())
-			.unwrap_or_else(|| project_dirs.config_dir().join("startup"));
-		let mut startup_command = Command::new("bash");
-		startup_command.arg(startup_script_path);
-		startup_command.arg("&");
-
-		startup_command.stdin(Stdio::null());
-		startup_command.stdout(Stdio::null());
-		startup_command.stderr(Stdio::null());
-		startup_command.env(
-			"FLAT_WAYLAND_DISPLAY",
-			std::env::var_os("WAYLAND_DISPLAY").unwrap_or_default(),
-		);
-		startup_command.env(
-			"STARDUST_INSTANCE",
-			event_loop_info
-				.socket_path
-				.file_name()
-				.expect("Stardust socket path not found"),
-		);
+	let mut startup_child = run_startup_script(
+		project_dirs.as_ref(),
+		&cli_args,
+		&event_loop_info.socket_path,
 		#[cfg(feature = "wayland")]
-		{
-			if let Some(wayland_socket) = wayland.socket_name.as_ref() {
-				startup_command.env("WAYLAND_DISPLAY", &wayland_socket);
-			}
-			startup_command.env(
-				"DISPLAY",
-				format!(":{}", X_DISPLAY.get().cloned().unwrap_or_default()),
-			);
-			startup_command.env("GDK_BACKEND", "wayland");
-			startup_command.env("QT_QPA_PLATFORM", "wayland");
-			startup_command.env("MOZ_ENABLE_WAYLAND", "1");
-			startup_command.env("CLUTTER_BACKEND", "wayland");
-			startup_command.env("SDL_VIDEODRIVER", "wayland");
-		}
-		unsafe {
-			startup_command.pre_exec(|| {
-				nix::unistd::setsid()
-					.map(|_| ())
-					.map_err(|_| std::io::ErrorKind::Other.into())
-			})
-		};
-		let child = startup_command.spawn().ok()?;
-		Some(child)
-	})();
+		wayland.socket_name.as_deref(),
+	);
+	restore_saved_clients();
 
 	let mut last_frame_delta = Duration::ZERO;
 	let mut sleep_duration = Duration::ZERO;
@@ -289,6 +262,8 @@ fn main() {
 					play_space.update(sk);
 				}
 				input::process_input();
+				fields::send_changes();
+				spatial::animation::update(sk.time_elapsed_unscaled());
 				nodes::root::Root::send_frame_events(sk.time_elapsed_unscaled());
 				adaptive_sleep(
 					sk,
@@ -326,6 +301,112 @@ fn main() {
 	info!("Cleanly shut down Stardust");
 }
 
+fn run_headless(project_dirs: Option<ProjectDirs>, cli_args: Arc<CliArgs>) {
+	let (event_thread, event_loop_info) = start_event_loop();
+	let _tokio_handle = event_loop_info.tokio_handle.enter();
+	info!("Stardust ready! (headless)");
+
+	let mut startup_child = run_startup_script(
+		project_dirs.as_ref(),
+		&cli_args,
+		&event_loop_info.socket_path,
+		#[cfg(feature = "wayland")]
+		None,
+	);
+	restore_saved_clients();
+
+	let mut last_frame = Instant::now();
+	while !event_thread.is_finished() {
+		let _span = debug_span!("Headless step");
+		let _span = _span.enter();
+
+		destroy_queue::clear();
+		input::process_input();
+		fields::send_changes();
+		let now = Instant::now();
+		let delta = (now - last_frame).as_secs_f64();
+		spatial::animation::update(delta);
+		nodes::root::Root::send_frame_events(delta);
+		last_frame = now;
+
+		std::thread::sleep(HEADLESS_FRAME_INTERVAL);
+	}
+
+	if let Some(mut startup_child) = startup_child.take() {
+		let _ = startup_child.kill();
+	}
+	event_thread
+		.join()
+		.expect("Failed to cleanly shut down event loop")
+		.unwrap();
+
+	info!("Cleanly shut down Stardust");
+}
+
+fn start_event_loop() -> (JoinHandle<color_eyre::eyre::Result<()>>, EventLoopInfo) {
+	let (info_sender, info_receiver) = oneshot::channel::<EventLoopInfo>();
+	let event_thread = std::thread::Builder::new()
+		.name("event_loop".to_owned())
+		.spawn(move || event_loop(info_sender))
+		.unwrap();
+	let event_loop_info = info_receiver.blocking_recv().unwrap();
+	(event_thread, event_loop_info)
+}
+
+fn run_startup_script(
+	project_dirs: Option<&ProjectDirs>,
+	cli_args: &CliArgs,
+	socket_path: &Path,
+	#[cfg(feature = "wayland")] wayland_socket: Option<&str>,
+) -> Option<Child> {
+	let project_dirs = project_dirs?;
+	let startup_script_path = cli_args
+		.startup_script
+		.clone()
+		.and_then(|p| p.canonicalize().ok())
+		.unwrap_or_else(|| project_dirs.config_dir().join("startup"));
+	let mut startup_command = Command::new("bash");
+	startup_command.arg(startup_script_path);
+	startup_command.arg("&");
+
+	startup_command.stdin(Stdio::null());
+	startup_command.stdout(Stdio::null());
+	startup_command.stderr(Stdio::null());
+	startup_command.env(
+		"FLAT_WAYLAND_DISPLAY",
+		std::env::var_os("WAYLAND_DISPLAY").unwrap_or_default(),
+	);
+	startup_command.env(
+		"STARDUST_INSTANCE",
+		socket_path
+			.file_name()
+			.expect("Stardust socket path not found"),
+	);
+	#[cfg(feature = "wayland")]
+	{
+		if let Some(wayland_socket) = wayland_socket {
+			startup_command.env("WAYLAND_DISPLAY", wayland_socket);
+		}
+		startup_command.env(
+			"DISPLAY",
+			format!(":{}", X_DISPLAY.get().cloned().unwrap_or_default()),
+		);
+		startup_command.env("GDK_BACKEND", "wayland");
+		startup_command.env("QT_QPA_PLATFORM", "wayland");
+		startup_command.env("MOZ_ENABLE_WAYLAND", "1");
+		startup_command.env("CLUTTER_BACKEND", "wayland");
+		startup_command.env("SDL_VIDEODRIVER", "wayland");
+	}
+	unsafe {
+		startup_command.pre_exec(|| {
+			nix::unistd::setsid()
+				.map(|_| ())
+				.map_err(|_| std::io::ErrorKind::Other.into())
+		})
+	};
+	startup_command.spawn().ok()
+}
+
 fn adaptive_sleep(
 	sk: &impl StereoKitMultiThread,
 	last_frame_delta: &mut Duration,
@@ -372,8 +453,10 @@ async fn event_loop(info_sender: oneshot::Sender<EventLoopInfo>) -> color_eyre::
 
 	info!("Cleanly shut down event loop");
 
-	unsafe {
-		stereokit::sys::sk_quit();
+	if SK_MULTITHREAD.get().is_some() {
+		unsafe {
+			stereokit::sys::sk_quit();
+		}
 	}
 
 	Ok(())
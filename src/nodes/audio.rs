@@ -1,30 +1,62 @@
-use super::{Aspect, Node};
+use super::{Aspect, Message, Node};
 use crate::core::client::Client;
 use crate::core::destroy_queue;
 use crate::core::registry::Registry;
 use crate::core::resource::get_resource_file;
-use crate::create_interface;
+use crate::core::shm::read_fd;
 use crate::nodes::spatial::{Spatial, Transform};
 use color_eyre::eyre::{eyre, Result};
 use glam::{vec3, Vec4Swizzles};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use send_wrapper::SendWrapper;
+use serde::Deserialize;
+use stardust_xr::schemas::flex::deserialize;
 use stardust_xr::values::ResourceID;
 
 use std::ops::DerefMut;
 use std::sync::Arc;
 use std::{ffi::OsStr, path::PathBuf};
 use stereokit::{Sound as SkSound, SoundInstance, StereoKitDraw};
+use tracing::warn;
 
 static SOUND_REGISTRY: Registry<Sound> = Registry::new();
 
 stardust_xr_server_codegen::codegen_audio_protocol!();
+
+enum SoundSource {
+	File(PathBuf),
+	/// Encoded WAV or MP3 bytes
+	Memory(Vec<u8>),
+}
+impl SoundSource {
+	fn load(&self, sk: &impl StereoKitDraw) -> Result<SkSound> {
+		match self {
+			SoundSource::File(path) => Ok(sk.sound_create(path.clone())?),
+			SoundSource::Memory(data) => {
+				// StereoKit can only decode sounds from files, and picks the format from the extension
+				let extension = if data.starts_with(b"RIFF") {
+					"wav"
+				} else {
+					"mp3"
+				};
+				let path = std::env::temp_dir()
+					.join(format!("stardust-sound-{}.{extension}", nanoid::nanoid!()));
+				std::fs::write(&path, data)?;
+				let sound = sk.sound_create(path.clone());
+				let _ = std::fs::remove_file(path);
+				Ok(sound?)
+			}
+		}
+	}
+}
+
 pub struct Sound {
 	space: Arc<Spatial>,
 
 	volume: f32,
-	pending_audio_path: PathBuf,
+	/// Taken when the sound is first loaded, so a broken one isn't retried every frame
+	pending_source: Mutex<Option<SoundSource>>,
 	sk_sound: OnceCell<SendWrapper<SkSound>>,
 	instance: Mutex<Option<SoundInstance>>,
 	stop: Mutex<Option<()>>,
@@ -38,10 +70,17 @@ impl Sound {
 			&[OsStr::new("wav"), OsStr::new("mp3")],
 		)
 		.ok_or_else(|| eyre!("Resource not found"))?;
+		Sound::add_to_source(node, SoundSource::File(pending_audio_path))
+	}
+	/// Load a WAV or MP3 sound from its bytes, for clients that can't share files with the server.
+	pub fn add_to_from_memory(node: &Arc<Node>, data: Vec<u8>) -> Result<Arc<Sound>> {
+		Sound::add_to_source(node, SoundSource::Memory(data))
+	}
+	fn add_to_source(node: &Arc<Node>, source: SoundSource) -> Result<Arc<Sound>> {
 		let sound = Sound {
 			space: node.get_aspect::<Spatial>().unwrap().clone(),
 			volume: 1.0,
-			pending_audio_path,
+			pending_source: Mutex::new(Some(source)),
 			sk_sound: OnceCell::new(),
 			instance: Mutex::new(None),
 			stop: Mutex::new(None),
@@ -54,9 +93,17 @@ impl Sound {
 	}
 
 	fn update(&self, sk: &impl StereoKitDraw) {
-		let sound = self.sk_sound.get_or_init(|| {
-			SendWrapper::new(sk.sound_create(self.pending_audio_path.clone()).unwrap())
-		});
+		if let Some(source) = self.pending_source.lock().take() {
+			match source.load(sk) {
+				Ok(sound) => {
+					let _ = self.sk_sound.set(SendWrapper::new(sound));
+				}
+				Err(error) => warn!(?error, "Failed to load sound"),
+			}
+		}
+		let Some(sound) = self.sk_sound.get() else {
+			return;
+		};
 		if self.stop.lock().take().is_some() {
 			if let Some(instance) = self.instance.lock().take() {
 				sk.sound_inst_stop(instance);
@@ -104,7 +151,13 @@ pub fn update(sk: &impl StereoKitDraw) {
 	}
 }
 
-create_interface!(AudioInterface, AudioInterfaceAspect, "/audio");
+pub fn create_interface(client: &Arc<Client>) -> Result<()> {
+	let node = Node::create_path(client, "/audio", false);
+	<AudioInterface as AudioInterfaceAspect>::add_node_members(&node);
+	node.add_local_signal("create_sound_from_fd", create_sound_from_fd_flex);
+	node.add_to_scenegraph()?;
+	Ok(())
+}
 struct AudioInterface;
 impl AudioInterfaceAspect for AudioInterface {
 	#[doc = "Create a sound node. WAV and MP3 are supported."]
@@ -126,3 +179,39 @@ impl AudioInterfaceAspect for AudioInterface {
 		Ok(())
 	}
 }
+
+/// Like `create_sound`, but the WAV/MP3 bytes are in the message's fd instead of a resource file.
+fn create_sound_from_fd_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct CreateSoundInfo<'a> {
+		name: &'a str,
+		parent_path: &'a str,
+		transform: Transform,
+	}
+	let info: CreateSoundInfo = deserialize(&message.data)?;
+	let parent = calling_client
+		.get_node("Spatial parent", info.parent_path)?
+		.get_aspect::<Spatial>()?;
+	let transform = info.transform.to_mat4(true, true, true);
+	let fd = message
+		.fds
+		.into_iter()
+		.next()
+		.ok_or_else(|| eyre!("Sound needs to be sent in an fd"))?;
+	let sound_data = read_fd(fd)?;
+
+	let node = Node::create_parent_name(
+		&calling_client,
+		AudioInterface::CREATE_SOUND_PARENT_PATH,
+		info.name,
+		true,
+	)
+	.add_to_scenegraph()?;
+	Spatial::add_to(&node, Some(parent.clone()), transform, false);
+	Sound::add_to_from_memory(&node, sound_data)?;
+	Ok(())
+}
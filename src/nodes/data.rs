@@ -1,11 +1,13 @@
 use super::alias::AliasInfo;
 use super::fields::Field;
 use super::spatial::{parse_transform, Spatial};
-use super::{Alias, Aspect, Node};
+use super::{Alias, Aspect, Message, Node};
 use crate::core::client::Client;
+use crate::core::clipboard::{self, Selection};
 use crate::core::node_collections::LifeLinkedNodeMap;
+use crate::core::permissions::Capability;
 use crate::core::registry::Registry;
-use crate::create_interface;
+use crate::core::scenegraph::MethodResponseSender;
 use crate::nodes::fields::FIELD_ALIAS_INFO;
 use crate::nodes::spatial::Transform;
 use color_eyre::eyre::{bail, ensure, eyre, Result};
@@ -13,8 +15,11 @@ use lazy_static::lazy_static;
 use nanoid::nanoid;
 use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
-use stardust_xr::schemas::flex::flexbuffers;
+use serde::Deserialize;
+use stardust_xr::schemas::flex::{deserialize, flexbuffers, serialize};
 use stardust_xr::values::Datamap;
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
 use std::sync::{Arc, Weak};
 
 lazy_static! {
@@ -199,7 +204,72 @@ impl Drop for PulseReceiver {
 	}
 }
 
-create_interface!(DataInterface, DataInterfaceAspect, "/data");
+pub fn create_interface(client: &Arc<Client>) -> Result<()> {
+	let node = Node::create_path(client, "/data", false);
+	<DataInterface as DataInterfaceAspect>::add_node_members(&node);
+	node.add_local_method("get_clipboard_mime_types", get_clipboard_mime_types_flex);
+	node.add_local_method("read_clipboard", read_clipboard_flex);
+	node.add_local_signal("set_clipboard", set_clipboard_flex);
+	node.add_to_scenegraph()?;
+	Ok(())
+}
+
+fn get_clipboard_mime_types_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	_message: Message,
+	response: MethodResponseSender,
+) {
+	response.wrap_sync(move || {
+		calling_client.ensure_permission(Capability::Clipboard)?;
+		let mime_types = clipboard::get_selection()
+			.map(|selection| selection.mime_types())
+			.unwrap_or_default();
+		Ok(serialize(mime_types)?.into())
+	});
+}
+/// Returns an fd the clipboard contents can be read from until EOF, the same way Wayland clients receive them.
+fn read_clipboard_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+	response: MethodResponseSender,
+) {
+	response.wrap_sync(move || {
+		calling_client.ensure_permission(Capability::Clipboard)?;
+		let mime_type: String = deserialize(message.as_ref())?;
+		let selection = clipboard::get_selection().ok_or_else(|| eyre!("Clipboard is empty"))?;
+		ensure!(
+			selection.mime_types().contains(&mime_type),
+			"Clipboard has no data for mime type {mime_type}"
+		);
+
+		let (read, write) = UnixStream::pair()?;
+		selection.send(mime_type, OwnedFd::from(write));
+		Ok(Message {
+			data: serialize(())?,
+			fds: vec![OwnedFd::from(read)],
+		})
+	});
+}
+/// Replaces the clipboard with data for each mime type, or clears it if there's none.
+fn set_clipboard_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct SetClipboardInfo {
+		contents: Vec<(String, Vec<u8>)>,
+	}
+	calling_client.ensure_permission(Capability::Clipboard)?;
+	let info: SetClipboardInfo = deserialize(message.as_ref())?;
+	let selection =
+		(!info.contents.is_empty()).then(|| Selection::Stardust(Arc::new(info.contents)));
+	clipboard::set_selection(selection);
+	Ok(())
+}
+
 struct DataInterface;
 impl DataInterfaceAspect for DataInterface {
 	fn create_pulse_sender(
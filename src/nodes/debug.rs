@@ -0,0 +1,149 @@
+use super::alias::Alias;
+use super::spatial::{Spatial, Transform};
+use super::{Message, Node};
+use crate::core::client::{Client, CLIENTS};
+use crate::core::permissions::Capability;
+use crate::core::scenegraph::MethodResponseSender;
+use color_eyre::eyre::Result;
+use portable_atomic::Ordering;
+use serde::Serialize;
+use stardust_xr::schemas::flex::serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+struct ClientInfo {
+	pid: Option<i32>,
+	exe: Option<PathBuf>,
+	nodes: Vec<NodeInfo>,
+}
+impl ClientInfo {
+	fn from_client(client: &Client) -> Self {
+		let mut nodes: Vec<NodeInfo> = client
+			.scenegraph
+			.get_nodes()
+			.iter()
+			.map(|node| NodeInfo::from_node(node))
+			.collect();
+		nodes.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+		ClientInfo {
+			pid: client.pid,
+			exe: client.get_exe().map(ToOwned::to_owned),
+			nodes,
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct NodeInfo {
+	path: String,
+	enabled: bool,
+	aspects: Vec<&'static str>,
+	spatial: Option<SpatialInfo>,
+	alias_of: Option<AliasInfo>,
+}
+impl NodeInfo {
+	fn from_node(node: &Node) -> Self {
+		NodeInfo {
+			path: node.get_path().to_string(),
+			enabled: node.enabled.load(Ordering::Relaxed),
+			aspects: node.get_aspect_names(),
+			spatial: node
+				.get_aspect::<Spatial>()
+				.ok()
+				.map(|spatial| SpatialInfo::from_spatial(&spatial)),
+			alias_of: node
+				.get_aspect::<Alias>()
+				.ok()
+				.and_then(|alias| AliasInfo::from_alias(&alias)),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct SpatialInfo {
+	/// Paths of each spatial parent, closest first. Parents from other clients are prefixed with their pid as `pid:path`.
+	parents: Vec<String>,
+	global_transform: Transform,
+}
+impl SpatialInfo {
+	fn from_spatial(spatial: &Spatial) -> Self {
+		let client = spatial.node().and_then(|node| node.get_client());
+		let mut parents = Vec::new();
+		let mut parent = spatial.get_parent();
+		while let Some(spatial) = parent {
+			if let Some(node) = spatial.node() {
+				parents.push(node_reference(&node, client.as_deref()));
+			}
+			parent = spatial.get_parent();
+		}
+
+		let (scale, rotation, translation) =
+			spatial.global_transform().to_scale_rotation_translation();
+		SpatialInfo {
+			parents,
+			global_transform: Transform {
+				translation: Some(translation.into()),
+				rotation: Some(rotation.into()),
+				scale: Some(scale.into()),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct AliasInfo {
+	original_pid: Option<i32>,
+	original_path: String,
+	enabled: bool,
+}
+impl AliasInfo {
+	fn from_alias(alias: &Alias) -> Option<Self> {
+		let original = alias.original.upgrade()?;
+		Some(AliasInfo {
+			original_pid: original.get_client().and_then(|client| client.pid),
+			original_path: original.get_path().to_string(),
+			enabled: alias.enabled.load(Ordering::Relaxed),
+		})
+	}
+}
+
+fn node_reference(node: &Node, relative_to: Option<&Client>) -> String {
+	let node_client = node.get_client();
+	let same_client = match (node_client.as_deref(), relative_to) {
+		(Some(a), Some(b)) => std::ptr::eq(a, b),
+		_ => false,
+	};
+	if same_client {
+		node.get_path().to_string()
+	} else {
+		let pid = node_client
+			.and_then(|client| client.pid)
+			.map(|pid| pid.to_string())
+			.unwrap_or_else(|| "server".to_string());
+		format!("{pid}:{}", node.get_path())
+	}
+}
+
+pub fn create_interface(client: &Arc<Client>) -> Result<()> {
+	let node = Node::create_path(client, "/debug", false);
+	node.add_local_method("list_clients", list_clients_flex);
+	node.add_to_scenegraph().map(|_| ())
+}
+
+fn list_clients_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	_message: Message,
+	response: MethodResponseSender,
+) {
+	response.wrap_sync(move || {
+		calling_client.ensure_permission(Capability::Debug)?;
+		let clients: Vec<ClientInfo> = CLIENTS
+			.get_vec()
+			.iter()
+			.map(|client| ClientInfo::from_client(client))
+			.collect();
+		Ok(serialize(clients)?.into())
+	});
+}
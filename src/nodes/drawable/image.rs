@@ -0,0 +1,180 @@
+use super::texture::{get_dynamic_texture, DynamicTexture};
+use crate::{
+	core::{client::Client, destroy_queue, registry::Registry, resource::get_resource_file},
+	nodes::{spatial::Spatial, Aspect, Message, Node},
+};
+use color_eyre::eyre::{eyre, Result};
+use glam::{vec3, Mat4};
+use mint::Vector2;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use portable_atomic::{AtomicBool, Ordering};
+use serde::Deserialize;
+use stardust_xr::{schemas::flex::deserialize, values::ResourceID};
+use std::{ffi::OsStr, path::PathBuf, sync::Arc};
+use stereokit::{
+	named_colors::WHITE, Bounds, Material, Mesh, RenderLayer, StereoKitDraw, Tex, Transparency,
+};
+
+static IMAGE_REGISTRY: Registry<Image> = Registry::new();
+
+/// Where an image's pixels come from.
+#[derive(Debug, Clone, Deserialize)]
+pub enum ImageSource {
+	/// PNG or JPG
+	Resource(ResourceID),
+	/// Path to a dynamic texture node, for pixels the client keeps updating
+	Texture(String),
+}
+
+/// An `ImageSource` resolved for the client, uploaded to the material on the next draw.
+pub enum ImageTexture {
+	File(PathBuf),
+	Dynamic(Arc<DynamicTexture>),
+}
+impl ImageTexture {
+	pub fn from_source(client: &Client, source: ImageSource) -> Result<Self> {
+		Ok(match source {
+			ImageSource::Resource(resource) => ImageTexture::File(
+				get_resource_file(&resource, client, &[OsStr::new("png"), OsStr::new("jpg")])
+					.ok_or_else(|| eyre!("Resource not found"))?,
+			),
+			ImageSource::Texture(path) => {
+				ImageTexture::Dynamic(get_dynamic_texture(client, &path)?)
+			}
+		})
+	}
+}
+
+/// A flat quad showing a texture, centered on its spatial and facing along its Z axis.
+pub struct Image {
+	enabled: Arc<AtomicBool>,
+	space: Arc<Spatial>,
+	/// In meters
+	size: Mutex<Vector2<f32>>,
+	pending_texture: Mutex<Option<ImageTexture>>,
+	/// Keeps a texture loaded from a file alive while the material uses it
+	file_tex: Mutex<Option<Tex>>,
+	/// Keeps a dynamic texture alive while the material uses it
+	dynamic_tex: Mutex<Option<Arc<DynamicTexture>>>,
+	material: OnceCell<Material>,
+}
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
+impl Image {
+	pub fn add_to(
+		node: &Arc<Node>,
+		texture: ImageTexture,
+		size: Vector2<f32>,
+	) -> Result<Arc<Image>> {
+		let _ = node
+			.get_aspect::<Spatial>()
+			.unwrap()
+			.bounding_box_calc
+			.set(|node| {
+				let Ok(image) = node.get_aspect::<Image>() else {
+					return Bounds::default();
+				};
+				let size = *image.size.lock();
+				Bounds {
+					center: [0.0; 3].into(),
+					dimensions: [size.x, size.y, 0.0].into(),
+				}
+			});
+
+		let image = IMAGE_REGISTRY.add(Image {
+			enabled: node.enabled.clone(),
+			space: node.get_aspect::<Spatial>()?.clone(),
+			size: Mutex::new(size),
+			pending_texture: Mutex::new(Some(texture)),
+			file_tex: Mutex::new(None),
+			dynamic_tex: Mutex::new(None),
+			material: OnceCell::new(),
+		});
+		node.add_local_signal("set_size", Image::set_size_flex);
+		node.add_local_signal("set_source", Image::set_source_flex);
+		node.add_aspect_raw(image.clone());
+		Ok(image)
+	}
+
+	fn set_size_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let image = node.get_aspect::<Image>()?;
+		*image.size.lock() = deserialize(message.as_ref())?;
+		Ok(())
+	}
+	fn set_source_flex(
+		node: Arc<Node>,
+		calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let image = node.get_aspect::<Image>()?;
+		let source: ImageSource = deserialize(message.as_ref())?;
+		let texture = ImageTexture::from_source(&calling_client, source)?;
+		image.pending_texture.lock().replace(texture);
+		Ok(())
+	}
+
+	fn draw(&self, sk: &impl StereoKitDraw) {
+		let material = self.material.get_or_init(|| {
+			let material = sk.material_copy(Material::UNLIT);
+			sk.material_set_transparency(&material, Transparency::Blend);
+			material
+		});
+		if let Some(texture) = self.pending_texture.lock().take() {
+			match texture {
+				ImageTexture::File(path) => {
+					if let Ok(tex) = sk.tex_create_file(path, true, 0) {
+						sk.material_set_texture(material, "diffuse", &tex);
+						if let Some(old_tex) = self.file_tex.lock().replace(tex) {
+							destroy_queue::add(old_tex);
+						}
+						self.dynamic_tex.lock().take();
+					}
+				}
+				ImageTexture::Dynamic(texture) => {
+					sk.material_set_texture(material, "diffuse", texture.sk_tex(sk));
+					self.dynamic_tex.lock().replace(texture);
+					if let Some(old_tex) = self.file_tex.lock().take() {
+						destroy_queue::add(old_tex);
+					}
+				}
+			}
+		}
+
+		let size = *self.size.lock();
+		sk.mesh_draw(
+			Mesh::QUAD,
+			material,
+			self.space.global_transform() * Mat4::from_scale(vec3(size.x, size.y, 1.0)),
+			WHITE,
+			RenderLayer::LAYER0,
+		);
+	}
+}
+impl Aspect for Image {
+	const NAME: &'static str = "Image";
+}
+impl Drop for Image {
+	fn drop(&mut self) {
+		if let Some(material) = self.material.take() {
+			destroy_queue::add(material);
+		}
+		if let Some(tex) = self.file_tex.lock().take() {
+			destroy_queue::add(tex);
+		}
+		IMAGE_REGISTRY.remove(self);
+	}
+}
+
+pub fn draw_all(sk: &impl StereoKitDraw) {
+	for image in IMAGE_REGISTRY.get_valid_contents() {
+		if image.enabled.load(Ordering::Relaxed) {
+			image.draw(sk);
+		}
+	}
+}
@@ -0,0 +1,295 @@
+use super::{
+	model::{holdout_material, TextureParameter},
+	texture::DynamicTexture,
+	MaterialParameter,
+};
+use crate::{
+	core::{client::Client, delta::Delta, destroy_queue, registry::Registry, shm::read_fd},
+	nodes::{spatial::Spatial, Aspect, Message, Node},
+};
+use color_eyre::eyre::{bail, ensure, Result};
+use glam::Vec3;
+use mint::{Vector2, Vector3};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use portable_atomic::{AtomicBool, Ordering};
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use stardust_xr::schemas::flex::deserialize;
+use std::{os::fd::OwnedFd, sync::Arc};
+use stereokit::{
+	bounds_grow_to_fit_pt, named_colors::WHITE, Bounds, Color32, Material, Mesh as SKMesh,
+	RenderLayer, StereoKitDraw, Vert,
+};
+
+static MESH_REGISTRY: Registry<Mesh> = Registry::new();
+const WHITE_32: Color32 = Color32 {
+	r: 255,
+	g: 255,
+	b: 255,
+	a: 255,
+};
+
+/// Vertex data for a mesh. Every attribute other than positions is optional, but needs one entry per vertex if it's there.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MeshData {
+	pub positions: Vec<Vector3<f32>>,
+	/// Smooth normals are calculated from the triangles if these aren't given
+	pub normals: Option<Vec<Vector3<f32>>>,
+	pub uvs: Option<Vec<Vector2<f32>>>,
+	/// Linear RGBA, multiplied with the material's color
+	pub colors: Option<Vec<[f32; 4]>>,
+	/// Every 3 make a triangle, counter-clockwise when looking at its front
+	pub indices: Vec<u32>,
+}
+impl MeshData {
+	/// Inline mesh data, or else flex serialized mesh data in the message's first fd so big meshes don't have to fit in a message.
+	pub fn from_message(data: Option<MeshData>, fds: Vec<OwnedFd>) -> Result<Self> {
+		let data = match (data, fds.into_iter().next()) {
+			(Some(data), _) => data,
+			(None, Some(fd)) => deserialize(&read_fd(fd)?)?,
+			(None, None) => bail!("Mesh data needs to be sent inline or in an fd"),
+		};
+		data.validate()?;
+		Ok(data)
+	}
+	fn validate(&self) -> Result<()> {
+		let vertex_count = self.positions.len();
+		ensure!(
+			self.normals
+				.as_ref()
+				.map_or(true, |n| n.len() == vertex_count),
+			"There need to be as many normals as positions"
+		);
+		ensure!(
+			self.uvs.as_ref().map_or(true, |u| u.len() == vertex_count),
+			"There need to be as many UVs as positions"
+		);
+		ensure!(
+			self.colors
+				.as_ref()
+				.map_or(true, |c| c.len() == vertex_count),
+			"There need to be as many colors as positions"
+		);
+		ensure!(
+			self.indices.len() % 3 == 0,
+			"Indices need to be a multiple of 3 to make triangles"
+		);
+		ensure!(
+			self.indices.iter().all(|i| (*i as usize) < vertex_count),
+			"Indices can't be past the last vertex"
+		);
+		Ok(())
+	}
+
+	fn smooth_normals(&self) -> Vec<Vec3> {
+		let mut normals = vec![Vec3::ZERO; self.positions.len()];
+		for triangle in self.indices.chunks_exact(3) {
+			let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(self.positions[triangle[i] as usize]));
+			// not normalized so bigger triangles count for more
+			let face_normal = (b - a).cross(c - a);
+			for i in triangle {
+				normals[*i as usize] += face_normal;
+			}
+		}
+		normals.into_iter().map(Vec3::normalize_or_zero).collect()
+	}
+	fn vertices(&self) -> Vec<Vert> {
+		let normals = match &self.normals {
+			Some(normals) => normals.iter().copied().map(Vec3::from).collect(),
+			None => self.smooth_normals(),
+		};
+		self.positions
+			.iter()
+			.enumerate()
+			.map(|(i, position)| Vert {
+				pos: Vec3::from(*position),
+				norm: normals[i],
+				uv: self
+					.uvs
+					.as_ref()
+					.map_or([0.0; 2].into(), |uvs| uvs[i].into()),
+				col: self.colors.as_ref().map_or(WHITE_32, |colors| {
+					let [r, g, b, a] = colors[i].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8);
+					Color32 { r, g, b, a }
+				}),
+			})
+			.collect()
+	}
+}
+
+pub struct Mesh {
+	enabled: Arc<AtomicBool>,
+	space: Arc<Spatial>,
+	data: Mutex<Delta<MeshData>>,
+	sk_mesh: OnceCell<SKMesh>,
+	material: Mutex<Option<Arc<Material>>>,
+	pending_material_parameters: Mutex<FxHashMap<String, MaterialParameter>>,
+	pending_material_textures: Mutex<FxHashMap<String, TextureParameter>>,
+	/// Dynamic textures the material samples, kept alive for as long as it does
+	material_dynamic_textures: Mutex<FxHashMap<String, Arc<DynamicTexture>>>,
+	pending_holdout: AtomicBool,
+}
+unsafe impl Send for Mesh {}
+unsafe impl Sync for Mesh {}
+
+impl Mesh {
+	pub fn add_to(node: &Arc<Node>, data: MeshData) -> Result<Arc<Mesh>> {
+		let _ = node
+			.get_aspect::<Spatial>()
+			.unwrap()
+			.bounding_box_calc
+			.set(|node| {
+				let mut bounds = Bounds::default();
+				if let Ok(mesh) = node.get_aspect::<Mesh>() {
+					for position in &mesh.data.lock().positions {
+						bounds = bounds_grow_to_fit_pt(bounds, *position);
+					}
+				}
+				bounds
+			});
+
+		let mut data = Delta::new(data);
+		data.mark_changed();
+		let mesh = MESH_REGISTRY.add(Mesh {
+			enabled: node.enabled.clone(),
+			space: node.get_aspect::<Spatial>()?.clone(),
+			data: Mutex::new(data),
+			sk_mesh: OnceCell::new(),
+			material: Mutex::new(None),
+			pending_material_parameters: Mutex::new(FxHashMap::default()),
+			pending_material_textures: Mutex::new(FxHashMap::default()),
+			material_dynamic_textures: Mutex::new(FxHashMap::default()),
+			pending_holdout: AtomicBool::new(false),
+		});
+		node.add_local_signal("update_mesh", Mesh::update_mesh_flex);
+		node.add_local_signal("set_material_parameter", Mesh::set_material_parameter_flex);
+		node.add_local_signal(
+			"set_material_texture_from_fd",
+			Mesh::set_material_texture_from_fd_flex,
+		);
+		node.add_local_signal(
+			"set_material_dynamic_texture",
+			Mesh::set_material_dynamic_texture_flex,
+		);
+		node.add_local_signal("apply_holdout_material", Mesh::apply_holdout_material_flex);
+		node.add_aspect_raw(mesh.clone());
+
+		Ok(mesh)
+	}
+
+	/// Replace all the vertex data, inline or through an fd like `create_mesh`.
+	fn update_mesh_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let mesh = node.get_aspect::<Mesh>()?;
+		let data: Option<MeshData> = deserialize(&message.data)?;
+		**mesh.data.lock() = MeshData::from_message(data, message.fds)?;
+		Ok(())
+	}
+	fn set_material_parameter_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let mesh = node.get_aspect::<Mesh>()?;
+		let (parameter_name, value): (String, MaterialParameter) = deserialize(&message.data)?;
+		mesh.pending_material_parameters
+			.lock()
+			.insert(parameter_name, value);
+		Ok(())
+	}
+	fn set_material_texture_from_fd_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let mesh = node.get_aspect::<Mesh>()?;
+		let (parameter_name, texture) = TextureParameter::from_fd_message(message)?;
+		mesh.pending_material_textures
+			.lock()
+			.insert(parameter_name, texture);
+		Ok(())
+	}
+	fn set_material_dynamic_texture_flex(
+		node: Arc<Node>,
+		calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let mesh = node.get_aspect::<Mesh>()?;
+		let (parameter_name, texture) =
+			TextureParameter::from_dynamic_texture_message(&calling_client, message)?;
+		mesh.pending_material_textures
+			.lock()
+			.insert(parameter_name, texture);
+		Ok(())
+	}
+	fn apply_holdout_material_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		_message: Message,
+	) -> Result<()> {
+		let mesh = node.get_aspect::<Mesh>()?;
+		mesh.pending_holdout.store(true, Ordering::Relaxed);
+		Ok(())
+	}
+
+	fn draw(&self, sk: &impl StereoKitDraw) {
+		let Some(client) = self.space.node().and_then(|node| node.get_client()) else {
+			return;
+		};
+		let sk_mesh = self.sk_mesh.get_or_init(|| sk.mesh_create());
+		if let Some(data) = self.data.lock().delta() {
+			sk.mesh_set_data(sk_mesh, &data.vertices(), &data.indices, true);
+		}
+
+		let mut material = self.material.lock();
+		let material =
+			material.get_or_insert_with(|| Arc::new(sk.material_copy(Material::DEFAULT)));
+		if self.pending_holdout.swap(false, Ordering::Relaxed) {
+			*material = holdout_material(sk);
+			self.material_dynamic_textures.lock().clear();
+		}
+		for (parameter_name, parameter_value) in self.pending_material_parameters.lock().drain() {
+			// the material could be shared (e.g. holdout) so it's copied instead of changed
+			let new_material = sk.material_copy(material.as_ref().as_ref());
+			parameter_value.apply_to_material(&client, sk, &new_material, &parameter_name);
+			*material = Arc::new(new_material);
+		}
+		let mut dynamic_textures = self.material_dynamic_textures.lock();
+		for (parameter_name, texture) in self.pending_material_textures.lock().drain() {
+			let new_material = sk.material_copy(material.as_ref().as_ref());
+			texture.apply_to_material(sk, &new_material, parameter_name, &mut dynamic_textures);
+			*material = Arc::new(new_material);
+		}
+
+		sk.mesh_draw(
+			sk_mesh,
+			material.as_ref().as_ref(),
+			self.space.global_transform(),
+			WHITE,
+			RenderLayer::LAYER0,
+		);
+	}
+}
+impl Aspect for Mesh {
+	const NAME: &'static str = "Mesh";
+}
+impl Drop for Mesh {
+	fn drop(&mut self) {
+		if let Some(sk_mesh) = self.sk_mesh.take() {
+			destroy_queue::add(sk_mesh);
+		}
+		MESH_REGISTRY.remove(self);
+	}
+}
+
+pub fn draw_all(sk: &impl StereoKitDraw) {
+	for mesh in MESH_REGISTRY.get_valid_contents() {
+		if mesh.enabled.load(Ordering::Relaxed) {
+			mesh.draw(sk);
+		}
+	}
+}
@@ -1,26 +1,40 @@
+pub mod image;
 pub mod lines;
+pub mod mesh;
 pub mod model;
 pub mod shaders;
 pub mod text;
+pub mod texture;
 
-use self::{lines::Lines, model::Model, text::Text};
+use self::{
+	image::{Image, ImageSource, ImageTexture},
+	lines::Lines,
+	mesh::{Mesh, MeshData},
+	model::Model,
+	text::Text,
+	texture::{dmabuf_planes, DmabufPlaneInfo, DmabufTextureInfo, DynamicTexture},
+};
 use super::{
 	spatial::{Spatial, Transform},
-	Node,
+	Message, Node,
 };
-use crate::{
-	core::{client::Client, resource::get_resource_file},
-	create_interface,
+use crate::core::{
+	client::Client, permissions::Capability, resource::get_resource_file, shm::read_fd,
 };
 use color_eyre::eyre::{self, Result};
+use mint::Vector2;
 use parking_lot::Mutex;
-use stardust_xr::values::ResourceID;
+use serde::Deserialize;
+use stardust_xr::{schemas::flex::deserialize, values::ResourceID};
 use std::{ffi::OsStr, path::PathBuf, sync::Arc};
 use stereokit::StereoKitDraw;
 
 // #[instrument(level = "debug", skip(sk))]
 pub fn draw(sk: &impl StereoKitDraw) {
+	texture::update_all(sk);
+	image::draw_all(sk);
 	lines::draw_all(sk);
+	mesh::draw_all(sk);
 	model::draw_all(sk);
 	text::draw_all(sk);
 
@@ -40,11 +54,22 @@ static QUEUED_SKYLIGHT: Mutex<Option<PathBuf>> = Mutex::new(None);
 static QUEUED_SKYTEX: Mutex<Option<PathBuf>> = Mutex::new(None);
 
 stardust_xr_server_codegen::codegen_drawable_protocol!();
-create_interface!(DrawableInterface, DrawableInterfaceAspect, "/drawable");
+pub fn create_interface(client: &Arc<Client>) -> Result<()> {
+	let node = Node::create_path(client, "/drawable", false);
+	<DrawableInterface as DrawableInterfaceAspect>::add_node_members(&node);
+	node.add_local_signal("create_mesh", create_mesh_flex);
+	node.add_local_signal("load_model_from_fd", load_model_from_fd_flex);
+	node.add_local_signal("create_image", create_image_flex);
+	node.add_local_signal("create_shm_texture", create_shm_texture_flex);
+	node.add_local_signal("create_dmabuf_texture", create_dmabuf_texture_flex);
+	node.add_to_scenegraph()?;
+	Ok(())
+}
 
 pub struct DrawableInterface;
 impl DrawableInterfaceAspect for DrawableInterface {
 	fn set_sky_tex(_node: Arc<Node>, calling_client: Arc<Client>, tex: ResourceID) -> Result<()> {
+		calling_client.ensure_permission(Capability::SkyTex)?;
 		let resource_path = get_resource_file(&tex, &calling_client, &[OsStr::new("hdr")])
 			.ok_or(eyre::eyre!("Could not find resource"))?;
 		QUEUED_SKYTEX.lock().replace(resource_path);
@@ -56,6 +81,7 @@ impl DrawableInterfaceAspect for DrawableInterface {
 		calling_client: Arc<Client>,
 		light: ResourceID,
 	) -> Result<()> {
+		calling_client.ensure_permission(Capability::SkyTex)?;
 		let resource_path = get_resource_file(&light, &calling_client, &[OsStr::new("hdr")])
 			.ok_or(eyre::eyre!("Could not find resource"))?;
 		QUEUED_SKYLIGHT.lock().replace(resource_path);
@@ -119,3 +145,149 @@ impl DrawableInterfaceAspect for DrawableInterface {
 		Ok(())
 	}
 }
+
+/// Create a mesh from vertex data, sent inline or (for big meshes) in an fd.
+fn create_mesh_flex(_node: Arc<Node>, calling_client: Arc<Client>, message: Message) -> Result<()> {
+	#[derive(Deserialize)]
+	struct CreateMeshInfo<'a> {
+		name: &'a str,
+		parent_path: &'a str,
+		transform: Transform,
+		mesh: Option<MeshData>,
+	}
+	let info: CreateMeshInfo = deserialize(&message.data)?;
+	let parent = calling_client
+		.get_node("Spatial parent", info.parent_path)?
+		.get_aspect::<Spatial>()?;
+	let transform = info.transform.to_mat4(true, true, true);
+	let mesh_data = MeshData::from_message(info.mesh, message.fds)?;
+
+	let node = Node::create_parent_name(&calling_client, "/drawable/mesh", info.name, true)
+		.add_to_scenegraph()?;
+	Spatial::add_to(&node, Some(parent.clone()), transform, false);
+	Mesh::add_to(&node, mesh_data)?;
+	Ok(())
+}
+
+/// Like `load_model`, but the glTF/GLB bytes are in the message's fd instead of a resource file.
+fn load_model_from_fd_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct LoadModelInfo<'a> {
+		name: &'a str,
+		parent_path: &'a str,
+		transform: Transform,
+	}
+	let info: LoadModelInfo = deserialize(&message.data)?;
+	let parent = calling_client
+		.get_node("Spatial parent", info.parent_path)?
+		.get_aspect::<Spatial>()?;
+	let transform = info.transform.to_mat4(true, true, true);
+	let fd = message
+		.fds
+		.into_iter()
+		.next()
+		.ok_or_else(|| eyre::eyre!("Model needs to be sent in an fd"))?;
+	let model_data = read_fd(fd)?;
+
+	let node = Node::create_parent_name(
+		&calling_client,
+		DrawableInterface::LOAD_MODEL_PARENT_PATH,
+		info.name,
+		true,
+	)
+	.add_to_scenegraph()?;
+	Spatial::add_to(&node, Some(parent.clone()), transform, false);
+	Model::add_to_from_memory(&node, model_data)?;
+	Ok(())
+}
+
+/// Create a quad showing an image file or a dynamic texture, `size` in meters.
+fn create_image_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct CreateImageInfo<'a> {
+		name: &'a str,
+		parent_path: &'a str,
+		transform: Transform,
+		source: ImageSource,
+		size: Vector2<f32>,
+	}
+	let info: CreateImageInfo = deserialize(&message.data)?;
+	let parent = calling_client
+		.get_node("Spatial parent", info.parent_path)?
+		.get_aspect::<Spatial>()?;
+	let transform = info.transform.to_mat4(true, true, true);
+	let texture = ImageTexture::from_source(&calling_client, info.source)?;
+
+	let node = Node::create_parent_name(&calling_client, "/drawable/image", info.name, true)
+		.add_to_scenegraph()?;
+	Spatial::add_to(&node, Some(parent.clone()), transform, false);
+	Image::add_to(&node, texture, info.size)?;
+	Ok(())
+}
+
+/// Create a texture from a shm buffer (e.g. a memfd) the client keeps drawing into, sending `damage` to the texture when it changes.
+fn create_shm_texture_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct CreateShmTextureInfo<'a> {
+		name: &'a str,
+		size: Vector2<u32>,
+		stride: u32,
+		/// DRM fourcc, AB24 or AR24
+		format: u32,
+	}
+	let info: CreateShmTextureInfo = deserialize(&message.data)?;
+	let fd = message
+		.fds
+		.into_iter()
+		.next()
+		.ok_or_else(|| eyre::eyre!("Texture needs to be sent in an fd"))?;
+
+	let node = Node::create_parent_name(&calling_client, "/drawable/texture", info.name, true);
+	DynamicTexture::add_to_shm(&node, info.size, info.stride, info.format, fd)?;
+	node.add_to_scenegraph()?;
+	Ok(())
+}
+
+/// Create a texture from a dmabuf the client renders into, with one fd per plane.
+fn create_dmabuf_texture_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct CreateDmabufTextureInfo<'a> {
+		name: &'a str,
+		size: Vector2<u32>,
+		/// DRM fourcc
+		format: u32,
+		modifier: u64,
+		planes: Vec<DmabufPlaneInfo>,
+	}
+	let info: CreateDmabufTextureInfo = deserialize(&message.data)?;
+	let planes = dmabuf_planes(info.planes, message.fds)?;
+
+	let node = Node::create_parent_name(&calling_client, "/drawable/texture", info.name, true);
+	DynamicTexture::add_to_dmabuf(
+		&node,
+		DmabufTextureInfo {
+			size: info.size,
+			format: info.format,
+			modifier: info.modifier,
+			planes,
+		},
+	)?;
+	node.add_to_scenegraph()?;
+	Ok(())
+}
@@ -1,17 +1,23 @@
+use super::texture::{get_dynamic_texture, DynamicTexture};
 use super::{MaterialParameter, ModelAspect, ModelPartAspect, Node};
 use crate::core::client::Client;
 use crate::core::destroy_queue;
 use crate::core::node_collections::LifeLinkedNodeMap;
 use crate::core::registry::Registry;
 use crate::core::resource::get_resource_file;
+use crate::core::scenegraph::MethodResponseSender;
+use crate::core::shm::read_fd;
 use crate::nodes::spatial::Spatial;
-use crate::nodes::Aspect;
+use crate::nodes::{Aspect, Message};
 use crate::SK_MULTITHREAD;
 use color_eyre::eyre::{eyre, Result};
+use glam::Mat4;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use portable_atomic::{AtomicBool, Ordering};
 use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use stardust_xr::schemas::flex::{deserialize, serialize};
 use stardust_xr::values::ResourceID;
 
 use std::ffi::OsStr;
@@ -19,15 +25,87 @@ use std::path::PathBuf;
 use std::sync::{Arc, Weak};
 use stereokit::named_colors::WHITE;
 use stereokit::{
-	Bounds, Color128, Material, Model as SKModel, RenderLayer, Shader, StereoKitDraw,
+	AnimMode, Bounds, Color128, Material, Model as SKModel, RenderLayer, Shader, Sk, StereoKitDraw,
 	StereoKitMultiThread, Transparency,
 };
 
 static MODEL_REGISTRY: Registry<Model> = Registry::new();
 static HOLDOUT_MATERIAL: OnceCell<Arc<Material>> = OnceCell::new();
 
+/// Cuts a hole in the world, see `apply_holdout_material`.
+pub(super) fn holdout_material(sk: &impl StereoKitMultiThread) -> Arc<Material> {
+	HOLDOUT_MATERIAL
+		.get_or_init(|| {
+			let mat = sk.material_copy(Material::UNLIT);
+			sk.material_set_transparency(&mat, Transparency::None);
+			sk.material_set_color(
+				&mat,
+				"color",
+				stereokit::sys::color128 {
+					r: 0.0,
+					g: 0.0,
+					b: 0.0,
+					a: 0.0,
+				},
+			);
+			Arc::new(mat)
+		})
+		.clone()
+}
+
+/// A texture parameter that isn't a resource file, so can't be a `MaterialParameter`.
+pub(super) enum TextureParameter {
+	/// Encoded image bytes (PNG, JPG...)
+	Data(Vec<u8>),
+	/// Pixels the client keeps updating
+	Dynamic(Arc<DynamicTexture>),
+}
+impl TextureParameter {
+	/// Read the texture sent along with a `set_material_texture_from_fd` signal.
+	pub(super) fn from_fd_message(message: Message) -> Result<(String, Self)> {
+		let parameter_name: String = deserialize(&message.data)?;
+		let fd = message
+			.fds
+			.into_iter()
+			.next()
+			.ok_or_else(|| eyre!("Texture needs to be sent in an fd"))?;
+		Ok((parameter_name, TextureParameter::Data(read_fd(fd)?)))
+	}
+	/// Find the dynamic texture node a `set_material_dynamic_texture` signal points to.
+	pub(super) fn from_dynamic_texture_message(
+		calling_client: &Client,
+		message: Message,
+	) -> Result<(String, Self)> {
+		let (parameter_name, texture_path): (String, String) = deserialize(&message.data)?;
+		let texture = get_dynamic_texture(calling_client, &texture_path)?;
+		Ok((parameter_name, TextureParameter::Dynamic(texture)))
+	}
+
+	/// Dynamic textures end up in `dynamic_textures` so they outlive the material sampling them.
+	pub(super) fn apply_to_material(
+		self,
+		sk: &impl StereoKitDraw,
+		material: &Material,
+		parameter_name: String,
+		dynamic_textures: &mut FxHashMap<String, Arc<DynamicTexture>>,
+	) {
+		match self {
+			TextureParameter::Data(data) => {
+				if let Ok(tex) = sk.tex_create_mem(&data, true, 0) {
+					sk.material_set_texture(material, &parameter_name, &tex);
+					dynamic_textures.remove(&parameter_name);
+				}
+			}
+			TextureParameter::Dynamic(texture) => {
+				sk.material_set_texture(material, &parameter_name, texture.sk_tex(sk));
+				dynamic_textures.insert(parameter_name, texture);
+			}
+		}
+	}
+}
+
 impl MaterialParameter {
-	fn apply_to_material(
+	pub(super) fn apply_to_material(
 		&self,
 		client: &Client,
 		sk: &impl StereoKitMultiThread,
@@ -80,25 +158,14 @@ pub struct ModelPart {
 	space: Arc<Spatial>,
 	model: Weak<Model>,
 	pending_material_parameters: Mutex<FxHashMap<String, MaterialParameter>>,
+	pending_material_textures: Mutex<FxHashMap<String, TextureParameter>>,
+	/// Dynamic textures the part's material samples, kept alive for as long as it does
+	material_dynamic_textures: Mutex<FxHashMap<String, Arc<DynamicTexture>>>,
 	pending_material_replacement: Mutex<Option<Arc<Material>>>,
 }
 impl ModelPart {
 	fn create_for_model(sk: &impl StereoKitMultiThread, model: &Arc<Model>, sk_model: &SKModel) {
-		HOLDOUT_MATERIAL.get_or_init(|| {
-			let mat = sk.material_copy(Material::UNLIT);
-			sk.material_set_transparency(&mat, Transparency::None);
-			sk.material_set_color(
-				&mat,
-				"color",
-				stereokit::sys::color128 {
-					r: 0.0,
-					g: 0.0,
-					b: 0.0,
-					a: 0.0,
-				},
-			);
-			Arc::new(mat)
-		});
+		holdout_material(sk);
 
 		let first_root_part = sk.model_node_get_root(sk_model);
 		let mut current_option_part = Some(first_root_part);
@@ -184,14 +251,54 @@ impl ModelPart {
 			space,
 			model: Arc::downgrade(model),
 			pending_material_parameters: Mutex::new(FxHashMap::default()),
+			pending_material_textures: Mutex::new(FxHashMap::default()),
+			material_dynamic_textures: Mutex::new(FxHashMap::default()),
 			pending_material_replacement: Mutex::new(None),
 		});
 		<ModelPart as ModelPartAspect>::add_node_members(&node);
+		node.add_local_signal(
+			"set_material_texture_from_fd",
+			ModelPart::set_material_texture_from_fd_flex,
+		);
+		node.add_local_signal(
+			"set_material_dynamic_texture",
+			ModelPart::set_material_dynamic_texture_flex,
+		);
 		node.add_aspect_raw(model_part.clone());
 		model.parts.add(id, &node);
 		Some(model_part)
 	}
 
+	/// Like `set_material_parameter` with a texture, but the image's bytes are in an fd.
+	fn set_material_texture_from_fd_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let model_part = node.get_aspect::<ModelPart>()?;
+		let (parameter_name, texture) = TextureParameter::from_fd_message(message)?;
+		model_part
+			.pending_material_textures
+			.lock()
+			.insert(parameter_name, texture);
+		Ok(())
+	}
+	/// Set a texture parameter to a dynamic texture node, so it shows the pixels the client streams into it.
+	fn set_material_dynamic_texture_flex(
+		node: Arc<Node>,
+		calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let model_part = node.get_aspect::<ModelPart>()?;
+		let (parameter_name, texture) =
+			TextureParameter::from_dynamic_texture_message(&calling_client, message)?;
+		model_part
+			.pending_material_textures
+			.lock()
+			.insert(parameter_name, texture);
+		Ok(())
+	}
+
 	pub fn replace_material(&self, replacement: Arc<Material>) {
 		self.pending_material_replacement
 			.lock()
@@ -213,6 +320,7 @@ impl ModelPart {
 		};
 		if let Some(material_replacement) = self.pending_material_replacement.lock().take() {
 			sk.model_node_set_material(sk_model, self.id, material_replacement.as_ref().as_ref());
+			self.material_dynamic_textures.lock().clear();
 		}
 
 		let mut material_parameters = self.pending_material_parameters.lock();
@@ -224,6 +332,15 @@ impl ModelPart {
 			parameter_value.apply_to_material(&client, sk, &new_material, parameter_name.as_str());
 			sk.model_node_set_material(sk_model, self.id, &new_material);
 		}
+		let mut dynamic_textures = self.material_dynamic_textures.lock();
+		for (parameter_name, texture) in self.pending_material_textures.lock().drain() {
+			let Some(material) = sk.model_node_get_material(sk_model, self.id) else {
+				continue;
+			};
+			let new_material = sk.material_copy(material);
+			texture.apply_to_material(sk, &new_material, parameter_name, &mut dynamic_textures);
+			sk.model_node_set_material(sk_model, self.id, &new_material);
+		}
 
 		sk.model_node_set_transform_model(
 			sk_model,
@@ -260,13 +377,58 @@ impl ModelPartAspect for ModelPart {
 	}
 }
 
+/// An animation in the model's glTF, as listed by `get_animations`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnimationInfo {
+	pub name: String,
+	/// In seconds
+	pub duration: f32,
+}
+
+struct AnimationPlayback {
+	index: usize,
+	/// Seconds into the animation
+	time: f32,
+	looping: bool,
+	paused: bool,
+	/// Whether the parts are at `time` yet, so a paused animation poses them once instead of overriding their transforms every frame
+	posed: bool,
+	blend: Option<AnimationBlend>,
+}
+/// Crossfade out of the animation that was playing before, so switching doesn't snap.
+struct AnimationBlend {
+	index: usize,
+	time: f32,
+	duration: f32,
+	elapsed: f32,
+}
+
+fn advance_animation_time(time: f32, delta: f32, duration: f32, looping: bool) -> f32 {
+	let time = time + delta;
+	if looping && duration > 0.0 {
+		time % duration
+	} else {
+		time.min(duration)
+	}
+}
+fn blend_transforms(from: Mat4, to: Mat4, t: f32) -> Mat4 {
+	let (from_scale, from_rotation, from_translation) = from.to_scale_rotation_translation();
+	let (to_scale, to_rotation, to_translation) = to.to_scale_rotation_translation();
+	Mat4::from_scale_rotation_translation(
+		from_scale.lerp(to_scale, t),
+		from_rotation.slerp(to_rotation, t),
+		from_translation.lerp(to_translation, t),
+	)
+}
+
 pub struct Model {
 	self_ref: Weak<Model>,
 	enabled: Arc<AtomicBool>,
 	space: Arc<Spatial>,
-	_resource_id: ResourceID,
 	sk_model: OnceCell<SKModel>,
 	parts: LifeLinkedNodeMap<i32>,
+	animations: Vec<AnimationInfo>,
+	animation: Mutex<Option<AnimationPlayback>>,
 }
 unsafe impl Send for Model {}
 unsafe impl Sync for Model {}
@@ -280,30 +442,250 @@ impl Model {
 		)
 		.ok_or_else(|| eyre!("Resource not found"))?;
 
+		Model::add_to_with(node, |sk| {
+			Ok(sk.model_create_file(pending_model_path.to_str().unwrap(), None::<Shader>)?)
+		})
+	}
+	/// Load a glTF or GLB model from its bytes, for clients that can't share files with the server.
+	pub fn add_to_from_memory(node: &Arc<Node>, data: Vec<u8>) -> Result<Arc<Model>> {
+		// StereoKit picks the format from the file name
+		let file_name = if data.starts_with(b"glTF") {
+			"model.glb"
+		} else {
+			"model.gltf"
+		};
+		Model::add_to_with(node, |sk| {
+			Ok(sk.model_create_mem(file_name, &data, None::<Shader>)?)
+		})
+	}
+	fn add_to_with(
+		node: &Arc<Node>,
+		load: impl FnOnce(&Sk) -> Result<SKModel>,
+	) -> Result<Arc<Model>> {
+		let sk = SK_MULTITHREAD
+			.get()
+			.ok_or_else(|| eyre!("StereoKit is not running, models can't be loaded"))?;
+		let sk_model = sk.model_copy(load(sk)?);
+		let animations = (0..sk.model_anim_count(&sk_model))
+			.map(|index| AnimationInfo {
+				name: sk
+					.model_anim_get_name(&sk_model, index)
+					.unwrap_or_default()
+					.to_string(),
+				duration: sk.model_anim_get_duration(&sk_model, index),
+			})
+			.collect();
+
 		let model = Arc::new_cyclic(|self_ref| Model {
 			self_ref: self_ref.clone(),
 			enabled: node.enabled.clone(),
 			space: node.get_aspect::<Spatial>().unwrap().clone(),
-			_resource_id: resource_id,
 			sk_model: OnceCell::new(),
 			parts: LifeLinkedNodeMap::default(),
+			animations,
+			animation: Mutex::new(None),
 		});
 		MODEL_REGISTRY.add_raw(&model);
 
-		let sk = SK_MULTITHREAD.get().unwrap();
-		let sk_model = sk.model_copy(
-			sk.model_create_file(pending_model_path.to_str().unwrap(), None::<Shader>)?,
-		);
 		ModelPart::create_for_model(sk, &model.self_ref.upgrade().unwrap(), &sk_model);
 		let _ = model.sk_model.set(sk_model);
+		node.add_local_method("get_animations", Model::get_animations_flex);
+		node.add_local_signal("play_animation", Model::play_animation_flex);
+		node.add_local_signal("pause_animation", Model::pause_animation_flex);
+		node.add_local_signal("resume_animation", Model::resume_animation_flex);
+		node.add_local_signal("set_animation_looping", Model::set_animation_looping_flex);
+		node.add_local_signal("seek_animation", Model::seek_animation_flex);
 		node.add_aspect_raw(model.clone());
 		Ok(model)
 	}
 
-	fn draw(&self, sk: &impl StereoKitDraw) {
+	/// Names and durations of every animation in the model.
+	fn get_animations_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		_message: Message,
+		response: MethodResponseSender,
+	) {
+		response.wrap_sync(move || {
+			let model = node.get_aspect::<Model>()?;
+			Ok(serialize(&model.animations)?.into())
+		});
+	}
+	/// Play an animation from the start, fading from the current one over `blend_duration` seconds.
+	fn play_animation_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		#[derive(Deserialize)]
+		struct PlayAnimationInfo {
+			name: String,
+			looping: bool,
+			blend_duration: f32,
+		}
+		let info: PlayAnimationInfo = deserialize(message.as_ref())?;
+		let model = node.get_aspect::<Model>()?;
+		let index = model
+			.animations
+			.iter()
+			.position(|animation| animation.name == info.name)
+			.ok_or_else(|| eyre!("Animation not found"))?;
+
+		let mut animation = model.animation.lock();
+		let blend = animation
+			.take()
+			.filter(|_| info.blend_duration > 0.0)
+			.map(|previous| AnimationBlend {
+				index: previous.index,
+				time: previous.time,
+				duration: info.blend_duration,
+				elapsed: 0.0,
+			});
+		*animation = Some(AnimationPlayback {
+			index,
+			time: 0.0,
+			looping: info.looping,
+			paused: false,
+			posed: false,
+			blend,
+		});
+		Ok(())
+	}
+	fn pause_animation_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		_message: Message,
+	) -> Result<()> {
+		let model = node.get_aspect::<Model>()?;
+		if let Some(animation) = model.animation.lock().as_mut() {
+			animation.paused = true;
+		}
+		Ok(())
+	}
+	fn resume_animation_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		_message: Message,
+	) -> Result<()> {
+		let model = node.get_aspect::<Model>()?;
+		if let Some(animation) = model.animation.lock().as_mut() {
+			animation.paused = false;
+		}
+		Ok(())
+	}
+	fn set_animation_looping_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let looping: bool = deserialize(message.as_ref())?;
+		let model = node.get_aspect::<Model>()?;
+		if let Some(animation) = model.animation.lock().as_mut() {
+			animation.looping = looping;
+		}
+		Ok(())
+	}
+	/// Jump to `time` seconds into the playing animation, finishing any blend.
+	fn seek_animation_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let time: f32 = deserialize(message.as_ref())?;
+		let model = node.get_aspect::<Model>()?;
+		if let Some(animation) = model.animation.lock().as_mut() {
+			let duration = model.animations[animation.index].duration;
+			animation.time = time.clamp(0.0, duration);
+			animation.posed = false;
+			animation.blend = None;
+		}
+		Ok(())
+	}
+
+	/// Pose the model for this frame of its animation, then move the parts to match so clients see where they are.
+	/// A non-looping animation is dropped once it finishes so the parts are left free to move.
+	fn update_animation(&self, sk: &impl StereoKitDraw, sk_model: &SKModel, delta: f32) {
+		let mut animation_lock = self.animation.lock();
+		let Some(animation) = animation_lock.as_mut() else {
+			return;
+		};
+		if animation.paused && animation.posed && animation.blend.is_none() {
+			return;
+		}
+		let duration = self.animations[animation.index].duration;
+		let mut finished = false;
+		if !animation.paused {
+			animation.time =
+				advance_animation_time(animation.time, delta, duration, animation.looping);
+			if !animation.looping && animation.time >= duration {
+				finished = true;
+			}
+			if let Some(blend) = animation.blend.as_mut() {
+				let blend_duration = self.animations[blend.index].duration;
+				blend.time = advance_animation_time(blend.time, delta, blend_duration, true);
+				blend.elapsed += delta;
+			}
+		}
+		if animation
+			.blend
+			.as_ref()
+			.is_some_and(|blend| blend.elapsed >= blend.duration)
+		{
+			animation.blend = None;
+		}
+
+		let parts: Vec<Arc<ModelPart>> = self
+			.parts
+			.nodes()
+			.into_iter()
+			.filter_map(|node| node.get_aspect::<ModelPart>().ok())
+			.collect();
+		// the animation being faded out is posed first so the new one is left active for the next frame
+		let blend_from = animation.blend.as_ref().map(|blend| {
+			sk.model_play_anim_idx(sk_model, blend.index as i32, AnimMode::Manual);
+			sk.model_set_anim_time(sk_model, blend.time);
+			let transforms: Vec<Mat4> = parts
+				.iter()
+				.map(|part| sk.model_node_get_transform_local(sk_model, part.id))
+				.collect();
+			(transforms, blend.elapsed / blend.duration)
+		});
+		sk.model_play_anim_idx(sk_model, animation.index as i32, AnimMode::Manual);
+		sk.model_set_anim_time(sk_model, animation.time);
+		for (i, part) in parts.iter().enumerate() {
+			let mut transform = sk.model_node_get_transform_local(sk_model, part.id);
+			if let Some((from_transforms, t)) = &blend_from {
+				transform = blend_transforms(from_transforms[i], transform, *t);
+				sk.model_node_set_transform_local(sk_model, part.id, transform);
+			}
+			part.space.set_local_transform(transform);
+		}
+		animation.posed = true;
+
+		let name = self.animations[animation.index].name.clone();
+		if finished {
+			*animation_lock = None;
+		}
+		drop(animation_lock);
+		if finished {
+			self.send_animation_finished(name);
+		}
+	}
+	fn send_animation_finished(&self, name: String) {
+		let Some(node) = self.space.node() else {
+			return;
+		};
+		let Ok(message) = serialize(name) else {
+			return;
+		};
+		let _ = node.send_remote_signal("animation_finished", message);
+	}
+
+	fn draw(&self, sk: &impl StereoKitDraw, delta: f32) {
 		let Some(sk_model) = self.sk_model.get() else {
 			return;
 		};
+		self.update_animation(sk, sk_model, delta);
 		for model_node_node in self.parts.nodes() {
 			if let Ok(model_node) = model_node_node.get_aspect::<ModelPart>() {
 				model_node.update(sk);
@@ -332,9 +714,10 @@ impl Drop for Model {
 }
 
 pub fn draw_all(sk: &impl StereoKitDraw) {
+	let delta = sk.time_elapsed_unscaled() as f32;
 	for model in MODEL_REGISTRY.get_valid_contents() {
 		if model.enabled.load(Ordering::Relaxed) {
-			model.draw(sk);
+			model.draw(sk, delta);
 		}
 	}
 }
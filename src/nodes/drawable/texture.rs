@@ -0,0 +1,312 @@
+use crate::{
+	core::{client::Client, destroy_queue, registry::Registry},
+	nodes::{Aspect, Message, Node},
+};
+use color_eyre::eyre::{bail, ensure, eyre, Result};
+use mint::Vector2;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use portable_atomic::{AtomicBool, Ordering};
+use serde::Deserialize;
+use stardust_xr::schemas::flex::deserialize;
+use std::{
+	any::Any,
+	ffi::c_void,
+	fs::File,
+	io,
+	os::{fd::OwnedFd, unix::fs::FileExt},
+	sync::Arc,
+};
+use stereokit::{StereoKitDraw, Tex, TextureAddress, TextureFormat, TextureType};
+
+static DYNAMIC_TEXTURE_REGISTRY: Registry<DynamicTexture> = Registry::new();
+
+/// DRM fourcc for RGBA byte order, the same as frames sent to clients
+pub const FORMAT_ABGR8888: u32 = u32::from_le_bytes(*b"AB24");
+/// DRM fourcc for BGRA byte order
+pub const FORMAT_ARGB8888: u32 = u32::from_le_bytes(*b"AR24");
+
+/// Part of a texture that has new pixels, in pixels from the top left.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DamageRegion {
+	pub origin: Vector2<u32>,
+	pub size: Vector2<u32>,
+}
+
+pub struct DmabufPlane {
+	pub fd: OwnedFd,
+	pub offset: u32,
+	pub stride: u32,
+}
+/// Everything needed to import a dmabuf, without depending on the wayland renderer.
+pub struct DmabufTextureInfo {
+	pub size: Vector2<u32>,
+	/// DRM fourcc
+	pub format: u32,
+	pub modifier: u64,
+	pub planes: Vec<DmabufPlane>,
+}
+
+enum TextureSource {
+	Shm {
+		file: File,
+		stride: u32,
+		/// Tightly packed copy of the buffer, updated where it's damaged
+		pixels: Mutex<Vec<u8>>,
+		dirty: AtomicBool,
+	},
+	/// Taken by the wayland renderer once it's imported, after that the GPU sees the client's drawing directly
+	Dmabuf(Mutex<Option<DmabufTextureInfo>>),
+}
+
+/// Pixels a client keeps drawing into, from a shm buffer or dmabuf, so video frames and custom UI can be shown on materials and images.
+pub struct DynamicTexture {
+	size: Vector2<u32>,
+	format: u32,
+	source: TextureSource,
+	sk_tex: OnceCell<Tex>,
+	/// The renderer's import of the dmabuf, which has to live as long as the texture uses it
+	imported: Mutex<Option<Box<dyn Any + Send + Sync>>>,
+}
+unsafe impl Send for DynamicTexture {}
+unsafe impl Sync for DynamicTexture {}
+
+impl DynamicTexture {
+	fn add_to(
+		node: &Node,
+		size: Vector2<u32>,
+		format: u32,
+		source: TextureSource,
+	) -> Arc<DynamicTexture> {
+		let texture = DYNAMIC_TEXTURE_REGISTRY.add(DynamicTexture {
+			size,
+			format,
+			source,
+			sk_tex: OnceCell::new(),
+			imported: Mutex::new(None),
+		});
+		node.add_local_signal("damage", DynamicTexture::damage_flex);
+		node.add_aspect_raw(texture.clone());
+		texture
+	}
+	pub fn add_to_shm(
+		node: &Node,
+		size: Vector2<u32>,
+		stride: u32,
+		format: u32,
+		fd: OwnedFd,
+	) -> Result<()> {
+		ensure!(
+			format == FORMAT_ABGR8888 || format == FORMAT_ARGB8888,
+			"Shm textures need to be AB24 or AR24"
+		);
+		ensure!(size.x > 0 && size.y > 0, "Texture can't be empty");
+		ensure!(stride >= size.x * 4, "Stride is too small for the width");
+		let file = File::from(fd);
+		let buffer_len = stride as u64 * (size.y as u64 - 1) + size.x as u64 * 4;
+		ensure!(
+			file.metadata()?.len() >= buffer_len,
+			"Buffer is too small for the size and stride"
+		);
+
+		let mut pixels = vec![0; size.x as usize * size.y as usize * 4];
+		copy_damage(&file, stride, size, full_region(size), &mut pixels)?;
+		let source = TextureSource::Shm {
+			file,
+			stride,
+			pixels: Mutex::new(pixels),
+			dirty: AtomicBool::new(true),
+		};
+		DynamicTexture::add_to(node, size, format, source);
+		Ok(())
+	}
+	pub fn add_to_dmabuf(node: &Node, info: DmabufTextureInfo) -> Result<()> {
+		ensure!(!info.planes.is_empty(), "Dmabuf needs at least 1 plane");
+		ensure!(info.size.x > 0 && info.size.y > 0, "Texture can't be empty");
+		let (size, format) = (info.size, info.format);
+		DynamicTexture::add_to(
+			node,
+			size,
+			format,
+			TextureSource::Dmabuf(Mutex::new(Some(info))),
+		);
+		Ok(())
+	}
+
+	/// The StereoKit texture that gets the pixels, to be put on materials.
+	pub fn sk_tex(&self, sk: &impl StereoKitDraw) -> &Tex {
+		self.sk_tex.get_or_init(|| {
+			let format = match self.format {
+				FORMAT_ARGB8888 => TextureFormat::BGRA32,
+				_ => TextureFormat::RGBA32,
+			};
+			let tex = sk.tex_create(TextureType::IMAGE_NO_MIPS, format);
+			sk.tex_set_address(&tex, TextureAddress::Clamp);
+			tex
+		})
+	}
+
+	/// Copy the damaged part of the client's buffer so it's uploaded next frame. Damaging dmabufs does nothing since the GPU already has their pixels.
+	fn damage(&self, region: Option<DamageRegion>) -> Result<()> {
+		let TextureSource::Shm {
+			file,
+			stride,
+			pixels,
+			dirty,
+		} = &self.source
+		else {
+			return Ok(());
+		};
+		let region = region.unwrap_or(full_region(self.size));
+		ensure!(
+			region.origin.x.saturating_add(region.size.x) <= self.size.x
+				&& region.origin.y.saturating_add(region.size.y) <= self.size.y,
+			"Damage is outside the texture"
+		);
+		copy_damage(file, *stride, self.size, region, &mut pixels.lock())?;
+		dirty.store(true, Ordering::Relaxed);
+		Ok(())
+	}
+	fn damage_flex(node: Arc<Node>, _calling_client: Arc<Client>, message: Message) -> Result<()> {
+		let texture = node.get_aspect::<DynamicTexture>()?;
+		let region: Option<DamageRegion> = deserialize(message.as_ref())?;
+		texture.damage(region)
+	}
+
+	fn update(&self, sk: &impl StereoKitDraw) {
+		let TextureSource::Shm { pixels, dirty, .. } = &self.source else {
+			return;
+		};
+		if !dirty.swap(false, Ordering::Relaxed) {
+			return;
+		}
+		let sk_tex = self.sk_tex(sk);
+		let mut pixels = pixels.lock();
+		unsafe {
+			stereokit::sys::tex_set_colors(
+				sk_tex.0.as_ptr(),
+				self.size.x as i32,
+				self.size.y as i32,
+				pixels.as_mut_ptr() as *mut c_void,
+			);
+		}
+	}
+
+	/// Dmabufs that haven't been imported yet, for the wayland renderer.
+	pub fn take_pending_dmabuf(&self) -> Option<DmabufTextureInfo> {
+		match &self.source {
+			TextureSource::Dmabuf(info) => info.lock().take(),
+			TextureSource::Shm { .. } => None,
+		}
+	}
+	/// Point the texture at the renderer's import of the dmabuf.
+	///
+	/// # Safety
+	/// `gl_tex` has to be a valid GL texture in StereoKit's context for as long as `import` is alive.
+	pub unsafe fn set_imported(
+		&self,
+		sk: &impl StereoKitDraw,
+		gl_tex: u32,
+		gl_format: i64,
+		import: Box<dyn Any + Send + Sync>,
+	) {
+		sk.tex_set_surface(
+			self.sk_tex(sk),
+			gl_tex as usize as *mut c_void,
+			TextureType::IMAGE_NO_MIPS,
+			gl_format,
+			self.size.x as i32,
+			self.size.y as i32,
+			1,
+			false,
+		);
+		self.imported.lock().replace(import);
+	}
+}
+impl Aspect for DynamicTexture {
+	const NAME: &'static str = "DynamicTexture";
+}
+impl Drop for DynamicTexture {
+	fn drop(&mut self) {
+		if let Some(sk_tex) = self.sk_tex.take() {
+			destroy_queue::add(sk_tex);
+		}
+		if let Some(import) = self.imported.lock().take() {
+			destroy_queue::add(import);
+		}
+		DYNAMIC_TEXTURE_REGISTRY.remove(self);
+	}
+}
+
+fn full_region(size: Vector2<u32>) -> DamageRegion {
+	DamageRegion {
+		origin: [0; 2].into(),
+		size,
+	}
+}
+/// Read the rows of `region` from the client's buffer into the tightly packed `pixels`.
+fn copy_damage(
+	file: &File,
+	stride: u32,
+	size: Vector2<u32>,
+	region: DamageRegion,
+	pixels: &mut [u8],
+) -> io::Result<()> {
+	let row_length = size.x as usize * 4;
+	for row in region.origin.y..region.origin.y + region.size.y {
+		let start = row as usize * row_length + region.origin.x as usize * 4;
+		let end = start + region.size.x as usize * 4;
+		let offset = row as u64 * stride as u64 + region.origin.x as u64 * 4;
+		file.read_exact_at(&mut pixels[start..end], offset)?;
+	}
+	Ok(())
+}
+
+pub fn get_dynamic_texture(calling_client: &Client, path: &str) -> Result<Arc<DynamicTexture>> {
+	calling_client
+		.get_node("Dynamic texture", path)?
+		.get_aspect::<DynamicTexture>()
+		.map_err(|_| eyre!("Node isn't a dynamic texture"))
+}
+
+/// Dmabuf textures waiting to be imported by the wayland renderer.
+pub fn pending_dmabuf_textures() -> Vec<Arc<DynamicTexture>> {
+	DYNAMIC_TEXTURE_REGISTRY
+		.get_valid_contents()
+		.into_iter()
+		.filter(|texture| match &texture.source {
+			TextureSource::Dmabuf(info) => info.lock().is_some(),
+			TextureSource::Shm { .. } => false,
+		})
+		.collect()
+}
+
+pub fn update_all(sk: &impl StereoKitDraw) {
+	for texture in DYNAMIC_TEXTURE_REGISTRY.get_valid_contents() {
+		texture.update(sk);
+	}
+}
+
+/// Where each plane sent with `create_dmabuf_texture` is in its fd, the fds are in the same order.
+#[derive(Deserialize)]
+pub struct DmabufPlaneInfo {
+	pub offset: u32,
+	pub stride: u32,
+}
+pub fn dmabuf_planes(
+	plane_infos: Vec<DmabufPlaneInfo>,
+	fds: Vec<OwnedFd>,
+) -> Result<Vec<DmabufPlane>> {
+	if plane_infos.len() != fds.len() {
+		bail!("Every dmabuf plane needs an fd");
+	}
+	Ok(plane_infos
+		.into_iter()
+		.zip(fds)
+		.map(|(info, fd)| DmabufPlane {
+			fd,
+			offset: info.offset,
+			stride: info.stride,
+		})
+		.collect())
+}
@@ -1,4 +1,4 @@
-use super::{BoxFieldAspect, FieldTrait, Node};
+use super::{shape, BoxFieldAspect, FieldShape, FieldTrait, Node};
 use crate::nodes::fields::FieldAspect;
 use crate::nodes::spatial::Spatial;
 use crate::{core::client::Client, nodes::fields::Field};
@@ -20,6 +20,7 @@ impl BoxField {
 			size: Mutex::new(size.into()),
 		};
 		<BoxField as FieldAspect>::add_node_members(node);
+		shape::add_node_members(node);
 		<BoxField as BoxFieldAspect>::add_node_members(node);
 		node.add_aspect(Field::Box(box_field));
 	}
@@ -30,6 +31,11 @@ impl BoxField {
 }
 
 impl FieldTrait for BoxField {
+	fn shape(&self) -> FieldShape {
+		FieldShape::Box {
+			size: (*self.size.lock()).into(),
+		}
+	}
 	fn local_distance(&self, p: Vec3A) -> f32 {
 		let size = self.size.lock();
 		let q = vec3(
@@ -0,0 +1,119 @@
+use super::shape::CompositeChildShape;
+use super::{shape, Field, FieldShape, FieldTrait, Node};
+use crate::core::client::Client;
+use crate::nodes::fields::FieldAspect;
+use crate::nodes::spatial::Spatial;
+use crate::nodes::Message;
+use color_eyre::eyre::Result;
+use glam::Vec3A;
+use portable_atomic::AtomicF32;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use stardust_xr::schemas::flex::deserialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize_repr, Serialize_repr)]
+#[repr(u32)]
+pub enum CompositeOperation {
+	/// Inside any of the fields
+	Union,
+	/// Inside all of the fields
+	Intersection,
+	/// Inside the first field but none of the others
+	Subtraction,
+}
+
+/// A field made out of other fields, so complex shapes don't need their own primitive.
+pub struct CompositeField {
+	space: Arc<Spatial>,
+	operation: CompositeOperation,
+	/// How far the blend between fields reaches, 0 for sharp edges
+	smoothness: AtomicF32,
+	fields: Vec<Arc<Field>>,
+}
+
+impl CompositeField {
+	pub fn add_to(
+		node: &Arc<Node>,
+		operation: CompositeOperation,
+		smoothness: f32,
+		fields: Vec<Arc<Field>>,
+	) {
+		let composite_field = CompositeField {
+			space: node.get_aspect::<Spatial>().unwrap().clone(),
+			operation,
+			smoothness: AtomicF32::new(smoothness.max(0.0)),
+			fields,
+		};
+		<CompositeField as FieldAspect>::add_node_members(node);
+		shape::add_node_members(node);
+		node.add_local_signal("set_smoothness", CompositeField::set_smoothness_flex);
+		node.add_aspect(Field::Composite(composite_field));
+	}
+
+	pub fn set_smoothness(&self, smoothness: f32) {
+		self.smoothness
+			.store(smoothness.max(0.0), Ordering::Relaxed);
+	}
+	fn set_smoothness_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let this_field = node.get_aspect::<Field>()?;
+		let Field::Composite(this_field) = &*this_field else {
+			return Ok(());
+		};
+		this_field.set_smoothness(deserialize(message.as_ref())?);
+		Ok(())
+	}
+}
+
+impl FieldTrait for CompositeField {
+	fn shape(&self) -> FieldShape {
+		FieldShape::Composite {
+			operation: self.operation,
+			smoothness: self.smoothness.load(Ordering::Relaxed),
+			fields: self
+				.fields
+				.iter()
+				.map(|field| {
+					CompositeChildShape::new(
+						field.shape(),
+						Spatial::space_to_space_matrix(
+							Some(field.spatial_ref()),
+							Some(self.space.as_ref()),
+						),
+					)
+				})
+				.collect(),
+		}
+	}
+	fn local_distance(&self, p: Vec3A) -> f32 {
+		let k = self.smoothness.load(Ordering::Relaxed);
+		let mut distances = self
+			.fields
+			.iter()
+			.map(|field| field.distance(&self.space, p));
+		let Some(first) = distances.next() else {
+			return f32::MAX;
+		};
+		distances.fold(first, |a, b| match self.operation {
+			CompositeOperation::Union => smooth_min(a, b, k),
+			CompositeOperation::Intersection => -smooth_min(-a, -b, k),
+			CompositeOperation::Subtraction => -smooth_min(-a, b, k),
+		})
+	}
+	fn spatial_ref(&self) -> &Spatial {
+		self.space.as_ref()
+	}
+}
+
+/// Polynomial smooth minimum, see https://iquilezles.org/articles/smin/
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+	if k <= 0.0 {
+		return a.min(b);
+	}
+	let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+	b + (a - b) * h - k * h * (1.0 - h)
+}
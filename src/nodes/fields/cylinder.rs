@@ -1,4 +1,4 @@
-use super::{CylinderFieldAspect, Field, FieldTrait, Node};
+use super::{shape, CylinderFieldAspect, Field, FieldShape, FieldTrait, Node};
 use crate::core::client::Client;
 use crate::nodes::fields::FieldAspect;
 use crate::nodes::spatial::Spatial;
@@ -23,6 +23,7 @@ impl CylinderField {
 			radius: AtomicF32::new(radius.abs()),
 		};
 		<CylinderField as FieldAspect>::add_node_members(node);
+		shape::add_node_members(node);
 		<CylinderField as CylinderFieldAspect>::add_node_members(node);
 		node.add_aspect(Field::Cylinder(cylinder_field));
 	}
@@ -33,6 +34,12 @@ impl CylinderField {
 	}
 }
 impl FieldTrait for CylinderField {
+	fn shape(&self) -> FieldShape {
+		FieldShape::Cylinder {
+			length: self.length.load(Ordering::Relaxed),
+			radius: self.radius.load(Ordering::Relaxed),
+		}
+	}
 	fn local_distance(&self, p: Vec3A) -> f32 {
 		let radius = self.radius.load(Ordering::Relaxed);
 		let length = self.length.load(Ordering::Relaxed);
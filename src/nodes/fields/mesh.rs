@@ -0,0 +1,384 @@
+use super::{shape, Field, FieldShape, FieldTrait, Node};
+use crate::nodes::fields::FieldAspect;
+use crate::nodes::spatial::Spatial;
+use color_eyre::eyre::{eyre, Result};
+use glam::{vec3a, Mat4, Vec3A};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Triangles per BVH leaf
+const MAX_LEAF_SIZE: usize = 4;
+/// Direction rays are cast in to check if a point is inside the mesh, slightly off-axis to avoid hitting edges exactly
+const INSIDE_RAY_DIRECTION: [f32; 3] = [0.5773, 0.5774, 0.5775];
+
+/// Exact signed distance to the triangles of a glTF model.
+///
+/// The sign comes from counting how many triangles a ray from the point crosses, which assumes the mesh is closed and watertight.
+/// Holes, open edges or self-intersections make points near them come out on the wrong side.
+pub struct MeshField {
+	space: Arc<Spatial>,
+	bvh: Bvh,
+}
+
+impl MeshField {
+	/// Loading and building the BVH can fail or take a while, so it's done before the node exists.
+	pub fn load(model_path: &Path) -> Result<Bvh> {
+		Ok(Bvh::new(load_triangles(model_path)?))
+	}
+	pub fn add_to(node: &Arc<Node>, bvh: Bvh) {
+		let mesh_field = MeshField {
+			space: node.get_aspect::<Spatial>().unwrap().clone(),
+			bvh,
+		};
+		<MeshField as FieldAspect>::add_node_members(node);
+		shape::add_node_members(node);
+		node.add_aspect(Field::Mesh(mesh_field));
+	}
+}
+
+impl FieldTrait for MeshField {
+	fn shape(&self) -> FieldShape {
+		let bounds = self.bvh.nodes[0].bounds();
+		FieldShape::Mesh {
+			bounds_min: bounds.min.into(),
+			bounds_max: bounds.max.into(),
+		}
+	}
+	fn local_distance(&self, p: Vec3A) -> f32 {
+		let distance = self.bvh.closest_distance_squared(p).sqrt();
+		if self.bvh.ray_hit_count(p, INSIDE_RAY_DIRECTION.into()) % 2 == 1 {
+			-distance
+		} else {
+			distance
+		}
+	}
+	fn spatial_ref(&self) -> &Spatial {
+		self.space.as_ref()
+	}
+}
+
+fn load_triangles(path: &Path) -> Result<Vec<Triangle>> {
+	let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+	let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+	let scene = document
+		.default_scene()
+		.or_else(|| document.scenes().next())
+		.ok_or_else(|| eyre!("Model has no scenes"))?;
+
+	let mut triangles = Vec::new();
+	for node in scene.nodes() {
+		add_node_triangles(&node, Mat4::IDENTITY, &buffers, &mut triangles);
+	}
+	if triangles.is_empty() {
+		return Err(eyre!("Model has no triangles"));
+	}
+	Ok(triangles)
+}
+fn add_node_triangles(
+	node: &gltf::Node,
+	parent_transform: Mat4,
+	buffers: &[gltf::buffer::Data],
+	triangles: &mut Vec<Triangle>,
+) {
+	let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+	if let Some(mesh) = node.mesh() {
+		for primitive in mesh
+			.primitives()
+			.filter(|p| p.mode() == gltf::mesh::Mode::Triangles)
+		{
+			let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+			let Some(positions) = reader.read_positions() else {
+				continue;
+			};
+			let positions: Vec<Vec3A> = positions
+				.map(|p| transform.transform_point3a(p.into()))
+				.collect();
+			let indices: Vec<u32> = match reader.read_indices() {
+				Some(indices) => indices.into_u32().collect(),
+				None => (0..positions.len() as u32).collect(),
+			};
+			triangles.extend(
+				indices
+					.chunks_exact(3)
+					.filter_map(|i| {
+						Some(Triangle {
+							a: *positions.get(i[0] as usize)?,
+							b: *positions.get(i[1] as usize)?,
+							c: *positions.get(i[2] as usize)?,
+						})
+					})
+					.filter(|t| !t.is_degenerate()),
+			);
+		}
+	}
+	for child in node.children() {
+		add_node_triangles(&child, transform, buffers, triangles);
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Triangle {
+	a: Vec3A,
+	b: Vec3A,
+	c: Vec3A,
+}
+impl Triangle {
+	fn is_degenerate(&self) -> bool {
+		(self.b - self.a).cross(self.c - self.a).length_squared() <= f32::EPSILON * f32::EPSILON
+	}
+	fn centroid(&self) -> Vec3A {
+		(self.a + self.b + self.c) / 3.0
+	}
+	fn bounds(&self) -> Aabb {
+		Aabb {
+			min: self.a.min(self.b).min(self.c),
+			max: self.a.max(self.b).max(self.c),
+		}
+	}
+
+	/// From Real-Time Collision Detection by Christer Ericson, 5.1.5
+	fn closest_point(&self, p: Vec3A) -> Vec3A {
+		let (a, b, c) = (self.a, self.b, self.c);
+		let ab = b - a;
+		let ac = c - a;
+		let ap = p - a;
+		let d1 = ab.dot(ap);
+		let d2 = ac.dot(ap);
+		if d1 <= 0.0 && d2 <= 0.0 {
+			return a;
+		}
+
+		let bp = p - b;
+		let d3 = ab.dot(bp);
+		let d4 = ac.dot(bp);
+		if d3 >= 0.0 && d4 <= d3 {
+			return b;
+		}
+
+		let vc = d1 * d4 - d3 * d2;
+		if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+			let v = d1 / (d1 - d3);
+			return a + ab * v;
+		}
+
+		let cp = p - c;
+		let d5 = ab.dot(cp);
+		let d6 = ac.dot(cp);
+		if d6 >= 0.0 && d5 <= d6 {
+			return c;
+		}
+
+		let vb = d5 * d2 - d1 * d6;
+		if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+			let w = d2 / (d2 - d6);
+			return a + ac * w;
+		}
+
+		let va = d3 * d6 - d5 * d4;
+		if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+			let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+			return b + (c - b) * w;
+		}
+
+		let denom = 1.0 / (va + vb + vc);
+		let v = vb * denom;
+		let w = vc * denom;
+		a + ab * v + ac * w
+	}
+
+	/// Möller–Trumbore, only counting hits in front of the origin
+	fn ray_hits(&self, origin: Vec3A, direction: Vec3A) -> bool {
+		let edge_1 = self.b - self.a;
+		let edge_2 = self.c - self.a;
+		let h = direction.cross(edge_2);
+		let det = edge_1.dot(h);
+		if det.abs() < f32::EPSILON {
+			return false;
+		}
+		let inv_det = 1.0 / det;
+		let s = origin - self.a;
+		let u = inv_det * s.dot(h);
+		if !(0.0..=1.0).contains(&u) {
+			return false;
+		}
+		let q = s.cross(edge_1);
+		let v = inv_det * direction.dot(q);
+		if v < 0.0 || u + v > 1.0 {
+			return false;
+		}
+		inv_det * edge_2.dot(q) > 0.0
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Aabb {
+	min: Vec3A,
+	max: Vec3A,
+}
+impl Aabb {
+	fn empty() -> Self {
+		Aabb {
+			min: Vec3A::splat(f32::MAX),
+			max: Vec3A::splat(f32::MIN),
+		}
+	}
+	fn union(self, other: Aabb) -> Aabb {
+		Aabb {
+			min: self.min.min(other.min),
+			max: self.max.max(other.max),
+		}
+	}
+	fn grow(self, p: Vec3A) -> Aabb {
+		Aabb {
+			min: self.min.min(p),
+			max: self.max.max(p),
+		}
+	}
+	fn distance_squared(&self, p: Vec3A) -> f32 {
+		let outside = (self.min - p).max(p - self.max).max(Vec3A::ZERO);
+		outside.length_squared()
+	}
+	fn ray_intersects(&self, origin: Vec3A, inv_direction: Vec3A) -> bool {
+		let t1 = (self.min - origin) * inv_direction;
+		let t2 = (self.max - origin) * inv_direction;
+		let t_min = t1.min(t2).max_element();
+		let t_max = t1.max(t2).min_element();
+		t_max >= t_min.max(0.0)
+	}
+}
+
+enum BvhNode {
+	Leaf {
+		bounds: Aabb,
+		start: usize,
+		end: usize,
+	},
+	Branch {
+		bounds: Aabb,
+		left: usize,
+		right: usize,
+	},
+}
+impl BvhNode {
+	fn bounds(&self) -> &Aabb {
+		match self {
+			BvhNode::Leaf { bounds, .. } => bounds,
+			BvhNode::Branch { bounds, .. } => bounds,
+		}
+	}
+}
+
+pub struct Bvh {
+	nodes: Vec<BvhNode>,
+	triangles: Vec<Triangle>,
+}
+impl Bvh {
+	fn new(mut triangles: Vec<Triangle>) -> Self {
+		let mut nodes = Vec::with_capacity(triangles.len() / MAX_LEAF_SIZE * 2 + 1);
+		let triangle_count = triangles.len();
+		Self::build(&mut nodes, &mut triangles, 0, triangle_count);
+		Bvh { nodes, triangles }
+	}
+	fn build(
+		nodes: &mut Vec<BvhNode>,
+		triangles: &mut [Triangle],
+		start: usize,
+		end: usize,
+	) -> usize {
+		let bounds = triangles[start..end]
+			.iter()
+			.fold(Aabb::empty(), |bounds, t| bounds.union(t.bounds()));
+		let index = nodes.len();
+		if end - start <= MAX_LEAF_SIZE {
+			nodes.push(BvhNode::Leaf { bounds, start, end });
+			return index;
+		}
+
+		// split at the median centroid along the axis the centroids are most spread out on
+		let centroid_bounds = triangles[start..end]
+			.iter()
+			.fold(Aabb::empty(), |bounds, t| bounds.grow(t.centroid()));
+		let extent = centroid_bounds.max - centroid_bounds.min;
+		let axis = if extent.x >= extent.y && extent.x >= extent.z {
+			0
+		} else if extent.y >= extent.z {
+			1
+		} else {
+			2
+		};
+		let mid = (start + end) / 2;
+		triangles[start..end].select_nth_unstable_by(mid - start, |a, b| {
+			a.centroid()[axis].total_cmp(&b.centroid()[axis])
+		});
+
+		nodes.push(BvhNode::Leaf {
+			bounds,
+			start: 0,
+			end: 0,
+		});
+		let left = Self::build(nodes, triangles, start, mid);
+		let right = Self::build(nodes, triangles, mid, end);
+		nodes[index] = BvhNode::Branch {
+			bounds,
+			left,
+			right,
+		};
+		index
+	}
+
+	fn closest_distance_squared(&self, p: Vec3A) -> f32 {
+		let mut closest = f32::MAX;
+		let mut stack = vec![0];
+		while let Some(index) = stack.pop() {
+			let node = &self.nodes[index];
+			if node.bounds().distance_squared(p) >= closest {
+				continue;
+			}
+			match node {
+				BvhNode::Leaf { start, end, .. } => {
+					for triangle in &self.triangles[*start..*end] {
+						closest = closest.min(triangle.closest_point(p).distance_squared(p));
+					}
+				}
+				BvhNode::Branch { left, right, .. } => {
+					// visit the closer child first so the further one is more likely to get culled
+					let left_distance = self.nodes[*left].bounds().distance_squared(p);
+					let right_distance = self.nodes[*right].bounds().distance_squared(p);
+					if left_distance < right_distance {
+						stack.push(*right);
+						stack.push(*left);
+					} else {
+						stack.push(*left);
+						stack.push(*right);
+					}
+				}
+			}
+		}
+		closest
+	}
+
+	fn ray_hit_count(&self, origin: Vec3A, direction: Vec3A) -> usize {
+		let inv_direction = vec3a(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+		let mut hits = 0;
+		let mut stack = vec![0];
+		while let Some(index) = stack.pop() {
+			let node = &self.nodes[index];
+			if !node.bounds().ray_intersects(origin, inv_direction) {
+				continue;
+			}
+			match node {
+				BvhNode::Leaf { start, end, .. } => {
+					hits += self.triangles[*start..*end]
+						.iter()
+						.filter(|t| t.ray_hits(origin, direction))
+						.count();
+				}
+				BvhNode::Branch { left, right, .. } => {
+					stack.push(*left);
+					stack.push(*right);
+				}
+			}
+		}
+		hits
+	}
+}
@@ -1,30 +1,46 @@
 pub mod r#box;
+mod composite;
 mod cylinder;
+mod mesh;
+mod shape;
 mod sphere;
 mod torus;
 
+use self::composite::{CompositeField, CompositeOperation};
 use self::cylinder::CylinderField;
+use self::mesh::MeshField;
 use self::r#box::BoxField;
+pub use self::shape::{send_changes, FieldShape};
 use self::sphere::SphereField;
 use self::torus::TorusField;
 
 use super::alias::AliasInfo;
 use super::spatial::Spatial;
-use super::{Aspect, Node};
+use super::{Aspect, Message, Node};
 use crate::core::client::Client;
-use crate::create_interface;
+use crate::core::resource::get_resource_file;
+use crate::core::scenegraph::MethodResponseSender;
 use crate::nodes::spatial::Transform;
-use color_eyre::eyre::Result;
-use glam::{vec2, vec3a, Mat4, Vec3, Vec3A};
+use color_eyre::eyre::{ensure, eyre, Result};
+use glam::{vec2, vec3a, Mat3A, Mat4, Vec3, Vec3A};
 use mint::Vector3;
 use once_cell::sync::Lazy;
+use serde::Deserialize;
+use stardust_xr::schemas::flex::deserialize;
+use stardust_xr::values::ResourceID;
+use std::ffi::OsStr;
 use std::ops::Deref;
 use std::sync::Arc;
 
-// TODO: get SDFs working properly with non-uniform scale and so on, output distance relative to the spatial it's compared against
-
 pub static FIELD_ALIAS_INFO: Lazy<AliasInfo> = Lazy::new(|| AliasInfo {
-	server_methods: vec!["distance", "normal", "closest_point", "ray_march"],
+	server_signals: vec!["subscribe_changes", "unsubscribe_changes"],
+	server_methods: vec![
+		"distance",
+		"normal",
+		"closest_point",
+		"ray_march",
+		"get_shape",
+	],
 	..Default::default()
 });
 
@@ -33,17 +49,21 @@ stardust_xr_server_codegen::codegen_field_protocol!();
 pub trait FieldTrait: Send + Sync + 'static {
 	fn spatial_ref(&self) -> &Spatial;
 
+	fn shape(&self) -> FieldShape;
+
 	fn local_distance(&self, p: Vec3A) -> f32;
+	/// Points out of the field, found with central differences `r` apart
 	fn local_normal(&self, p: Vec3A, r: f32) -> Vec3A {
-		let d = self.local_distance(p);
 		let e = vec2(r, 0_f32);
 
-		let n = vec3a(d, d, d)
-			- vec3a(
-				self.local_distance(vec3a(e.x, e.y, e.y)),
-				self.local_distance(vec3a(e.y, e.x, e.y)),
-				self.local_distance(vec3a(e.y, e.y, e.x)),
-			);
+		let n = vec3a(
+			self.local_distance(p + vec3a(e.x, e.y, e.y))
+				- self.local_distance(p - vec3a(e.x, e.y, e.y)),
+			self.local_distance(p + vec3a(e.y, e.x, e.y))
+				- self.local_distance(p - vec3a(e.y, e.x, e.y)),
+			self.local_distance(p + vec3a(e.y, e.y, e.x))
+				- self.local_distance(p - vec3a(e.y, e.y, e.x)),
+		);
 
 		n.normalize()
 	}
@@ -51,19 +71,40 @@ pub trait FieldTrait: Send + Sync + 'static {
 		p - (self.local_normal(p, r) * self.local_distance(p))
 	}
 
+	/// Local distances are in the field's own units, so with any scale between the spaces they'd be stretched.
+	/// Instead find the closest point locally and measure to it in the reference space.
+	///
+	/// This is exact for uniform scale. Under non-uniform scale the closest point in the field's space isn't always the
+	/// closest in the reference space, so the result is only an upper bound on how far away the surface is (exact along
+	/// the scaled axes). It takes about 9 evaluations of the field, so it's only done once per query.
+	fn distance_in_space(&self, reference_to_local_space: Mat4, p: Vec3A) -> f32 {
+		let local_p = reference_to_local_space.transform_point3a(p);
+		let local_distance = self.local_distance(local_p);
+		let closest_point = reference_to_local_space
+			.inverse()
+			.transform_point3a(self.local_closest_point(local_p, 0.001));
+		let distance = closest_point.distance(p);
+		if distance.is_finite() {
+			distance.copysign(local_distance)
+		} else {
+			// the closest point isn't well defined (e.g. the center of a sphere) so just undo the scale
+			let (scale, _, _) = reference_to_local_space.to_scale_rotation_translation();
+			local_distance / scale.max_element()
+		}
+	}
+
 	fn distance(&self, reference_space: &Spatial, p: Vec3A) -> f32 {
 		let reference_to_local_space =
 			Spatial::space_to_space_matrix(Some(reference_space), Some(self.spatial_ref()));
-		let local_p = reference_to_local_space.transform_point3a(p);
-		self.local_distance(local_p)
+		self.distance_in_space(reference_to_local_space, p)
 	}
 	fn normal(&self, reference_space: &Spatial, p: Vec3A, r: f32) -> Vec3A {
 		let reference_to_local_space =
 			Spatial::space_to_space_matrix(Some(reference_space), Some(self.spatial_ref()));
 		let local_p = reference_to_local_space.transform_point3a(p);
-		reference_to_local_space
-			.inverse()
-			.transform_vector3a(self.local_normal(local_p, r))
+		// normals transform by the inverse transpose, so going back to the reference space is just the transpose
+		(Mat3A::from_mat4(reference_to_local_space).transpose() * self.local_normal(local_p, r))
+			.normalize()
 	}
 	fn closest_point(&self, reference_space: &Spatial, p: Vec3A, r: f32) -> Vec3A {
 		let reference_to_local_space =
@@ -73,7 +114,6 @@ pub trait FieldTrait: Send + Sync + 'static {
 			.inverse()
 			.transform_point3a(self.local_closest_point(local_p, r))
 	}
-
 	fn ray_march(&self, ray: Ray) -> RayMarchResult {
 		let mut result = RayMarchResult {
 			ray_origin: ray.origin.into(),
@@ -86,25 +126,34 @@ pub trait FieldTrait: Send + Sync + 'static {
 
 		let ray_to_field_matrix =
 			Spatial::space_to_space_matrix(Some(&ray.space), Some(self.spatial_ref()));
-		let mut ray_point = ray_to_field_matrix.transform_point3a(ray.origin.into());
-		let ray_direction = ray_to_field_matrix
-			.transform_vector3a(ray.direction.into())
-			.normalize();
+		let mut ray_point: Vec3A = ray.origin.into();
+		let ray_direction = Vec3A::from(ray.direction).normalize();
+		// how far the ray moves in the field's space for every unit it moves in its own
+		let local_march_scale = ray_to_field_matrix
+			.transform_vector3a(ray_direction)
+			.length();
 
+		// marching only takes 1 evaluation of the field per step, the closest point is measured properly at the end
+		let mut min_local_distance = f32::MAX;
+		let mut deepest_point = ray_point;
 		while result.ray_steps < MAX_RAY_STEPS && result.ray_length < MAX_RAY_LENGTH {
-			let distance = self.local_distance(ray_point);
-			let march_distance = distance.clamp(MIN_RAY_MARCH, MAX_RAY_MARCH);
+			let local_distance =
+				self.local_distance(ray_to_field_matrix.transform_point3a(ray_point));
+			if min_local_distance > local_distance {
+				min_local_distance = local_distance;
+				deepest_point = ray_point;
+				result.deepest_point_distance = result.ray_length;
+			}
 
+			// nothing is within the local distance, so the ray can safely go that far along it in the field's space
+			let march_distance =
+				(local_distance / local_march_scale).clamp(MIN_RAY_MARCH, MAX_RAY_MARCH);
 			result.ray_length += march_distance;
 			ray_point += ray_direction * march_distance;
 
-			if result.min_distance > distance {
-				result.deepest_point_distance = result.ray_length;
-				result.min_distance = distance;
-			}
-
 			result.ray_steps += 1;
 		}
+		result.min_distance = self.distance_in_space(ray_to_field_matrix, deepest_point);
 
 		result
 	}
@@ -184,6 +233,8 @@ pub enum Field {
 	Cylinder(CylinderField),
 	Sphere(SphereField),
 	Torus(TorusField),
+	Composite(CompositeField),
+	Mesh(MeshField),
 }
 impl Aspect for Field {
 	const NAME: &'static str = "Field";
@@ -196,6 +247,8 @@ impl Deref for Field {
 			Field::Cylinder(field) => field,
 			Field::Sphere(field) => field,
 			Field::Torus(field) => field,
+			Field::Composite(field) => field,
+			Field::Mesh(field) => field,
 		}
 	}
 }
@@ -266,7 +319,14 @@ impl Deref for Field {
 // 	}
 // }
 
-create_interface!(FieldInterface, FieldInterfaceAspect, "/field");
+pub fn create_interface(client: &Arc<Client>) -> Result<()> {
+	let node = Node::create_path(client, "/field", false);
+	<FieldInterface as FieldInterfaceAspect>::add_node_members(&node);
+	node.add_local_signal("create_composite_field", create_composite_field_flex);
+	node.add_local_method("create_mesh_field", create_mesh_field_flex);
+	node.add_to_scenegraph()?;
+	Ok(())
+}
 pub struct FieldInterface;
 impl FieldInterfaceAspect for FieldInterface {
 	fn create_box_field(
@@ -364,6 +424,77 @@ impl FieldInterfaceAspect for FieldInterface {
 	}
 }
 
+fn create_composite_field_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct CreateCompositeFieldInfo<'a> {
+		name: &'a str,
+		parent_path: &'a str,
+		transform: Transform,
+		operation: CompositeOperation,
+		smoothness: f32,
+		#[serde(borrow)]
+		field_paths: Vec<&'a str>,
+	}
+	let info: CreateCompositeFieldInfo = deserialize(message.as_ref())?;
+	let parent = calling_client
+		.get_node("Spatial parent", info.parent_path)?
+		.get_aspect::<Spatial>()?;
+	let transform = info.transform.to_mat4(true, true, false);
+	let fields = info
+		.field_paths
+		.into_iter()
+		.map(|path| find_field(&calling_client, path))
+		.collect::<Result<Vec<_>>>()?;
+	ensure!(!fields.is_empty(), "Composite fields need at least 1 field");
+
+	let node =
+		Node::create_parent_name(&calling_client, "/field", info.name, true).add_to_scenegraph()?;
+	Spatial::add_to(&node, Some(parent.clone()), transform, false);
+	CompositeField::add_to(&node, info.operation, info.smoothness, fields);
+	Ok(())
+}
+
+/// Resolves once the model is parsed, its BVH built and the field's node added, or with why it couldn't be loaded.
+/// The loading happens off the client's message loop so big models don't hold up its other messages.
+fn create_mesh_field_flex(
+	_node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+	response: MethodResponseSender,
+) {
+	response.wrap_async(async move {
+		#[derive(Deserialize)]
+		struct CreateMeshFieldInfo<'a> {
+			name: &'a str,
+			parent_path: &'a str,
+			transform: Transform,
+			model: ResourceID,
+		}
+		let info: CreateMeshFieldInfo = deserialize(message.as_ref())?;
+		let parent = calling_client
+			.get_node("Spatial parent", info.parent_path)?
+			.get_aspect::<Spatial>()?;
+		let transform = info.transform.to_mat4(true, true, false);
+		let model_path = get_resource_file(
+			&info.model,
+			&calling_client,
+			&[OsStr::new("glb"), OsStr::new("gltf")],
+		)
+		.ok_or_else(|| eyre!("Resource not found"))?;
+
+		let bvh = tokio::task::spawn_blocking(move || MeshField::load(&model_path)).await??;
+		let node = Node::create_parent_name(&calling_client, "/field", info.name, true)
+			.add_to_scenegraph()?;
+		Spatial::add_to(&node, Some(parent), transform, false);
+		MeshField::add_to(&node, bvh);
+		Ok(((), Vec::new()))
+	});
+}
+
 pub fn find_field(client: &Client, path: &str) -> Result<Arc<Field>> {
 	client.get_node("Field", path)?.get_aspect::<Field>()
 }
@@ -0,0 +1,204 @@
+use super::composite::CompositeOperation;
+use super::Field;
+use crate::core::client::Client;
+use crate::core::registry::Registry;
+use crate::core::scenegraph::MethodResponseSender;
+use crate::nodes::spatial::{Spatial, Transform};
+use crate::nodes::{Aspect, Message, Node};
+use color_eyre::eyre::Result;
+use glam::Mat4;
+use mint::{Quaternion, Vector3};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use stardust_xr::schemas::flex::{deserialize, serialize};
+use std::sync::{Arc, Weak};
+
+static SUBSCRIPTIONS: Registry<FieldSubscription> = Registry::new();
+
+/// The primitive a field is made of and its parameters, so clients can draw it without sampling distances.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldShape {
+	Box {
+		size: Vector3<f32>,
+	},
+	Cylinder {
+		length: f32,
+		radius: f32,
+	},
+	Sphere {
+		radius: f32,
+	},
+	Torus {
+		radius_a: f32,
+		radius_b: f32,
+	},
+	Composite {
+		operation: CompositeOperation,
+		smoothness: f32,
+		fields: Vec<CompositeChildShape>,
+	},
+	/// Only the bounding box, the triangles themselves are too much to send around
+	Mesh {
+		bounds_min: Vector3<f32>,
+		bounds_max: Vector3<f32>,
+	},
+}
+
+/// One of the fields inside a composite, placed relative to the composite itself.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompositeChildShape {
+	pub shape: FieldShape,
+	pub position: Vector3<f32>,
+	pub rotation: Quaternion<f32>,
+	pub scale: Vector3<f32>,
+}
+impl CompositeChildShape {
+	pub fn new(shape: FieldShape, child_to_composite: Mat4) -> Self {
+		let (scale, rotation, position) = child_to_composite.to_scale_rotation_translation();
+		CompositeChildShape {
+			shape,
+			position: position.into(),
+			rotation: rotation.into(),
+			scale: scale.into(),
+		}
+	}
+}
+
+pub(super) fn add_node_members(node: &Arc<Node>) {
+	node.add_local_method("get_shape", get_shape_flex);
+	node.add_local_signal("subscribe_changes", subscribe_changes_flex);
+	node.add_local_signal("unsubscribe_changes", unsubscribe_changes_flex);
+}
+
+fn get_shape_flex(
+	node: Arc<Node>,
+	_calling_client: Arc<Client>,
+	_message: Message,
+	response: MethodResponseSender,
+) {
+	response.wrap_sync(move || {
+		let field = node.get_aspect::<Field>()?;
+		Ok(serialize(field.shape())?.into())
+	});
+}
+
+/// The nodes that represent this field to the client, which is the field itself for its owner and any aliases of it otherwise.
+fn client_nodes(node: &Arc<Node>, client: &Arc<Client>) -> Vec<Arc<Node>> {
+	let is_client = |node: &Node| {
+		node.get_client()
+			.map(|node_client| Arc::ptr_eq(&node_client, client))
+			.unwrap_or(false)
+	};
+	let mut nodes: Vec<Arc<Node>> = node
+		.aliases
+		.get_valid_contents()
+		.iter()
+		.filter_map(|alias| alias.node.upgrade())
+		.filter(|alias_node| is_client(alias_node))
+		.collect();
+	if is_client(node) {
+		nodes.push(node.clone());
+	}
+	nodes
+}
+
+/// A client's node for a field that gets `shape_changed` and `transform_changed` sent to it whenever the field changes.
+pub struct FieldSubscription {
+	node: Weak<Node>,
+	field: Weak<Node>,
+	/// Transforms are sent relative to this
+	reference_space: Arc<Spatial>,
+	last_shape: Mutex<Option<FieldShape>>,
+	last_transform: Mutex<Option<Mat4>>,
+}
+impl FieldSubscription {
+	fn send_changes(&self) -> Option<()> {
+		let node = self.node.upgrade()?;
+		let field_node = self.field.upgrade()?;
+		let field = field_node.get_aspect::<Field>().ok()?;
+
+		let shape = field.shape();
+		let mut last_shape = self.last_shape.lock();
+		if last_shape.as_ref() != Some(&shape) {
+			let _ = node.send_remote_signal("shape_changed", serialize(&shape).ok()?);
+			*last_shape = Some(shape);
+		}
+
+		let transform = Spatial::space_to_space_matrix(
+			Some(field.spatial_ref()),
+			Some(self.reference_space.as_ref()),
+		);
+		let mut last_transform = self.last_transform.lock();
+		if !last_transform
+			.map(|last| last.abs_diff_eq(transform, f32::EPSILON))
+			.unwrap_or(false)
+		{
+			let (scale, rotation, position) = transform.to_scale_rotation_translation();
+			let transform_message = Transform {
+				translation: Some(position.into()),
+				rotation: Some(rotation.into()),
+				scale: Some(scale.into()),
+			};
+			let _ =
+				node.send_remote_signal("transform_changed", serialize(transform_message).ok()?);
+			*last_transform = Some(transform);
+		}
+		Some(())
+	}
+}
+impl Aspect for FieldSubscription {
+	const NAME: &'static str = "FieldSubscription";
+}
+
+fn subscribe_changes_flex(
+	node: Arc<Node>,
+	calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct SubscribeChangesInfo<'a> {
+		reference_space_path: &'a str,
+	}
+	let info: SubscribeChangesInfo = deserialize(message.as_ref())?;
+	let reference_space = calling_client
+		.get_node("Reference space", info.reference_space_path)?
+		.get_aspect::<Spatial>()?;
+	node.get_aspect::<Field>()?;
+
+	for client_node in client_nodes(&node, &calling_client) {
+		if let Ok(old_subscription) = client_node.get_aspect::<FieldSubscription>() {
+			SUBSCRIPTIONS.remove(&old_subscription);
+		}
+		let subscription = client_node.add_aspect(FieldSubscription {
+			node: Arc::downgrade(&client_node),
+			field: Arc::downgrade(&node),
+			reference_space: reference_space.clone(),
+			last_shape: Mutex::new(None),
+			last_transform: Mutex::new(None),
+		});
+		SUBSCRIPTIONS.add_raw(&subscription);
+	}
+	Ok(())
+}
+fn unsubscribe_changes_flex(
+	node: Arc<Node>,
+	calling_client: Arc<Client>,
+	_message: Message,
+) -> Result<()> {
+	for client_node in client_nodes(&node, &calling_client) {
+		if let Ok(subscription) = client_node.get_aspect::<FieldSubscription>() {
+			SUBSCRIPTIONS.remove(&subscription);
+		}
+	}
+	Ok(())
+}
+
+/// Tell subscribers about any fields that changed shape or moved since last frame.
+pub fn send_changes() {
+	for subscription in SUBSCRIPTIONS.get_valid_contents() {
+		if subscription.send_changes().is_none() {
+			SUBSCRIPTIONS.remove(&subscription);
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use super::{Field, FieldTrait, Node, SphereFieldAspect};
+use super::{shape, Field, FieldShape, FieldTrait, Node, SphereFieldAspect};
 use crate::core::client::Client;
 use crate::nodes::fields::FieldAspect;
 use crate::nodes::spatial::Spatial;
@@ -20,6 +20,7 @@ impl SphereField {
 			radius: AtomicF32::new(radius),
 		};
 		<SphereField as FieldAspect>::add_node_members(node);
+		shape::add_node_members(node);
 		<SphereField as SphereFieldAspect>::add_node_members(node);
 		node.add_aspect(Field::Sphere(sphere_field));
 	}
@@ -30,11 +31,16 @@ impl SphereField {
 }
 
 impl FieldTrait for SphereField {
+	fn shape(&self) -> FieldShape {
+		FieldShape::Sphere {
+			radius: self.radius.load(Ordering::Relaxed),
+		}
+	}
 	fn local_distance(&self, p: Vec3A) -> f32 {
 		p.length() - self.radius.load(Ordering::Relaxed)
 	}
 	fn local_normal(&self, p: Vec3A, _r: f32) -> Vec3A {
-		-p.normalize()
+		p.normalize()
 	}
 	fn local_closest_point(&self, p: Vec3A, _r: f32) -> Vec3A {
 		p.normalize() * self.radius.load(Ordering::Relaxed)
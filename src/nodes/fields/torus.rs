@@ -1,4 +1,4 @@
-use super::{Field, FieldTrait, Node, TorusFieldAspect};
+use super::{shape, Field, FieldShape, FieldTrait, Node, TorusFieldAspect};
 use crate::core::client::Client;
 use crate::nodes::fields::FieldAspect;
 use crate::nodes::spatial::Spatial;
@@ -23,6 +23,7 @@ impl TorusField {
 			radius_b: AtomicF32::new(radius_b.abs()),
 		};
 		<TorusField as FieldAspect>::add_node_members(node);
+		shape::add_node_members(node);
 		<TorusField as TorusFieldAspect>::add_node_members(node);
 		node.add_aspect(Field::Torus(torus_field));
 	}
@@ -33,6 +34,12 @@ impl TorusField {
 	}
 }
 impl FieldTrait for TorusField {
+	fn shape(&self) -> FieldShape {
+		FieldShape::Torus {
+			radius_a: self.radius_a.load(Ordering::Relaxed),
+			radius_b: self.radius_b.load(Ordering::Relaxed),
+		}
+	}
 	fn local_distance(&self, p: Vec3A) -> f32 {
 		let radius_a = self.radius_a.load(Ordering::Relaxed);
 		let radius_b = self.radius_b.load(Ordering::Relaxed);
@@ -1,5 +1,6 @@
 use super::{DistanceLink, InputSpecialization};
 use crate::core::client::Client;
+use crate::core::permissions::Capability;
 use crate::nodes::fields::{Field, Ray, RayMarchResult};
 use crate::nodes::input::{InputMethod, InputType};
 use crate::nodes::spatial::{parse_transform, Spatial, Transform};
@@ -77,6 +78,7 @@ pub fn create_pointer_flex(
 		transform: Transform,
 		datamap: Option<Vec<u8>>,
 	}
+	calling_client.ensure_permission(Capability::InputMethod)?;
 	let info: CreatePointerInfo = deserialize(message.as_ref())?;
 	let node = Node::create_parent_name(&calling_client, "/input/method/pointer", info.name, true);
 	let parent = calling_client
@@ -1,5 +1,6 @@
 use super::{DistanceLink, InputSpecialization};
 use crate::core::client::Client;
+use crate::core::permissions::Capability;
 use crate::nodes::fields::Field;
 use crate::nodes::input::{InputMethod, InputType};
 use crate::nodes::spatial::{parse_transform, Spatial, Transform};
@@ -60,6 +61,7 @@ pub fn create_tip_flex(
 		radius: f32,
 		datamap: Option<Vec<u8>>,
 	}
+	calling_client.ensure_permission(Capability::InputMethod)?;
 	let info: CreateTipInfo = deserialize(message.as_ref())?;
 	let node = Node::create_parent_name(&calling_client, "/input/method/tip", info.name, true);
 	let parent = calling_client
@@ -4,6 +4,7 @@ use crate::{
 		client::{Client, INTERNAL_CLIENT},
 		registry::Registry,
 		scenegraph::MethodResponseSender,
+		shm::memfd_with,
 	},
 	nodes::{
 		drawable::{model::ModelPart, shaders::UNLIT_SHADER_BYTES},
@@ -12,25 +13,26 @@ use crate::{
 		Message, Node,
 	},
 };
-use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::eyre::{bail, ensure, eyre, Result};
 use glam::Mat4;
 use lazy_static::lazy_static;
 use mint::{RowMatrix4, Vector2};
 use nanoid::nanoid;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use stardust_xr::schemas::flex::{deserialize, serialize};
-use std::sync::Arc;
+use std::{ffi::c_void, os::fd::OwnedFd, sync::Arc};
 use stereokit::{
 	Color128, Material, Rect, RenderLayer, StereoKitDraw, Tex, TextureType, Transparency,
 };
+use tokio::sync::oneshot;
 
 lazy_static! {
 	pub(super) static ref ITEM_TYPE_INFO_CAMERA: TypeInfo = TypeInfo {
 		type_name: "camera",
-		aliased_local_signals: vec!["apply_preview_material", "frame"],
-		aliased_local_methods: vec![],
+		aliased_local_signals: vec!["apply_preview_material"],
+		aliased_local_methods: vec!["frame"],
 		aliased_remote_signals: vec![],
 		ui: Default::default(),
 		items: Registry::new(),
@@ -38,11 +40,35 @@ lazy_static! {
 	};
 }
 
+/// DRM_FORMAT_ABGR8888, so the red, green, blue and alpha bytes of each pixel in that order
+const FRAME_FORMAT: u32 = u32::from_le_bytes(*b"AB24");
+
 struct FrameInfo {
 	proj_matrix: Mat4,
 	px_size: Vector2<u32>,
 }
 
+/// The layout of a rendered frame's pixels, which are in the fd sent along with it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CameraFrameInfo {
+	/// DRM fourcc code of the pixel format
+	pub format: u32,
+	/// Size in pixels
+	pub size: Vector2<u32>,
+	/// Bytes from the start of one row to the next
+	pub stride: u32,
+	/// StereoKit time in seconds the frame was rendered at
+	pub timestamp: f64,
+}
+type FrameSender = oneshot::Sender<Result<(CameraFrameInfo, OwnedFd)>>;
+
+/// Frame requests that were rendered last update, so the texture has them now.
+#[derive(Default)]
+struct RenderedFrames {
+	timestamp: f64,
+	requests: Vec<FrameSender>,
+}
+
 pub struct CameraItem {
 	space: Arc<Spatial>,
 	frame_info: Mutex<FrameInfo>,
@@ -50,6 +76,8 @@ pub struct CameraItem {
 	sk_mat: OnceCell<Arc<Material>>,
 	applied_to: Registry<ModelPart>,
 	apply_to: Registry<ModelPart>,
+	frame_requests: Mutex<Vec<FrameSender>>,
+	rendered_frames: Mutex<RenderedFrames>,
 }
 impl CameraItem {
 	pub fn add_to(node: &Arc<Node>, proj_matrix: Mat4, px_size: Vector2<u32>) {
@@ -67,6 +95,8 @@ impl CameraItem {
 				sk_mat: OnceCell::new(),
 				applied_to: Registry::new(),
 				apply_to: Registry::new(),
+				frame_requests: Mutex::new(Vec::new()),
+				rendered_frames: Mutex::new(RenderedFrames::default()),
 			}),
 		);
 		node.add_local_method("frame", CameraItem::frame_flex);
@@ -76,18 +106,23 @@ impl CameraItem {
 		);
 	}
 
+	/// Render a frame and return it in shared memory, available after the next frame is drawn.
 	fn frame_flex(
 		node: Arc<Node>,
 		_calling_client: Arc<Client>,
 		_message: Message,
 		response: MethodResponseSender,
 	) {
-		response.wrap_sync(move || {
-			let ItemType::Camera(_camera) = &node.get_aspect::<Item>().unwrap().specialization
-			else {
-				return Err(eyre!("Wrong item type?"));
+		response.wrap_async(async move {
+			let item = node.get_aspect::<Item>()?;
+			let ItemType::Camera(camera) = &item.specialization else {
+				bail!("Wrong item type?")
 			};
-			Ok(serialize(())?.into())
+			let (tx, rx) = oneshot::channel();
+			camera.frame_requests.lock().push(tx);
+
+			let (info, fd) = rx.await??;
+			Ok((info, vec![fd]))
 		});
 	}
 
@@ -133,7 +168,20 @@ impl CameraItem {
 			model_part.replace_material(sk_mat.clone())
 		}
 
-		if !self.applied_to.is_empty() {
+		let rendered_frames = std::mem::take(&mut *self.rendered_frames.lock());
+		if !rendered_frames.requests.is_empty() {
+			let frame = read_frame(sk_tex, frame_info.px_size, rendered_frames.timestamp);
+			for request in rendered_frames.requests {
+				let frame = match &frame {
+					Ok((info, fd)) => fd.try_clone().map(|fd| (*info, fd)).map_err(Into::into),
+					Err(e) => Err(eyre!("{e}")),
+				};
+				let _ = request.send(frame);
+			}
+		}
+
+		let frame_requests = std::mem::take(&mut *self.frame_requests.lock());
+		if !self.applied_to.is_empty() || !frame_requests.is_empty() {
 			sk.render_to(
 				sk_tex,
 				frame_info.proj_matrix,
@@ -148,9 +196,38 @@ impl CameraItem {
 				},
 			);
 		}
+		*self.rendered_frames.lock() = RenderedFrames {
+			timestamp: sk.time_get(),
+			requests: frame_requests,
+		};
 	}
 }
 
+fn read_frame(
+	sk_tex: &Tex,
+	px_size: Vector2<u32>,
+	timestamp: f64,
+) -> Result<(CameraFrameInfo, OwnedFd)> {
+	let stride = px_size.x * 4;
+	let mut pixels = vec![0_u8; stride as usize * px_size.y as usize];
+	let read = unsafe {
+		stereokit::sys::tex_get_data(
+			sk_tex.0.as_ptr(),
+			pixels.as_mut_ptr() as *mut c_void,
+			pixels.len(),
+		)
+	};
+	ensure!(read != 0, "Couldn't read the camera's texture");
+
+	let info = CameraFrameInfo {
+		format: FRAME_FORMAT,
+		size: px_size,
+		stride,
+		timestamp,
+	};
+	Ok((info, memfd_with("stardust-camera-frame", &pixels)?))
+}
+
 pub fn update(sk: &impl StereoKitDraw) {
 	for camera in ITEM_TYPE_INFO_CAMERA.items.get_valid_contents() {
 		let ItemType::Camera(camera) = &camera.specialization else {
@@ -10,6 +10,7 @@ use super::spatial::{parse_transform, Spatial};
 use super::{Alias, Aspect, Message, Node};
 use crate::core::client::Client;
 use crate::core::node_collections::LifeLinkedNodeMap;
+use crate::core::permissions::Capability;
 use crate::core::registry::Registry;
 use crate::nodes::alias::AliasInfo;
 use crate::nodes::fields::find_field;
@@ -464,6 +465,7 @@ pub fn register_item_ui_flex(
 	struct RegisterItemUIInfo<'a> {
 		item_type: &'a str,
 	}
+	calling_client.ensure_permission(Capability::ItemUI)?;
 	let info: RegisterItemUIInfo = deserialize(message.as_ref())?;
 	let type_info = type_info(info.item_type)?;
 	let ui = Node::create_parent_name(&calling_client, "/item", type_info.type_name, true)
@@ -2,6 +2,7 @@ use crate::{
 	core::{
 		client::{get_env, state, Client, INTERNAL_CLIENT},
 		registry::Registry,
+		scenegraph::MethodResponseSender,
 	},
 	nodes::{
 		drawable::model::ModelPart,
@@ -10,7 +11,7 @@ use crate::{
 		Message, Node,
 	},
 };
-use color_eyre::eyre::{eyre, Result};
+use color_eyre::eyre::{ensure, eyre, Result};
 use glam::Mat4;
 use lazy_static::lazy_static;
 use mint::Vector2;
@@ -22,7 +23,11 @@ use serde::{
 	Deserialize, Serialize,
 };
 use stardust_xr::schemas::flex::{deserialize, serialize};
-use std::sync::{Arc, Weak};
+use std::{
+	os::fd::OwnedFd,
+	sync::{Arc, Weak},
+};
+use tokio::sync::oneshot;
 use tracing::debug;
 
 lazy_static! {
@@ -34,17 +39,27 @@ lazy_static! {
 			"auto_size_toplevel",
 			"set_toplevel_size",
 			"set_toplevel_focused_visuals",
+			"set_output",
+			"set_preferred_scale",
 			"pointer_motion",
 			"pointer_button",
 			"pointer_scroll",
+			"pointer_relative_motion",
 			"keyboard_keymap",
 			"keyboard_key",
+			"text_input_commit",
 			"touch_down",
 			"touch_move",
 			"touch_up",
 			"reset_touches",
+			"tablet_tool_proximity",
+			"tablet_tool_motion",
+			"tablet_tool_tip",
+			"tablet_tool_pressure",
+			"tablet_tool_tilt",
+			"tablet_tool_button",
 		],
-		aliased_local_methods: vec![],
+		aliased_local_methods: vec!["capture_surface", "text_input_state"],
 		aliased_remote_signals: vec![
 			"toplevel_parent_changed",
 			"toplevel_title_changed",
@@ -53,6 +68,10 @@ lazy_static! {
 			"toplevel_move_request",
 			"toplevel_resize_request",
 			"toplevel_size_changed",
+			"toplevel_activation_request",
+			"layer_surface_changed",
+			"text_input_changed",
+			"pointer_constraint_changed",
 			"set_cursor",
 			"new_child",
 			"reposition_child",
@@ -129,7 +148,7 @@ impl serde::Serialize for SurfaceID {
 }
 
 /// The origin and size of the surface's "solid" part.
-#[derive(Debug, Serialize, Clone, Copy)]
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
 pub struct Geometry {
 	pub origin: Vector2<i32>,
 	pub size: Vector2<u32>,
@@ -153,6 +172,183 @@ pub struct ToplevelInfo {
 	pub logical_rectangle: Geometry,
 }
 
+/// Which layer a layer shell surface is in, from furthest back to furthest forward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layer {
+	Background,
+	Bottom,
+	Top,
+	Overlay,
+}
+/// Whether a layer shell surface wants keyboard input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyboardInteractivity {
+	None,
+	/// Wants all keyboard input while it's in the top or overlay layer (e.g. a lock screen or launcher)
+	Exclusive,
+	/// Wants keyboard input like a normal window when focused
+	OnDemand,
+}
+/// Edges of the output a layer shell surface is attached to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Anchor {
+	pub top: bool,
+	pub bottom: bool,
+	pub left: bool,
+	pub right: bool,
+}
+/// Distance in pixels from the anchored edges.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Margin {
+	pub top: i32,
+	pub right: i32,
+	pub bottom: i32,
+	pub left: i32,
+}
+/// The state of a layer shell surface (bars, launchers, notifications and such), meant to be placed relative to the user rather than as a window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayerSurfaceInfo {
+	/// What the surface is for, e.g. "waybar" or "notifications"
+	pub namespace: String,
+	pub layer: Layer,
+	pub anchor: Anchor,
+	/// Pixels from the anchored edge other surfaces should keep clear of, or -1 to ignore other surfaces' exclusive zones
+	pub exclusive_zone: i32,
+	pub margin: Margin,
+	pub keyboard_interactivity: KeyboardInteractivity,
+}
+
+/// A virtual output panels can be put on, which decides the size and pixel density their surfaces render at.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputInfo {
+	pub name: String,
+	/// Size in pixels
+	pub size: Vector2<u32>,
+	pub scale: i32,
+}
+
+/// The layout of a captured surface's pixels, which are in the fd sent along with it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SurfaceCaptureInfo {
+	/// DRM fourcc code of the pixel format
+	pub format: u32,
+	/// Size in pixels
+	pub size: Vector2<u32>,
+	/// Bytes from the start of one row to the next
+	pub stride: u32,
+}
+/// Resolves once the surface has been captured, which happens on the next frame.
+pub type SurfaceCapture = oneshot::Receiver<Result<(SurfaceCaptureInfo, OwnedFd)>>;
+
+/// How a surface wants the pointer held in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PointerConstraintKind {
+	/// The pointer shouldn't move at all, the client only wants relative motion (e.g. to turn a first person camera)
+	Lock,
+	/// The pointer shouldn't leave the region
+	Confine,
+}
+/// A surface's request to lock or confine the pointer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointerConstraint {
+	pub kind: PointerConstraintKind,
+	/// Pointer input to the surface activates the constraint, the toplevel losing focused visuals deactivates it
+	pub active: bool,
+	/// The part of the surface the constraint applies to, the whole surface if unset
+	pub region: Option<Vec<Geometry>>,
+	/// Where the client would like the pointer to be once the lock ends
+	pub cursor_position_hint: Option<Vector2<f32>>,
+}
+
+/// Something the panel UI's pen did over a surface, every event but leaving proximity brings the pen close to it.
+#[derive(Debug, Clone, Copy)]
+pub enum TabletToolEvent {
+	/// Bring the pen close to the surface or take it away
+	Proximity(bool),
+	Motion(Vector2<f32>),
+	/// Whether the pen's tip is touching the surface
+	Tip(bool),
+	/// From 0 to 1
+	Pressure(f32),
+	/// The pen's tilt away from the surface normal in degrees, positive x towards the right and positive y towards the bottom
+	Tilt(Vector2<f32>),
+	/// Buttons on the side of the pen, using linux input event codes (e.g. BTN_STYLUS)
+	Button {
+		button: u32,
+		pressed: bool,
+	},
+}
+
+/// What a text field is for, so a virtual keyboard can show the right layout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentPurpose {
+	#[default]
+	Normal,
+	Alpha,
+	Digits,
+	Number,
+	Phone,
+	Url,
+	Email,
+	Name,
+	Password,
+	Pin,
+	Date,
+	Time,
+	Datetime,
+	Terminal,
+}
+/// How a text field would like its input to behave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentHint {
+	Completion,
+	Spellcheck,
+	AutoCapitalization,
+	Lowercase,
+	Uppercase,
+	Titlecase,
+	HiddenText,
+	SensitiveData,
+	Latin,
+	Multiline,
+}
+/// The text field focused in a panel item, for virtual keyboards and input methods.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TextInputInfo {
+	/// Text around the cursor, if the client shares it
+	pub surrounding_text: Option<String>,
+	/// Byte offset of the cursor in the surrounding text
+	pub cursor: i32,
+	/// Byte offset of the other end of the selection in the surrounding text, the same as the cursor if nothing is selected
+	pub anchor: i32,
+	pub content_hints: Vec<ContentHint>,
+	pub content_purpose: ContentPurpose,
+	/// Where the text cursor is on the toplevel surface, so a keyboard can avoid covering it
+	pub cursor_rectangle: Option<Geometry>,
+}
+/// Text that isn't final yet, shown in the text field in place of the last preedit.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Preedit {
+	pub text: String,
+	/// Byte range of the cursor within the text, or -1 for both to hide it
+	pub cursor_begin: i32,
+	pub cursor_end: i32,
+}
+/// Changes to make to the focused text field all at once, applied in the order of the fields.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TextInputCommit {
+	/// Bytes to delete before and after the cursor
+	pub delete_surrounding: Option<(u32, u32)>,
+	/// Inserted at the cursor, replacing any preedit
+	pub commit_string: Option<String>,
+	pub preedit: Option<Preedit>,
+}
+
 /// Data on positioning a child
 #[derive(Debug, Clone, Serialize)]
 pub struct ChildInfo {
@@ -173,17 +369,29 @@ pub struct PanelItemInitData {
 	pub pointer_grab: Option<SurfaceID>,
 	/// The surface, if any, that has exclusive input to the keyboard.
 	pub keyboard_grab: Option<SurfaceID>,
+	/// Set if this panel item is a layer shell surface rather than a toplevel window.
+	pub layer_surface: Option<LayerSurfaceInfo>,
+	/// Outputs the panel item can be put on with `set_output`, it starts on the first one.
+	pub outputs: Vec<OutputInfo>,
+	/// The text field focused in the panel item, if any.
+	pub text_input: Option<TextInputInfo>,
 }
 
 pub trait Backend: Send + Sync + 'static {
 	fn start_data(&self) -> Result<PanelItemInitData>;
 
 	fn apply_surface_material(&self, surface: SurfaceID, model_part: &Arc<ModelPart>);
+	/// Copy the latest buffer committed to the surface into shared memory, e.g. for thumbnails.
+	fn capture_surface(&self, surface: &SurfaceID) -> Result<SurfaceCapture>;
 
 	fn close_toplevel(&self);
 	fn auto_size_toplevel(&self);
 	fn set_toplevel_size(&self, size: Vector2<u32>);
 	fn set_toplevel_focused_visuals(&self, focused: bool);
+	/// Move every surface of the panel item to the output with this name.
+	fn set_output(&self, output: &str) -> Result<()>;
+	/// Render the panel item's surfaces at this scale instead of their output's, sent to clients through fractional scale.
+	fn set_preferred_scale(&self, scale: f64);
 
 	fn pointer_motion(&self, surface: &SurfaceID, position: Vector2<f32>);
 	fn pointer_button(&self, surface: &SurfaceID, button: u32, pressed: bool);
@@ -193,13 +401,21 @@ pub trait Backend: Send + Sync + 'static {
 		scroll_distance: Option<Vector2<f32>>,
 		scroll_steps: Option<Vector2<f32>>,
 	);
+	/// Unaccelerated pointer movement in surface pixels, for clients that lock the pointer.
+	fn pointer_relative_motion(&self, surface: &SurfaceID, delta: Vector2<f32>);
 
 	fn keyboard_keys(&self, surface: &SurfaceID, keymap_id: &str, keys: Vec<i32>);
 
+	/// The text field focused in the panel item, only set while the toplevel has focused visuals.
+	fn text_input_state(&self) -> Option<TextInputInfo>;
+	fn text_input_commit(&self, commit: TextInputCommit);
+
 	fn touch_down(&self, surface: &SurfaceID, id: u32, position: Vector2<f32>);
 	fn touch_move(&self, id: u32, position: Vector2<f32>);
 	fn touch_up(&self, id: u32);
 	fn reset_touches(&self);
+
+	fn tablet_tool_event(&self, surface: &SurfaceID, event: TabletToolEvent);
 }
 
 pub fn panel_item_from_node(node: &Node) -> Option<Arc<dyn PanelItemTrait>> {
@@ -251,21 +467,37 @@ impl<B: Backend + ?Sized> PanelItem<B> {
 		);
 
 		node.add_local_signal("apply_surface_material", Self::apply_surface_material_flex);
+		node.add_local_method("capture_surface", Self::capture_surface_flex);
 		node.add_local_signal("close_toplevel", Self::close_toplevel_flex);
 		node.add_local_signal("auto_size_toplevel", Self::auto_size_toplevel_flex);
 		node.add_local_signal("set_toplevel_size", Self::set_toplevel_size_flex);
+		node.add_local_signal("set_output", Self::set_output_flex);
+		node.add_local_signal("set_preferred_scale", Self::set_preferred_scale_flex);
 
 		node.add_local_signal("pointer_motion", Self::pointer_motion_flex);
 		node.add_local_signal("pointer_button", Self::pointer_button_flex);
 		node.add_local_signal("pointer_scroll", Self::pointer_scroll_flex);
+		node.add_local_signal(
+			"pointer_relative_motion",
+			Self::pointer_relative_motion_flex,
+		);
 
 		node.add_local_signal("keyboard_key", Self::keyboard_keys_flex);
+		node.add_local_method("text_input_state", Self::text_input_state_flex);
+		node.add_local_signal("text_input_commit", Self::text_input_commit_flex);
 
 		node.add_local_signal("touch_down", Self::touch_down_flex);
 		node.add_local_signal("touch_move", Self::touch_move_flex);
 		node.add_local_signal("touch_up", Self::touch_up_flex);
 		node.add_local_signal("reset_touches", Self::reset_touches_flex);
 
+		node.add_local_signal("tablet_tool_proximity", Self::tablet_tool_proximity_flex);
+		node.add_local_signal("tablet_tool_motion", Self::tablet_tool_motion_flex);
+		node.add_local_signal("tablet_tool_tip", Self::tablet_tool_tip_flex);
+		node.add_local_signal("tablet_tool_pressure", Self::tablet_tool_pressure_flex);
+		node.add_local_signal("tablet_tool_tilt", Self::tablet_tool_tilt_flex);
+		node.add_local_signal("tablet_tool_button", Self::tablet_tool_button_flex);
+
 		panel_item
 	}
 	pub fn drop_toplevel(&self) {
@@ -317,6 +549,22 @@ impl<B: Backend + ?Sized> PanelItem<B> {
 			serialize((up, down, left, right)).unwrap(),
 		);
 	}
+	/// The toplevel wants attention, e.g. because the app was launched for it or it got a notification clicked.
+	pub fn toplevel_activation_request(&self, token: &str, startup_token: Option<&str>) {
+		let Some(node) = self.node.upgrade() else {
+			return;
+		};
+		let _ = node.send_remote_signal(
+			"toplevel_activation_request",
+			serialize((token, startup_token)).unwrap(),
+		);
+	}
+	pub fn layer_surface_changed(&self, info: &LayerSurfaceInfo) {
+		let Some(node) = self.node.upgrade() else {
+			return;
+		};
+		let _ = node.send_remote_signal("layer_surface_changed", serialize(info).unwrap());
+	}
 	pub fn toplevel_size_changed(&self, size: Vector2<u32>) {
 		let Some(node) = self.node.upgrade() else {
 			return;
@@ -324,6 +572,27 @@ impl<B: Backend + ?Sized> PanelItem<B> {
 		let _ = node.send_remote_signal("toplevel_size_changed", serialize(size).unwrap());
 	}
 
+	pub fn text_input_changed(&self, info: Option<&TextInputInfo>) {
+		let Some(node) = self.node.upgrade() else {
+			return;
+		};
+		let _ = node.send_remote_signal("text_input_changed", serialize(info).unwrap());
+	}
+
+	pub fn pointer_constraint_changed(
+		&self,
+		surface: &SurfaceID,
+		constraint: Option<&PointerConstraint>,
+	) {
+		let Some(node) = self.node.upgrade() else {
+			return;
+		};
+		let _ = node.send_remote_signal(
+			"pointer_constraint_changed",
+			serialize((surface, constraint)).unwrap(),
+		);
+	}
+
 	pub fn set_cursor(&self, geometry: Option<Geometry>) {
 		let Some(node) = self.node.upgrade() else {
 			return;
@@ -406,10 +675,53 @@ impl<B: Backend + ?Sized> PanelItem<B> {
 
 		Ok(())
 	}
+	fn capture_surface_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+		response: MethodResponseSender,
+	) {
+		response.wrap_async(async move {
+			let panel_item =
+				panel_item_from_node(&node).ok_or_else(|| eyre!("Panel item not found"))?;
+			let surface: SurfaceID = deserialize(message.as_ref())?;
+			debug!(?surface, "Capture surface");
+
+			let (info, fd) = panel_item.capture_surface(&surface)?.await??;
+			Ok((info, vec![fd]))
+		});
+	}
 
 	flex_no_args!(close_toplevel_flex, close_toplevel);
 	flex_no_args!(auto_size_toplevel_flex, auto_size_toplevel);
 	flex_deserialize!(set_toplevel_size_flex, set_toplevel_size);
+	fn set_output_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+		let output: &str = deserialize(message.as_ref())?;
+		panel_item.set_output(output)
+	}
+	fn set_preferred_scale_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+		let scale: f64 = deserialize(message.as_ref())?;
+		ensure!(
+			scale.is_finite() && scale > 0.0,
+			"Preferred scale must be positive"
+		);
+		panel_item.set_preferred_scale(scale);
+		Ok(())
+	}
 
 	fn pointer_motion_flex(
 		node: Arc<Node>,
@@ -464,6 +776,22 @@ impl<B: Backend + ?Sized> PanelItem<B> {
 
 		Ok(())
 	}
+	fn pointer_relative_motion_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+
+		let (surface_id, delta): (SurfaceID, Vector2<f32>) = deserialize(message.as_ref())?;
+		debug!(?surface_id, ?delta, "Pointer relative motion");
+
+		panel_item.pointer_relative_motion(&surface_id, delta);
+
+		Ok(())
+	}
 
 	fn keyboard_keys_flex(
 		node: Arc<Node>,
@@ -481,6 +809,33 @@ impl<B: Backend + ?Sized> PanelItem<B> {
 
 		Ok(())
 	}
+	fn text_input_state_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		_message: Message,
+		response: MethodResponseSender,
+	) {
+		response.wrap_sync(move || {
+			let panel_item =
+				panel_item_from_node(&node).ok_or_else(|| eyre!("Panel item not found"))?;
+			Ok(serialize(panel_item.text_input_state())?.into())
+		});
+	}
+	fn text_input_commit_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+		let commit: TextInputCommit = deserialize(message.as_ref())?;
+		debug!(?commit, "Text input commit");
+
+		panel_item.text_input_commit(commit);
+
+		Ok(())
+	}
 	pub fn grab_keyboard(&self, sid: Option<SurfaceID>) {
 		let Some(node) = self.node.upgrade() else {
 			return;
@@ -490,6 +845,103 @@ impl<B: Backend + ?Sized> PanelItem<B> {
 		let _ = node.send_remote_signal("grab_keyboard", message);
 	}
 
+	fn tablet_tool_proximity_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+
+		let (surface_id, in_proximity): (SurfaceID, bool) = deserialize(message.as_ref())?;
+		debug!(?surface_id, ?in_proximity, "Tablet tool proximity");
+
+		panel_item.tablet_tool_event(&surface_id, TabletToolEvent::Proximity(in_proximity));
+
+		Ok(())
+	}
+	fn tablet_tool_motion_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+
+		let (surface_id, position): (SurfaceID, Vector2<f32>) = deserialize(message.as_ref())?;
+		debug!(?surface_id, ?position, "Tablet tool motion");
+
+		panel_item.tablet_tool_event(&surface_id, TabletToolEvent::Motion(position));
+
+		Ok(())
+	}
+	fn tablet_tool_tip_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+
+		let (surface_id, down): (SurfaceID, bool) = deserialize(message.as_ref())?;
+		debug!(?surface_id, ?down, "Tablet tool tip");
+
+		panel_item.tablet_tool_event(&surface_id, TabletToolEvent::Tip(down));
+
+		Ok(())
+	}
+	fn tablet_tool_pressure_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+
+		let (surface_id, pressure): (SurfaceID, f32) = deserialize(message.as_ref())?;
+		debug!(?surface_id, ?pressure, "Tablet tool pressure");
+
+		panel_item.tablet_tool_event(&surface_id, TabletToolEvent::Pressure(pressure));
+
+		Ok(())
+	}
+	fn tablet_tool_tilt_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+
+		let (surface_id, tilt): (SurfaceID, Vector2<f32>) = deserialize(message.as_ref())?;
+		debug!(?surface_id, ?tilt, "Tablet tool tilt");
+
+		panel_item.tablet_tool_event(&surface_id, TabletToolEvent::Tilt(tilt));
+
+		Ok(())
+	}
+	fn tablet_tool_button_flex(
+		node: Arc<Node>,
+		_calling_client: Arc<Client>,
+		message: Message,
+	) -> Result<()> {
+		let Some(panel_item) = panel_item_from_node(&node) else {
+			return Ok(());
+		};
+
+		let (surface_id, button, pressed): (SurfaceID, u32, bool) = deserialize(message.as_ref())?;
+		debug!(?surface_id, button, pressed, "Tablet tool button");
+
+		panel_item.tablet_tool_event(&surface_id, TabletToolEvent::Button { button, pressed });
+
+		Ok(())
+	}
+
 	fn touch_down_flex(
 		node: Arc<Node>,
 		_calling_client: Arc<Client>,
@@ -543,6 +995,9 @@ impl<B: Backend + ?Sized> Backend for PanelItem<B> {
 	fn apply_surface_material(&self, surface: SurfaceID, model_part: &Arc<ModelPart>) {
 		self.backend.apply_surface_material(surface, model_part)
 	}
+	fn capture_surface(&self, surface: &SurfaceID) -> Result<SurfaceCapture> {
+		self.backend.capture_surface(surface)
+	}
 
 	fn close_toplevel(&self) {
 		self.backend.close_toplevel()
@@ -556,6 +1011,12 @@ impl<B: Backend + ?Sized> Backend for PanelItem<B> {
 	fn set_toplevel_focused_visuals(&self, focused: bool) {
 		self.backend.set_toplevel_focused_visuals(focused)
 	}
+	fn set_output(&self, output: &str) -> Result<()> {
+		self.backend.set_output(output)
+	}
+	fn set_preferred_scale(&self, scale: f64) {
+		self.backend.set_preferred_scale(scale)
+	}
 
 	fn pointer_motion(&self, surface: &SurfaceID, position: Vector2<f32>) {
 		self.backend.pointer_motion(surface, position)
@@ -572,11 +1033,21 @@ impl<B: Backend + ?Sized> Backend for PanelItem<B> {
 		self.backend
 			.pointer_scroll(surface, scroll_distance, scroll_steps)
 	}
+	fn pointer_relative_motion(&self, surface: &SurfaceID, delta: Vector2<f32>) {
+		self.backend.pointer_relative_motion(surface, delta)
+	}
 
 	fn keyboard_keys(&self, surface: &SurfaceID, keymap_id: &str, keys: Vec<i32>) {
 		self.backend.keyboard_keys(surface, keymap_id, keys)
 	}
 
+	fn text_input_state(&self) -> Option<TextInputInfo> {
+		self.backend.text_input_state()
+	}
+	fn text_input_commit(&self, commit: TextInputCommit) {
+		self.backend.text_input_commit(commit)
+	}
+
 	fn touch_down(&self, surface: &SurfaceID, id: u32, position: Vector2<f32>) {
 		self.backend.touch_down(surface, id, position)
 	}
@@ -589,6 +1060,10 @@ impl<B: Backend + ?Sized> Backend for PanelItem<B> {
 	fn reset_touches(&self) {
 		self.backend.reset_touches()
 	}
+
+	fn tablet_tool_event(&self, surface: &SurfaceID, event: TabletToolEvent) {
+		self.backend.tablet_tool_event(surface, event)
+	}
 }
 impl<B: Backend + ?Sized> Drop for PanelItem<B> {
 	fn drop(&mut self) {
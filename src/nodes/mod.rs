@@ -1,6 +1,7 @@
 pub mod alias;
 pub mod audio;
 pub mod data;
+pub mod debug;
 pub mod drawable;
 pub mod fields;
 pub mod hmd;
@@ -149,6 +150,9 @@ impl Node {
 	pub fn get_aspect<A: Aspect>(&self) -> Result<Arc<A>> {
 		self.aspects.get()
 	}
+	pub fn get_aspect_names(&self) -> Vec<&'static str> {
+		self.aspects.names()
+	}
 
 	pub fn send_local_signal(
 		self: Arc<Self>,
@@ -288,7 +292,7 @@ pub trait Aspect: Any + Send + Sync + 'static {
 }
 
 #[derive(Default)]
-struct Aspects(Mutex<FxHashMap<TypeId, Arc<dyn Any + Send + Sync + 'static>>>);
+struct Aspects(Mutex<FxHashMap<TypeId, (&'static str, Arc<dyn Any + Send + Sync + 'static>)>>);
 impl Aspects {
 	fn add<A: Aspect>(&self, t: A) -> Arc<A> {
 		let aspect = Arc::new(t);
@@ -296,15 +300,22 @@ impl Aspects {
 		aspect
 	}
 	fn add_raw<A: Aspect>(&self, aspect: Arc<A>) {
-		self.0.lock().insert(Self::type_key::<A>(), aspect);
+		self.0
+			.lock()
+			.insert(Self::type_key::<A>(), (A::NAME, aspect));
 	}
 	fn get<A: Aspect + Any + Send + Sync + 'static>(&self) -> Result<Arc<A>> {
 		self.0
 			.lock()
 			.get(&Self::type_key::<A>())
-			.and_then(|a| Arc::downcast(a.clone()).ok())
+			.and_then(|(_, a)| Arc::downcast(a.clone()).ok())
 			.ok_or(eyre!("Couldn't get aspect {}", A::NAME.to_lowercase()))
 	}
+	fn names(&self) -> Vec<&'static str> {
+		let mut names: Vec<_> = self.0.lock().values().map(|(name, _)| *name).collect();
+		names.sort_unstable();
+		names
+	}
 
 	fn type_key<A: 'static>() -> TypeId {
 		TypeId::of::<A>()
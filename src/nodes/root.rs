@@ -111,33 +111,35 @@ impl Drop for Root {
 
 macro_rules! var_env_insert {
 	($env:ident, $name:ident) => {
-		$env.insert(stringify!($name).to_string(), $name.get().unwrap().clone());
+		if let Some(value) = $name.get() {
+			$env.insert(stringify!($name).to_string(), value.clone());
+		}
 	};
 }
+/// Environment variables a client needs to connect to this server (and its wayland/X server, if any).
+pub fn connection_environment() -> FxHashMap<String, String> {
+	let mut env: FxHashMap<String, String> = FxHashMap::default();
+	var_env_insert!(env, STARDUST_INSTANCE);
+	#[cfg(feature = "wayland")]
+	{
+		var_env_insert!(env, WAYLAND_DISPLAY);
+		#[cfg(feature = "xwayland")]
+		if let Some(x_display) = X_DISPLAY.get() {
+			env.insert("DISPLAY".to_string(), format!(":{x_display}"));
+		}
+		env.insert("GDK_BACKEND".to_string(), "wayland".to_string());
+		env.insert("QT_QPA_PLATFORM".to_string(), "wayland".to_string());
+		env.insert("MOZ_ENABLE_WAYLAND".to_string(), "1".to_string());
+		env.insert("CLUTTER_BACKEND".to_string(), "wayland".to_string());
+		env.insert("SDL_VIDEODRIVER".to_string(), "wayland".to_string());
+	}
+	env
+}
 pub fn get_connection_environment_flex(
 	_node: Arc<Node>,
 	_calling_client: Arc<Client>,
 	_message: Message,
 	response: MethodResponseSender,
 ) {
-	response.wrap_sync(move || {
-		let mut env: FxHashMap<String, String> = FxHashMap::default();
-		var_env_insert!(env, STARDUST_INSTANCE);
-		#[cfg(feature = "wayland")]
-		{
-			var_env_insert!(env, WAYLAND_DISPLAY);
-			#[cfg(feature = "xwayland")]
-			env.insert(
-				"DISPLAY".to_string(),
-				format!(":{}", X_DISPLAY.get().unwrap()),
-			);
-			env.insert("GDK_BACKEND".to_string(), "wayland".to_string());
-			env.insert("QT_QPA_PLATFORM".to_string(), "wayland".to_string());
-			env.insert("MOZ_ENABLE_WAYLAND".to_string(), "1".to_string());
-			env.insert("CLUTTER_BACKEND".to_string(), "wayland".to_string());
-			env.insert("SDL_VIDEODRIVER".to_string(), "wayland".to_string());
-		}
-
-		Ok(serialize(env)?.into())
-	});
+	response.wrap_sync(move || Ok(serialize(connection_environment())?.into()));
 }
@@ -0,0 +1,158 @@
+use super::{Spatial, Transform};
+use crate::core::client::Client;
+use crate::core::registry::Registry;
+use crate::nodes::{Message, Node};
+use color_eyre::eyre::Result;
+use glam::{Mat4, Quat, Vec3};
+use serde::Deserialize;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use stardust_xr::schemas::flex::{deserialize, serialize};
+use std::sync::Arc;
+
+static ANIMATING: Registry<Spatial> = Registry::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize_repr, Serialize_repr)]
+#[repr(u32)]
+pub enum Easing {
+	Linear,
+	/// Cubic, starts slow
+	EaseIn,
+	/// Cubic, ends slow
+	EaseOut,
+	/// Cubic, starts and ends slow
+	EaseInOut,
+}
+impl Easing {
+	fn apply(self, t: f32) -> f32 {
+		match self {
+			Easing::Linear => t,
+			Easing::EaseIn => t * t * t,
+			Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+			Easing::EaseInOut => {
+				if t < 0.5 {
+					4.0 * t * t * t
+				} else {
+					1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+				}
+			}
+		}
+	}
+}
+
+/// Tween of a spatial's local transform, so clients don't have to send a transform every frame to move things smoothly.
+pub struct TransformAnimation {
+	start: (Vec3, Quat, Vec3),
+	end: (Vec3, Quat, Vec3),
+	duration: f32,
+	elapsed: f32,
+	easing: Easing,
+}
+impl TransformAnimation {
+	fn transform(&self) -> Mat4 {
+		let t = if self.duration > 0.0 {
+			(self.elapsed / self.duration).clamp(0.0, 1.0)
+		} else {
+			1.0
+		};
+		let t = self.easing.apply(t);
+		let (start_scale, start_rotation, start_translation) = self.start;
+		let (end_scale, end_rotation, end_translation) = self.end;
+		Mat4::from_scale_rotation_translation(
+			start_scale.lerp(end_scale, t),
+			start_rotation.slerp(end_rotation, t),
+			start_translation.lerp(end_translation, t),
+		)
+	}
+	fn is_finished(&self) -> bool {
+		self.elapsed >= self.duration
+	}
+}
+
+impl Spatial {
+	pub fn animate_transform(
+		self: &Arc<Self>,
+		transform: Transform,
+		duration: f32,
+		easing: Easing,
+	) {
+		self.interrupt_animation();
+
+		let (mut scale, mut rotation, mut translation) =
+			self.local_transform().to_scale_rotation_translation();
+		if rotation.is_nan() {
+			rotation = Quat::IDENTITY;
+		}
+		let start = (scale, rotation, translation);
+		if let Some(new_translation) = transform.translation {
+			translation = new_translation.into();
+		}
+		if let Some(new_rotation) = transform.rotation {
+			rotation = Quat::from(new_rotation).normalize();
+		}
+		if let Some(new_scale) = transform.scale {
+			scale = new_scale.into();
+		}
+
+		*self.animation.lock() = Some(TransformAnimation {
+			start,
+			end: (scale, rotation, translation),
+			duration: duration.max(0.0),
+			elapsed: 0.0,
+			easing,
+		});
+		ANIMATING.add_raw(self);
+	}
+	/// Stop the animation where it is, e.g. because something else set the transform
+	pub(super) fn interrupt_animation(&self) {
+		if self.animation.lock().take().is_some() {
+			ANIMATING.remove(self);
+			self.send_animation_ended(false);
+		}
+	}
+	fn send_animation_ended(&self, completed: bool) {
+		let Some(node) = self.node() else {
+			return;
+		};
+		let Ok(message) = serialize(completed) else {
+			return;
+		};
+		let _ = node.send_remote_signal("transform_animation_ended", message);
+	}
+}
+
+pub(super) fn animate_transform_flex(
+	node: Arc<Node>,
+	_calling_client: Arc<Client>,
+	message: Message,
+) -> Result<()> {
+	#[derive(Deserialize)]
+	struct AnimateTransformInfo {
+		transform: Transform,
+		duration: f32,
+		easing: Easing,
+	}
+	let info: AnimateTransformInfo = deserialize(message.as_ref())?;
+	let spatial = node.get_aspect::<Spatial>()?;
+	spatial.animate_transform(info.transform, info.duration, info.easing);
+	Ok(())
+}
+
+/// Advance every running animation by `delta` seconds.
+pub fn update(delta: f64) {
+	for spatial in ANIMATING.get_valid_contents() {
+		let mut animation_lock = spatial.animation.lock();
+		let Some(animation) = animation_lock.as_mut() else {
+			ANIMATING.remove(&spatial);
+			continue;
+		};
+		animation.elapsed += delta as f32;
+		*spatial.transform.lock() = animation.transform();
+
+		if animation.is_finished() {
+			animation_lock.take();
+			drop(animation_lock);
+			ANIMATING.remove(&spatial);
+			spatial.send_animation_ended(true);
+		}
+	}
+}
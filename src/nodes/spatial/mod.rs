@@ -1,9 +1,12 @@
+pub mod animation;
 pub mod zone;
 
+use self::animation::TransformAnimation;
 use self::zone::Zone;
 use super::fields::Field;
 use super::{Aspect, Node};
 use crate::core::client::Client;
+use crate::core::permissions::Capability;
 use crate::core::registry::Registry;
 use crate::create_interface;
 use color_eyre::eyre::{eyre, Result};
@@ -48,6 +51,7 @@ pub struct Spatial {
 	zone: Mutex<Weak<Zone>>,
 	children: Registry<Spatial>,
 	pub(super) bounding_box_calc: OnceCell<fn(&Node) -> Bounds>,
+	animation: Mutex<Option<TransformAnimation>>,
 }
 
 impl Spatial {
@@ -61,6 +65,7 @@ impl Spatial {
 			zone: Mutex::new(Weak::new()),
 			children: Registry::new(),
 			bounding_box_calc: OnceCell::default(),
+			animation: Mutex::new(None),
 		})
 	}
 	pub fn add_to(
@@ -78,6 +83,7 @@ impl Spatial {
 			parent.children.add_raw(&spatial);
 		}
 		<Spatial as SpatialAspect>::add_node_members(node);
+		node.add_local_signal("animate_transform", animation::animate_transform_flex);
 		node.add_aspect_raw(spatial.clone());
 		spatial
 	}
@@ -124,6 +130,7 @@ impl Spatial {
 		parent_transform * self.local_transform()
 	}
 	pub fn set_local_transform(&self, transform: Mat4) {
+		self.interrupt_animation();
 		*self.transform.lock() = transform;
 	}
 	pub fn set_local_transform_components(
@@ -184,7 +191,7 @@ impl Spatial {
 		}
 	}
 
-	fn get_parent(&self) -> Option<Arc<Spatial>> {
+	pub(super) fn get_parent(&self) -> Option<Arc<Spatial>> {
 		self.parent.lock().clone()
 	}
 	fn set_parent(self: &Arc<Self>, new_parent: Option<&Arc<Spatial>>) {
@@ -426,6 +433,7 @@ impl SpatialInterfaceAspect for SpatialInterface {
 		transform: Transform,
 		field: Arc<Node>,
 	) -> Result<()> {
+		calling_client.ensure_permission(Capability::Zone)?;
 		let parent = parent.get_aspect::<Spatial>()?;
 		let transform = parse_transform(transform, true, true, false);
 		let field = field.get_aspect::<Field>()?;
@@ -1,22 +1,264 @@
+use super::{
+	event_time,
+	seat::{PointerEvent, SeatData},
+	state::WaylandState,
+	SERIAL_COUNTER,
+};
+use crate::core::clipboard::{self, Selection};
+use parking_lot::{const_mutex, Mutex};
 use smithay::reexports::wayland_server::{
+	backend::ClientId,
 	protocol::{
-		wl_data_device::{
-			Request::{Release, SetSelection, StartDrag},
-			WlDataDevice,
-		},
-		wl_data_device_manager::{
-			Request::{CreateDataSource, GetDataDevice},
-			WlDataDeviceManager,
-		},
-		wl_data_source::{
-			Request::{Destroy, Offer, SetActions},
-			WlDataSource,
-		},
+		wl_data_device::{self, WlDataDevice},
+		wl_data_device_manager::{self, DndAction, WlDataDeviceManager},
+		wl_data_offer::{self, WlDataOffer},
+		wl_data_source::{self, WlDataSource},
+		wl_surface::WlSurface,
 	},
-	Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+	Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
 };
+use std::sync::Arc;
+use tracing::debug;
+
+/// Every data device, so selections and drags can reach any client
+static DATA_DEVICES: Mutex<Vec<WlDataDevice>> = const_mutex(Vec::new());
+static DRAG: Mutex<Option<Drag>> = const_mutex(None);
+
+pub struct DataSourceData {
+	mime_types: Vec<String>,
+	dnd_actions: DndAction,
+}
+impl Default for DataSourceData {
+	fn default() -> Self {
+		DataSourceData {
+			mime_types: Vec::new(),
+			dnd_actions: DndAction::empty(),
+		}
+	}
+}
+pub(crate) fn source_mime_types(source: &WlDataSource) -> Vec<String> {
+	source
+		.data::<Mutex<DataSourceData>>()
+		.map(|data| data.lock().mime_types.clone())
+		.unwrap_or_default()
+}
+fn source_dnd_actions(source: &WlDataSource) -> DndAction {
+	source
+		.data::<Mutex<DataSourceData>>()
+		.map(|data| data.lock().dnd_actions)
+		.unwrap_or(DndAction::empty())
+}
+
+pub struct DataOfferData {
+	/// Selections can come from Stardust clients too, drags are always from a Wayland source
+	source: Selection,
+	/// Offers for drags negotiate a mime type and action, selection offers don't
+	dnd: bool,
+	accepted_mime_type: Mutex<Option<String>>,
+}
+impl DataOfferData {
+	fn wayland_source(&self) -> Option<&WlDataSource> {
+		match &self.source {
+			Selection::Wayland(source) if source.is_alive() => Some(source),
+			_ => None,
+		}
+	}
+}
+
+/// Create an offer for the source's data on the device's client.
+fn create_offer(
+	dh: &DisplayHandle,
+	device: &WlDataDevice,
+	source: &Selection,
+	dnd: bool,
+) -> Option<WlDataOffer> {
+	let client = device.client()?;
+	let offer = client
+		.create_resource::<WlDataOffer, _, WaylandState>(
+			dh,
+			device.version(),
+			DataOfferData {
+				source: source.clone(),
+				dnd,
+				accepted_mime_type: Mutex::new(None),
+			},
+		)
+		.ok()?;
+	device.data_offer(&offer);
+	for mime_type in source.mime_types() {
+		offer.offer(mime_type);
+	}
+	if let (true, Selection::Wayland(source)) = (dnd, source) {
+		if offer.version() >= wl_data_offer::EVT_SOURCE_ACTIONS_SINCE {
+			offer.source_actions(source_dnd_actions(source));
+		}
+	}
+	Some(offer)
+}
+
+/// Send the current selection to a device, which should only happen when its client gets keyboard focus.
+fn send_selection(device: &WlDataDevice) {
+	let Some(seat) = device.data::<Arc<SeatData>>() else {
+		return;
+	};
+	let offer = clipboard::get_selection()
+		.as_ref()
+		.and_then(|source| create_offer(&seat.display_handle, device, source, false));
+	device.selection(offer.as_ref());
+}
+
+/// Called right before a client gets keyboard focus, as the protocol wants the selection sent before `wl_keyboard.enter`.
+pub fn keyboard_focus_entered(seat: &SeatData) {
+	let devices = DATA_DEVICES.lock().clone();
+	for device in devices.iter() {
+		if device
+			.data::<Arc<SeatData>>()
+			.map(|device_seat| std::ptr::eq(device_seat.as_ref(), seat))
+			.unwrap_or(false)
+		{
+			send_selection(device);
+		}
+	}
+}
+
+/// Send the new selection to every client with keyboard focus, whether it came from Wayland or Stardust.
+pub fn selection_changed() {
+	// seats lock their keyboard focus before the data devices, so don't hold the devices while checking it
+	let devices = {
+		let mut devices = DATA_DEVICES.lock();
+		devices.retain(Resource::is_alive);
+		devices.clone()
+	};
+	for device in devices.iter() {
+		let focused = device
+			.data::<Arc<SeatData>>()
+			.map(|seat| seat.has_keyboard_focus())
+			.unwrap_or(false);
+		if focused {
+			send_selection(device);
+		}
+	}
+}
+
+struct DragFocus {
+	surface: WlSurface,
+	device: WlDataDevice,
+	offer: Option<WlDataOffer>,
+}
+impl DragFocus {
+	fn leave(self) {
+		if self.device.is_alive() {
+			self.device.leave();
+		}
+	}
+}
+/// A drag in progress. Each panel gets pointer events separately, so the drag follows whichever surface the pointer moves over.
+struct Drag {
+	/// `None` when the client only drags within its own surfaces
+	source: Option<WlDataSource>,
+	origin: WlSurface,
+	_icon: Option<WlSurface>,
+	focus: Option<DragFocus>,
+}
+impl Drag {
+	fn motion(&mut self, surface: &WlSurface, x: f64, y: f64) {
+		if let Some(focus) = &self.focus {
+			if &focus.surface == surface {
+				focus.device.motion(event_time(), x, y);
+				return;
+			}
+		}
+		if let Some(focus) = self.focus.take() {
+			focus.leave();
+		}
+
+		// without a source the data can't leave the client it came from
+		let Some(client_id) = surface.client().map(|client| client.id()) else {
+			return;
+		};
+		if self.source.is_none()
+			&& self.origin.client().map(|client| client.id()) != Some(client_id.clone())
+		{
+			return;
+		}
+		let Some(device) = DATA_DEVICES
+			.lock()
+			.iter()
+			.find(|device| device.client().map(|client| client.id()) == Some(client_id.clone()))
+			.cloned()
+		else {
+			return;
+		};
+		let Some(seat) = device.data::<Arc<SeatData>>() else {
+			return;
+		};
+		let offer = self.source.as_ref().and_then(|source| {
+			create_offer(
+				&seat.display_handle,
+				&device,
+				&Selection::Wayland(source.clone()),
+				true,
+			)
+		});
+		device.enter(SERIAL_COUNTER.inc(), surface, x, y, offer.as_ref());
+		self.focus = Some(DragFocus {
+			surface: surface.clone(),
+			device,
+			offer,
+		});
+	}
+	fn finish(mut self) {
+		// the source's client may have destroyed it or disconnected since the drag started
+		let source = self.source.as_ref().filter(|source| source.is_alive());
+		let Some(focus) = self.focus.take() else {
+			if let Some(source) = source {
+				source.cancelled();
+			}
+			return;
+		};
+		let accepted = focus
+			.offer
+			.as_ref()
+			.and_then(|offer| offer.data::<DataOfferData>())
+			.map(|data| data.accepted_mime_type.lock().is_some())
+			.unwrap_or(self.source.is_none());
+		if !accepted || !focus.device.is_alive() {
+			if let Some(source) = source {
+				source.cancelled();
+			}
+			focus.leave();
+			return;
+		}
 
-use super::state::WaylandState;
+		focus.device.drop();
+		if let Some(source) = source {
+			if source.version() >= wl_data_source::EVT_DND_DROP_PERFORMED_SINCE {
+				source.dnd_drop_performed();
+			}
+		}
+		focus.leave();
+	}
+}
+
+/// Pointer events go to the drag instead of the surface while one is in progress. Returns whether the event was used.
+pub fn drag_pointer_event(surface: &WlSurface, event: &PointerEvent) -> bool {
+	let mut drag = DRAG.lock();
+	let Some(current_drag) = drag.as_mut() else {
+		return false;
+	};
+	match event {
+		PointerEvent::Motion(position) => {
+			current_drag.motion(surface, position.x as f64, position.y as f64);
+		}
+		PointerEvent::Button { state: 0, .. } => {
+			if let Some(current_drag) = drag.take() {
+				current_drag.finish();
+			}
+		}
+		_ => (),
+	}
+	true
+}
 
 impl GlobalDispatch<WlDataDeviceManager, (), WaylandState> for WaylandState {
 	fn bind(
@@ -42,58 +284,172 @@ impl Dispatch<WlDataDeviceManager, (), WaylandState> for WaylandState {
 		data_init: &mut DataInit<'_, WaylandState>,
 	) {
 		match request {
-			CreateDataSource { id } => {
-				data_init.init(id, ());
+			wl_data_device_manager::Request::CreateDataSource { id } => {
+				data_init.init(id, Mutex::new(DataSourceData::default()));
 			}
-			GetDataDevice { id, seat: _ } => {
-				data_init.init(id, ());
+			wl_data_device_manager::Request::GetDataDevice { id, seat } => {
+				let Some(seat_data) = seat.data::<Arc<SeatData>>() else {
+					return;
+				};
+				let device = data_init.init(id, seat_data.clone());
+				DATA_DEVICES.lock().push(device);
 			}
 			_ => unreachable!(),
 		}
 	}
 }
 
-impl Dispatch<WlDataSource, (), WaylandState> for WaylandState {
+impl Dispatch<WlDataSource, Mutex<DataSourceData>, WaylandState> for WaylandState {
 	fn request(
 		_state: &mut WaylandState,
 		_client: &Client,
 		_resource: &WlDataSource,
 		request: <WlDataSource as Resource>::Request,
-		_data: &(),
+		data: &Mutex<DataSourceData>,
 		_dhandle: &DisplayHandle,
 		_data_init: &mut DataInit<'_, WaylandState>,
 	) {
 		match request {
-			Offer { mime_type: _ } => {}
-			Destroy => {}
-			SetActions { dnd_actions: _ } => {}
+			wl_data_source::Request::Offer { mime_type } => {
+				data.lock().mime_types.push(mime_type);
+			}
+			wl_data_source::Request::SetActions { dnd_actions } => {
+				if let WEnum::Value(dnd_actions) = dnd_actions {
+					data.lock().dnd_actions = dnd_actions;
+				}
+			}
+			wl_data_source::Request::Destroy => (),
 			_ => unreachable!(),
 		}
 	}
+
+	fn destroyed(
+		_state: &mut WaylandState,
+		_client: ClientId,
+		resource: &WlDataSource,
+		_data: &Mutex<DataSourceData>,
+	) {
+		if clipboard::get_selection() == Some(Selection::Wayland(resource.clone())) {
+			clipboard::set_selection(None);
+		}
+		// there's nothing left to drop, so the drag just leaves wherever it was
+		let mut drag = DRAG.lock();
+		if drag.as_ref().and_then(|drag| drag.source.as_ref()) == Some(resource) {
+			if let Some(focus) = drag.take().and_then(|drag| drag.focus) {
+				focus.leave();
+			}
+		}
+	}
 }
 
-impl Dispatch<WlDataDevice, (), WaylandState> for WaylandState {
+impl Dispatch<WlDataDevice, Arc<SeatData>, WaylandState> for WaylandState {
 	fn request(
 		_state: &mut WaylandState,
 		_client: &Client,
-		_resource: &WlDataDevice,
+		resource: &WlDataDevice,
 		request: <WlDataDevice as Resource>::Request,
-		_data: &(),
+		_data: &Arc<SeatData>,
 		_dhandle: &DisplayHandle,
 		_data_init: &mut DataInit<'_, WaylandState>,
 	) {
 		match request {
-			StartDrag {
-				source: _,
-				origin: _,
-				icon: _,
+			wl_data_device::Request::StartDrag {
+				source,
+				origin,
+				icon,
 				serial: _,
-			} => {}
-			SetSelection {
-				source: _,
+			} => {
+				debug!(?source, ?origin, "Start drag");
+				if let Some(old_drag) = DRAG.lock().take() {
+					old_drag.finish();
+				}
+				*DRAG.lock() = Some(Drag {
+					source,
+					origin,
+					_icon: icon,
+					focus: None,
+				});
+			}
+			wl_data_device::Request::SetSelection { source, serial: _ } => {
+				clipboard::set_selection(source.map(Selection::Wayland));
+			}
+			wl_data_device::Request::Release => {
+				DATA_DEVICES.lock().retain(|device| device != resource);
+			}
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl Dispatch<WlDataOffer, DataOfferData, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		resource: &WlDataOffer,
+		request: <WlDataOffer as Resource>::Request,
+		data: &DataOfferData,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			wl_data_offer::Request::Accept {
 				serial: _,
-			} => {}
-			Release => {}
+				mime_type,
+			} => {
+				if !data.dnd {
+					return;
+				}
+				*data.accepted_mime_type.lock() = mime_type.clone();
+				if let Some(source) = data.wayland_source() {
+					source.target(mime_type);
+				}
+			}
+			wl_data_offer::Request::Receive { mime_type, fd } => {
+				// the source writes straight into the receiver's pipe, our copy of the fd closes once it's sent
+				data.source.send(mime_type, fd);
+			}
+			wl_data_offer::Request::Finish => {
+				if let Some(source) = data.wayland_source() {
+					if source.version() >= wl_data_source::EVT_DND_FINISHED_SINCE {
+						source.dnd_finished();
+					}
+				}
+			}
+			wl_data_offer::Request::SetActions {
+				dnd_actions,
+				preferred_action,
+			} => {
+				if !data.dnd {
+					return;
+				}
+				let (WEnum::Value(dnd_actions), WEnum::Value(preferred_action)) =
+					(dnd_actions, preferred_action)
+				else {
+					return;
+				};
+				let Some(source) = data.wayland_source() else {
+					return;
+				};
+				let possible_actions = dnd_actions & source_dnd_actions(source);
+				let action = if possible_actions.contains(preferred_action) {
+					preferred_action
+				} else if possible_actions.contains(DndAction::Copy) {
+					DndAction::Copy
+				} else if possible_actions.contains(DndAction::Move) {
+					DndAction::Move
+				} else if possible_actions.contains(DndAction::Ask) {
+					DndAction::Ask
+				} else {
+					DndAction::None
+				};
+				if resource.version() >= wl_data_offer::EVT_ACTION_SINCE {
+					resource.action(action);
+				}
+				if source.version() >= wl_data_source::EVT_ACTION_SINCE {
+					source.action(action);
+				}
+			}
+			wl_data_offer::Request::Destroy => (),
 			_ => unreachable!(),
 		}
 	}
@@ -0,0 +1,39 @@
+use super::{state::WaylandState, surface::CoreSurface};
+use smithay::{
+	delegate_fractional_scale, delegate_viewporter,
+	reexports::wayland_server::protocol::wl_surface::WlSurface,
+	wayland::{
+		compositor,
+		fractional_scale::{with_fractional_scale, FractionalScaleHandler},
+	},
+};
+
+impl FractionalScaleHandler for WaylandState {
+	fn new_fractional_scale(&mut self, surface: WlSurface) {
+		// clients usually ask for this before the surface has a role, so there may not be a core surface yet
+		let scale = CoreSurface::from_wl_surface(&surface)
+			.map(|core_surface| core_surface.preferred_scale())
+			.unwrap_or_else(CoreSurface::default_scale);
+		send_preferred_scale(&surface, scale);
+	}
+}
+delegate_fractional_scale!(WaylandState);
+delegate_viewporter!(WaylandState);
+
+/// Only does anything if the client asked for fractional scale on this surface.
+pub fn send_preferred_scale(surface: &WlSurface, scale: f64) {
+	compositor::with_states(surface, |states| {
+		with_fractional_scale(states, |fractional_scale| {
+			fractional_scale.set_preferred_scale(scale);
+		});
+	});
+}
+
+/// Set the preferred scale of all the surfaces (of one panel item usually), overriding their output's scale.
+pub fn set_surfaces_preferred_scale(surfaces: impl IntoIterator<Item = WlSurface>, scale: f64) {
+	for surface in surfaces {
+		if let Some(core_surface) = CoreSurface::from_wl_surface(&surface) {
+			core_surface.set_preferred_scale(scale);
+		}
+	}
+}
@@ -0,0 +1,604 @@
+use super::{
+	fractional_scale::set_surfaces_preferred_scale,
+	output::{output_infos, set_output_focus, set_surfaces_output},
+	pointer_constraints::handle_pointer_constraint,
+	seat::{handle_cursor, CursorInfo, KeyboardEvent, PointerEvent, SeatData},
+	state::{ClientState, WaylandState},
+	surface::CoreSurface,
+	text_input::handle_text_input,
+	xdg_shell::{attach_popup, PopupBackend, Popups},
+	SERIAL_COUNTER,
+};
+use crate::nodes::{
+	data::KEYMAPS,
+	drawable::model::ModelPart,
+	items::panel::{
+		Anchor, Backend, Geometry, KeyboardInteractivity, Layer, LayerSurfaceInfo, Margin,
+		PanelItem, PanelItemInitData, SurfaceCapture, SurfaceID, TabletToolEvent, TextInputCommit,
+		TextInputInfo, ToplevelInfo,
+	},
+};
+use color_eyre::eyre::{bail, eyre, Result};
+use mint::Vector2;
+use parking_lot::Mutex;
+use smithay::reexports::{
+	wayland_protocols_wlr::layer_shell::v1::server::{
+		zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+		zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+	},
+	wayland_server::{
+		protocol::wl_surface::WlSurface, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch,
+		New, Resource, WEnum, Weak as WlWeak,
+	},
+};
+use std::sync::{Arc, Weak};
+use tokio::sync::watch;
+use tracing::debug;
+
+/// Used for any dimension the client leaves up to us, there's no output edge to stretch it to in 3D
+const DEFAULT_SIZE: u32 = 1024;
+
+impl From<zwlr_layer_shell_v1::Layer> for Layer {
+	fn from(layer: zwlr_layer_shell_v1::Layer) -> Self {
+		match layer {
+			zwlr_layer_shell_v1::Layer::Background => Layer::Background,
+			zwlr_layer_shell_v1::Layer::Bottom => Layer::Bottom,
+			zwlr_layer_shell_v1::Layer::Top => Layer::Top,
+			_ => Layer::Overlay,
+		}
+	}
+}
+impl From<zwlr_layer_surface_v1::Anchor> for Anchor {
+	fn from(anchor: zwlr_layer_surface_v1::Anchor) -> Self {
+		Anchor {
+			top: anchor.contains(zwlr_layer_surface_v1::Anchor::Top),
+			bottom: anchor.contains(zwlr_layer_surface_v1::Anchor::Bottom),
+			left: anchor.contains(zwlr_layer_surface_v1::Anchor::Left),
+			right: anchor.contains(zwlr_layer_surface_v1::Anchor::Right),
+		}
+	}
+}
+impl From<zwlr_layer_surface_v1::KeyboardInteractivity> for KeyboardInteractivity {
+	fn from(interactivity: zwlr_layer_surface_v1::KeyboardInteractivity) -> Self {
+		match interactivity {
+			zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive => {
+				KeyboardInteractivity::Exclusive
+			}
+			zwlr_layer_surface_v1::KeyboardInteractivity::OnDemand => {
+				KeyboardInteractivity::OnDemand
+			}
+			_ => KeyboardInteractivity::None,
+		}
+	}
+}
+
+impl GlobalDispatch<ZwlrLayerShellV1, (), WaylandState> for WaylandState {
+	fn bind(
+		_state: &mut WaylandState,
+		_handle: &DisplayHandle,
+		_client: &Client,
+		resource: New<ZwlrLayerShellV1>,
+		_global_data: &(),
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		data_init.init(resource, ());
+	}
+}
+
+impl Dispatch<ZwlrLayerShellV1, (), WaylandState> for WaylandState {
+	fn request(
+		state: &mut WaylandState,
+		client: &Client,
+		_resource: &ZwlrLayerShellV1,
+		request: zwlr_layer_shell_v1::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwlr_layer_shell_v1::Request::GetLayerSurface {
+				id,
+				surface,
+				output: _,
+				layer,
+				namespace,
+			} => {
+				let layer = match layer {
+					WEnum::Value(layer) => layer.into(),
+					WEnum::Unknown(_) => Layer::Top,
+				};
+				let layer_surface = data_init.init(
+					id,
+					Mutex::new(LayerSurfaceData::new(&surface, namespace, layer)),
+				);
+				debug!(?layer_surface, ?surface, "Create layer surface");
+
+				let Some(seat_data) = client.get_data::<ClientState>().map(|s| s.seat.clone())
+				else {
+					return;
+				};
+				let client_pid = client
+					.get_credentials(&state.display_handle)
+					.ok()
+					.map(|c| c.pid);
+				CoreSurface::add_to(
+					state.display_handle.clone(),
+					&surface,
+					{
+						let layer_surface = layer_surface.downgrade();
+						move || {
+							let Ok(layer_surface) = layer_surface.upgrade() else {
+								return;
+							};
+							let Some(data) = LayerSurfaceData::get(&layer_surface) else {
+								return;
+							};
+							let Some(backend) =
+								LayerShellBackend::create(&layer_surface, seat_data.clone())
+							else {
+								return;
+							};
+							let panel_item = PanelItem::create(Box::new(backend), client_pid);
+							let mut data = data.lock();
+							data.panel_item = Arc::downgrade(&panel_item);
+							// the panel item's start data already has this
+							data.last_sent_info = Some(data.info());
+							drop(data);
+							handle_cursor(&panel_item, panel_item.backend.cursor.clone());
+							if let Some(wl_surface) = panel_item.backend.wl_surface() {
+								handle_text_input(
+									&panel_item,
+									&panel_item.backend.seat,
+									&wl_surface,
+								);
+								handle_pointer_constraint(
+									&panel_item,
+									&panel_item.backend.seat,
+									&wl_surface,
+									SurfaceID::Toplevel,
+								);
+							}
+						}
+					},
+					{
+						let layer_surface = layer_surface.downgrade();
+						move |_| {
+							let Ok(layer_surface) = layer_surface.upgrade() else {
+								return;
+							};
+							let Some(data) = LayerSurfaceData::get(&layer_surface) else {
+								return;
+							};
+							let mut data = data.lock();
+							let Some(panel_item) = data.panel_item() else {
+								// the client can't attach a buffer until it knows what size to make it
+								let size = data.configure_size();
+								drop(data);
+								layer_surface.configure(SERIAL_COUNTER.inc(), size.x, size.y);
+								return;
+							};
+							let info = data.info();
+							if data.last_sent_info.as_ref() != Some(&info) {
+								panel_item.layer_surface_changed(&info);
+								data.last_sent_info = Some(info);
+							}
+							drop(data);
+
+							let Some(wl_surface) = panel_item.backend.wl_surface() else {
+								return;
+							};
+							let Some(core_surface) = CoreSurface::from_wl_surface(&wl_surface)
+							else {
+								return;
+							};
+							let Some(size) = core_surface.size() else {
+								return;
+							};
+							panel_item.toplevel_size_changed(size);
+						}
+					},
+				);
+			}
+			zwlr_layer_shell_v1::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
+
+pub struct LayerSurfaceData {
+	wl_surface: WlWeak<WlSurface>,
+	panel_item: Weak<PanelItem<LayerShellBackend>>,
+	namespace: String,
+	layer: Layer,
+	size: Vector2<u32>,
+	anchor: Anchor,
+	exclusive_zone: i32,
+	margin: Margin,
+	keyboard_interactivity: KeyboardInteractivity,
+	/// So the panel item UI only gets told about actual changes
+	last_sent_info: Option<LayerSurfaceInfo>,
+}
+impl LayerSurfaceData {
+	fn new(wl_surface: &WlSurface, namespace: String, layer: Layer) -> Self {
+		LayerSurfaceData {
+			wl_surface: wl_surface.downgrade(),
+			panel_item: Weak::new(),
+			namespace,
+			layer,
+			size: [0, 0].into(),
+			anchor: Anchor::default(),
+			exclusive_zone: 0,
+			margin: Margin::default(),
+			keyboard_interactivity: KeyboardInteractivity::None,
+			last_sent_info: None,
+		}
+	}
+	fn get(layer_surface: &ZwlrLayerSurfaceV1) -> Option<&Mutex<Self>> {
+		layer_surface.data::<Mutex<Self>>()
+	}
+	fn panel_item(&self) -> Option<Arc<PanelItem<LayerShellBackend>>> {
+		self.panel_item.upgrade()
+	}
+	fn configure_size(&self) -> Vector2<u32> {
+		let or_default = |size: u32| if size == 0 { DEFAULT_SIZE } else { size };
+		[or_default(self.size.x), or_default(self.size.y)].into()
+	}
+	fn info(&self) -> LayerSurfaceInfo {
+		LayerSurfaceInfo {
+			namespace: self.namespace.clone(),
+			layer: self.layer,
+			anchor: self.anchor,
+			exclusive_zone: self.exclusive_zone,
+			margin: self.margin,
+			keyboard_interactivity: self.keyboard_interactivity,
+		}
+	}
+}
+impl Drop for LayerSurfaceData {
+	fn drop(&mut self) {
+		let Some(panel_item) = self.panel_item() else {
+			return;
+		};
+		panel_item.drop_toplevel();
+	}
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, Mutex<LayerSurfaceData>, WaylandState> for WaylandState {
+	fn request(
+		state: &mut WaylandState,
+		_client: &Client,
+		layer_surface: &ZwlrLayerSurfaceV1,
+		request: zwlr_layer_surface_v1::Request,
+		data: &Mutex<LayerSurfaceData>,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwlr_layer_surface_v1::Request::SetSize { width, height } => {
+				debug!(?layer_surface, width, height, "Set layer surface size");
+				data.lock().size = [width, height].into();
+			}
+			zwlr_layer_surface_v1::Request::SetAnchor { anchor } => {
+				if let WEnum::Value(anchor) = anchor {
+					data.lock().anchor = anchor.into();
+				}
+			}
+			zwlr_layer_surface_v1::Request::SetExclusiveZone { zone } => {
+				data.lock().exclusive_zone = zone;
+			}
+			zwlr_layer_surface_v1::Request::SetMargin {
+				top,
+				right,
+				bottom,
+				left,
+			} => {
+				data.lock().margin = Margin {
+					top,
+					right,
+					bottom,
+					left,
+				};
+			}
+			zwlr_layer_surface_v1::Request::SetKeyboardInteractivity {
+				keyboard_interactivity,
+			} => {
+				if let WEnum::Value(keyboard_interactivity) = keyboard_interactivity {
+					data.lock().keyboard_interactivity = keyboard_interactivity.into();
+				}
+			}
+			zwlr_layer_surface_v1::Request::SetLayer { layer } => {
+				if let WEnum::Value(layer) = layer {
+					data.lock().layer = layer.into();
+				}
+			}
+			zwlr_layer_surface_v1::Request::GetPopup { popup } => {
+				// the popup was made without a parent, so it only becomes part of the panel item here
+				debug!(?layer_surface, ?popup, "Layer surface popup");
+				let data = data.lock();
+				let Some(panel_item) = data.panel_item() else {
+					return;
+				};
+				let Ok(wl_surface) = data.wl_surface.upgrade() else {
+					return;
+				};
+				drop(data);
+				attach_popup(&state.display_handle, &popup, &wl_surface, panel_item);
+			}
+			zwlr_layer_surface_v1::Request::AckConfigure { serial: _ } => (),
+			// the panel item is dropped along with the layer surface's data
+			zwlr_layer_surface_v1::Request::Destroy => {
+				debug!(?layer_surface, "Destroy layer surface");
+			}
+			_ => unreachable!(),
+		}
+	}
+}
+
+pub struct LayerShellBackend {
+	layer_surface: WlWeak<ZwlrLayerSurfaceV1>,
+	wl_surface: WlWeak<WlSurface>,
+	cursor: watch::Receiver<Option<CursorInfo>>,
+	seat: Arc<SeatData>,
+	popups: Popups,
+}
+impl LayerShellBackend {
+	fn create(layer_surface: &ZwlrLayerSurfaceV1, seat: Arc<SeatData>) -> Option<Self> {
+		let wl_surface = LayerSurfaceData::get(layer_surface)?
+			.lock()
+			.wl_surface
+			.clone();
+		let cursor = seat.new_surface(&wl_surface.upgrade().ok()?);
+		Some(LayerShellBackend {
+			layer_surface: layer_surface.downgrade(),
+			wl_surface,
+			cursor,
+			seat,
+			popups: Popups::default(),
+		})
+	}
+	fn wl_surface(&self) -> Option<WlSurface> {
+		self.wl_surface.upgrade().ok()
+	}
+	/// The layer surface's surface and all the popups' surfaces
+	fn wl_surfaces(&self) -> Vec<WlSurface> {
+		self.wl_surface()
+			.into_iter()
+			.chain(self.popups.wl_surfaces())
+			.collect()
+	}
+	fn wl_surface_from_id(&self, id: &SurfaceID) -> Option<WlSurface> {
+		match id {
+			SurfaceID::Cursor => self.cursor.borrow().as_ref()?.surface.upgrade().ok(),
+			SurfaceID::Toplevel => self.wl_surface(),
+			SurfaceID::Child(popup) => self.popups.wl_surface(popup),
+		}
+	}
+
+	fn configure(&self, size: Vector2<u32>) {
+		let Ok(layer_surface) = self.layer_surface.upgrade() else {
+			return;
+		};
+		layer_surface.configure(SERIAL_COUNTER.inc(), size.x, size.y);
+		self.flush_client();
+	}
+	fn flush_client(&self) {
+		let Some(client) = self.wl_surface().and_then(|s| s.client()) else {
+			return;
+		};
+		if let Some(client_state) = client.get_data::<ClientState>() {
+			client_state.flush();
+		}
+	}
+}
+impl Drop for LayerShellBackend {
+	fn drop(&mut self) {
+		let Some(wl_surface) = self.wl_surface() else {
+			return;
+		};
+		self.seat.drop_surface(&wl_surface);
+	}
+}
+impl PopupBackend for LayerShellBackend {
+	fn seat(&self) -> &Arc<SeatData> {
+		&self.seat
+	}
+	fn popups(&self) -> &Popups {
+		&self.popups
+	}
+}
+impl Backend for LayerShellBackend {
+	fn start_data(&self) -> Result<PanelItemInitData> {
+		let Some(layer_surface) = self.layer_surface.upgrade().ok() else {
+			bail!("Layer surface not found")
+		};
+		let Some(data) = LayerSurfaceData::get(&layer_surface) else {
+			bail!("Layer surface data not found")
+		};
+		let layer_surface_info = data.lock().info();
+		let Some(wl_surface) = self.wl_surface() else {
+			bail!("Wayland surface not found")
+		};
+		let Some(core_surface) = CoreSurface::from_wl_surface(&wl_surface) else {
+			bail!("Core surface not found")
+		};
+		let Some(size) = core_surface.size() else {
+			bail!("Surface size not found")
+		};
+
+		Ok(PanelItemInitData {
+			cursor: self.cursor.borrow().as_ref().and_then(|c| c.cursor_data()),
+			toplevel: ToplevelInfo {
+				parent: None,
+				title: None,
+				app_id: None,
+				size,
+				min_size: None,
+				max_size: None,
+				logical_rectangle: Geometry {
+					origin: [0, 0].into(),
+					size,
+				},
+			},
+			children: self.popups.child_data(),
+			pointer_grab: None,
+			keyboard_grab: None,
+			layer_surface: Some(layer_surface_info),
+			outputs: output_infos(),
+			text_input: self.seat.text_input.info(&wl_surface),
+		})
+	}
+
+	fn apply_surface_material(&self, surface: SurfaceID, model_part: &Arc<ModelPart>) {
+		let Some(wl_surface) = self.wl_surface_from_id(&surface) else {
+			return;
+		};
+		let Some(core_surface) = CoreSurface::from_wl_surface(&wl_surface) else {
+			return;
+		};
+		core_surface.apply_material(model_part);
+	}
+	fn capture_surface(&self, surface: &SurfaceID) -> Result<SurfaceCapture> {
+		let core_surface = self
+			.wl_surface_from_id(surface)
+			.and_then(|wl_surface| CoreSurface::from_wl_surface(&wl_surface))
+			.ok_or_else(|| eyre!("Surface not found"))?;
+		Ok(core_surface.capture_to_fd())
+	}
+
+	fn close_toplevel(&self) {
+		let Ok(layer_surface) = self.layer_surface.upgrade() else {
+			return;
+		};
+		layer_surface.closed();
+		self.flush_client();
+	}
+	fn auto_size_toplevel(&self) {
+		let Ok(layer_surface) = self.layer_surface.upgrade() else {
+			return;
+		};
+		let Some(data) = LayerSurfaceData::get(&layer_surface) else {
+			return;
+		};
+		let size = data.lock().configure_size();
+		self.configure(size);
+	}
+	fn set_toplevel_size(&self, size: Vector2<u32>) {
+		self.configure(size);
+	}
+	// layer surfaces don't have an activated state, but launchers and such still have text fields
+	fn set_toplevel_focused_visuals(&self, focused: bool) {
+		if let Some(wl_surface) = self.wl_surface() {
+			self.seat.text_input.set_focused(&wl_surface, focused);
+			if focused {
+				set_output_focus(&wl_surface);
+			}
+		}
+		if !focused {
+			self.seat.pointer_constraints.deactivate(self.wl_surfaces());
+		}
+	}
+	fn set_output(&self, output: &str) -> Result<()> {
+		set_surfaces_output(self.wl_surfaces(), output)
+	}
+	fn set_preferred_scale(&self, scale: f64) {
+		set_surfaces_preferred_scale(self.wl_surfaces(), scale)
+	}
+
+	fn pointer_motion(&self, surface: &SurfaceID, position: Vector2<f32>) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat
+			.pointer_event(&surface, PointerEvent::Motion(position));
+	}
+	fn pointer_button(&self, surface: &SurfaceID, button: u32, pressed: bool) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat.pointer_event(
+			&surface,
+			PointerEvent::Button {
+				button,
+				state: if pressed { 1 } else { 0 },
+			},
+		)
+	}
+	fn pointer_scroll(
+		&self,
+		surface: &SurfaceID,
+		scroll_distance: Option<Vector2<f32>>,
+		scroll_steps: Option<Vector2<f32>>,
+	) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat.pointer_event(
+			&surface,
+			PointerEvent::Scroll {
+				axis_continuous: scroll_distance,
+				axis_discrete: scroll_steps,
+			},
+		)
+	}
+
+	fn pointer_relative_motion(&self, surface: &SurfaceID, delta: Vector2<f32>) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat.pointer_relative_motion(&surface, delta);
+	}
+
+	fn keyboard_keys(&self, surface: &SurfaceID, keymap_id: &str, keys: Vec<i32>) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		let keymaps = KEYMAPS.lock();
+		let Some(keymap) = keymaps.get(keymap_id).cloned() else {
+			return;
+		};
+		if self.seat.set_keymap(keymap, vec![surface.clone()]).is_err() {
+			return;
+		}
+		for key in keys {
+			self.seat.keyboard_event(
+				&surface,
+				KeyboardEvent::Key {
+					key: key.unsigned_abs(),
+					state: key > 0,
+				},
+			);
+		}
+	}
+
+	fn text_input_state(&self) -> Option<TextInputInfo> {
+		self.seat.text_input.info(&self.wl_surface()?)
+	}
+	fn text_input_commit(&self, commit: TextInputCommit) {
+		let Some(wl_surface) = self.wl_surface() else {
+			return;
+		};
+		self.seat.text_input.commit(&wl_surface, &commit);
+	}
+
+	fn touch_down(&self, surface: &SurfaceID, id: u32, position: Vector2<f32>) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat.touch_down(&surface, id, position)
+	}
+	fn touch_move(&self, id: u32, position: Vector2<f32>) {
+		self.seat.touch_move(id, position)
+	}
+	fn touch_up(&self, id: u32) {
+		self.seat.touch_up(id)
+	}
+	fn reset_touches(&self) {
+		self.seat.reset_touches()
+	}
+	fn tablet_tool_event(&self, surface: &SurfaceID, event: TabletToolEvent) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat.tablet.tool_event(&surface, event);
+	}
+}
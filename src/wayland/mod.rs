@@ -1,10 +1,17 @@
 mod compositor;
-mod data_device;
+pub mod data_device;
 mod decoration;
+mod fractional_scale;
+mod layer_shell;
+pub mod output;
+mod pointer_constraints;
+mod screencopy;
 mod seat;
 mod state;
 mod surface;
-// mod xdg_activation;
+mod tablet;
+mod text_input;
+pub mod xdg_activation;
 mod drm;
 mod xdg_shell;
 #[cfg(feature = "xwayland_rootful")]
@@ -18,18 +25,28 @@ pub mod xwayland_rootless;
 #[cfg(feature = "xwayland_rootless")]
 use self::xwayland_rootless::XWaylandState;
 
-use self::{state::WaylandState, surface::CORE_SURFACES};
+use self::{
+	output::{OutputConfig, RefreshRateTracker},
+	state::WaylandState,
+	surface::CORE_SURFACES,
+};
+use crate::nodes::drawable::texture::{self, DynamicTexture};
 use crate::wayland::seat::SeatData;
-use crate::{core::task, wayland::state::ClientState};
-use color_eyre::eyre::{ensure, Result};
+use crate::{
+	core::{permissions::Permissions, task},
+	wayland::state::ClientState,
+};
+use color_eyre::eyre::{ensure, eyre, Result};
 use global_counter::primitive::exact::CounterU32;
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
 use parking_lot::Mutex;
+use send_wrapper::SendWrapper;
 use sk::StereoKitDraw;
-use smithay::backend::allocator::dmabuf::Dmabuf;
+use smithay::backend::allocator::dmabuf::{Dmabuf, DmabufFlags};
+use smithay::backend::allocator::{Fourcc, Modifier};
 use smithay::backend::egl::EGLContext;
-use smithay::backend::renderer::gles::GlesRenderer;
-use smithay::backend::renderer::ImportDma;
+use smithay::backend::renderer::gles::{ffi, GlesRenderer};
+use smithay::backend::renderer::{ImportDma, Texture};
 use smithay::reexports::wayland_server::backend::ClientId;
 use smithay::reexports::wayland_server::DisplayHandle;
 use smithay::reexports::wayland_server::{Display, ListeningSocket};
@@ -41,17 +58,27 @@ use std::{
 	ffi::c_void,
 	os::unix::{net::UnixListener, prelude::FromRawFd},
 	sync::Arc,
+	time::Instant,
 };
 use stereokit as sk;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::{
 	io::unix::AsyncFd, net::UnixListener as AsyncUnixListener, sync::mpsc, task::JoinHandle,
 };
-use tracing::{debug_span, info, instrument};
+use tracing::{debug_span, info, instrument, warn};
 
 pub static X_DISPLAY: OnceCell<u32> = OnceCell::new();
 pub static WAYLAND_DISPLAY: OnceCell<String> = OnceCell::new();
 pub static SERIAL_COUNTER: CounterU32 = CounterU32::new(0);
+/// Input event timestamps are in milliseconds since this, wrapping around like the protocol expects
+static EVENT_TIME_START: Lazy<Instant> = Lazy::new(Instant::now);
+pub fn event_time() -> u32 {
+	EVENT_TIME_START.elapsed().as_millis() as u32
+}
+/// Same clock as `event_time` in microseconds, for events that need finer timing like relative pointer motion
+pub fn event_time_us() -> u64 {
+	EVENT_TIME_START.elapsed().as_micros() as u64
+}
 
 struct EGLRawHandles {
 	display: *const c_void,
@@ -98,14 +125,15 @@ pub struct Wayland {
 	join_handle: JoinHandle<Result<()>>,
 	renderer: GlesRenderer,
 	dmabuf_rx: UnboundedReceiver<(Dmabuf, Option<dmabuf::ImportNotifier>)>,
-	wayland_state: Arc<Mutex<WaylandState>>,
+	_wayland_state: Arc<Mutex<WaylandState>>,
+	refresh_rate_tracker: RefreshRateTracker,
 	#[cfg(feature = "xwayland_rootful")]
 	pub x_lock: X11Lock,
 	#[cfg(feature = "xwayland_rootless")]
 	pub xwayland_state: XWaylandState,
 }
 impl Wayland {
-	pub fn new() -> Result<Self> {
+	pub fn new(output_configs: Vec<OutputConfig>) -> Result<Self> {
 		let egl_raw_handles = get_sk_egl()?;
 		let renderer = unsafe {
 			GlesRenderer::new(EGLContext::from_raw(
@@ -123,7 +151,8 @@ impl Wayland {
 
 		#[cfg(feature = "xwayland_rootless")]
 		let xwayland_state = XWaylandState::create(&display_handle)?;
-		let wayland_state = WaylandState::new(display_handle, &renderer, dmabuf_tx);
+		let wayland_state =
+			WaylandState::new(display_handle, &renderer, dmabuf_tx, &output_configs);
 
 		let socket = ListeningSocket::bind_auto("wayland", 0..33)?;
 		let socket_name = socket
@@ -137,6 +166,7 @@ impl Wayland {
 		let x_display = start_xwayland(socket.as_raw_fd())?;
 		info!(socket_name, "Wayland active");
 
+		xdg_activation::set_wayland_state(&wayland_state);
 		let join_handle = Wayland::start_loop(display.clone(), socket, wayland_state.clone())?;
 
 		Ok(Wayland {
@@ -145,7 +175,8 @@ impl Wayland {
 			join_handle,
 			renderer,
 			dmabuf_rx,
-			wayland_state,
+			_wayland_state: wayland_state,
+			refresh_rate_tracker: RefreshRateTracker::default(),
 			#[cfg(feature = "xwayland_rootful")]
 			x_lock: x_display,
 			#[cfg(feature = "xwayland_rootless")]
@@ -173,11 +204,17 @@ impl Wayland {
 				tokio::select! {
 					acc = listen_async.accept() => { // New client connected
 						let (stream, _) = acc?;
+						let exe = stream
+							.peer_cred()
+							.ok()
+							.and_then(|c| c.pid())
+							.and_then(|pid| std::fs::read_link(format!("/proc/{pid}/exe")).ok());
 						let client_state = Arc::new(ClientState {
 							id: OnceCell::new(),
 							compositor_state: Default::default(),
 							display: Arc::downgrade(&display),
-							seat: SeatData::new(&dh1)
+							seat: SeatData::new(&dh1),
+							permissions: Permissions::for_exe(exe.as_deref()),
 						});
 						let client = dh2.insert_client(stream.into_std()?, client_state.clone())?;
 						let _ = client_state.seat.client.set(client.id());
@@ -208,15 +245,56 @@ impl Wayland {
 		for core_surface in CORE_SURFACES.get_valid_contents() {
 			core_surface.process(sk, &mut self.renderer);
 		}
+		for texture in texture::pending_dmabuf_textures() {
+			if let Err(error) = self.import_dmabuf_texture(sk, &texture) {
+				warn!(?error, "Failed to import dmabuf texture");
+			}
+		}
 
 		self.display.flush_clients(None);
 	}
 
-	pub fn frame_event(&self, sk: &impl StereoKitDraw) {
-		let output = self.wayland_state.lock().output.clone();
+	/// Dynamic textures made from dmabufs can only be imported where the renderer lives.
+	fn import_dmabuf_texture(
+		&mut self,
+		sk: &impl StereoKitDraw,
+		texture: &DynamicTexture,
+	) -> Result<()> {
+		let Some(info) = texture.take_pending_dmabuf() else {
+			return Ok(());
+		};
+		let format = Fourcc::try_from(info.format).map_err(|_| eyre!("Unknown dmabuf format"))?;
+		let mut builder = Dmabuf::builder(
+			(info.size.x as i32, info.size.y as i32),
+			format,
+			DmabufFlags::empty(),
+		);
+		for (i, plane) in info.planes.into_iter().enumerate() {
+			builder.add_plane(
+				plane.fd,
+				i as u32,
+				plane.offset,
+				plane.stride,
+				Modifier::from(info.modifier),
+			);
+		}
+		let dmabuf = builder.build().ok_or_else(|| eyre!("Invalid dmabuf"))?;
+		let gles_tex = self.renderer.import_dmabuf(&dmabuf, None)?;
+		unsafe {
+			texture.set_imported(
+				sk,
+				gles_tex.tex_id(),
+				ffi::RGBA8.into(),
+				Box::new(SendWrapper::new((gles_tex, dmabuf))),
+			);
+		}
+		Ok(())
+	}
 
+	pub fn frame_event(&mut self, sk: &impl StereoKitDraw) {
+		self.refresh_rate_tracker.frame();
 		for core_surface in CORE_SURFACES.get_valid_contents() {
-			core_surface.frame(sk, output.clone());
+			core_surface.frame(sk);
 		}
 	}
 
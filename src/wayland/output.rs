@@ -0,0 +1,301 @@
+use super::{state::WaylandState, surface::CoreSurface};
+use crate::nodes::items::panel::OutputInfo;
+use color_eyre::eyre::{eyre, Result};
+use directories::ProjectDirs;
+use mint::Vector2;
+use parking_lot::{const_mutex, Mutex};
+use smithay::{
+	output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
+	reexports::wayland_server::{
+		protocol::wl_surface::WlSurface, DisplayHandle, Resource, Weak as WlWeak,
+	},
+	utils::{Size, Transform},
+};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// Used until the headset's display says otherwise, or if it never does
+const DEFAULT_REFRESH_RATE: f64 = 60.0;
+
+/// Every output, the first one is where surfaces start out
+static OUTPUTS: Mutex<Vec<Output>> = const_mutex(Vec::new());
+
+/// Nothing gets composited onto outputs in XR, so capturing one shows the surface last given focused visuals on it instead
+#[derive(Default)]
+struct OutputFocus(Mutex<Option<WlWeak<WlSurface>>>);
+
+/// A virtual output panels can be put on, parsed from `name=WIDTHxHEIGHT@SCALE` (the scale defaults to 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputConfig {
+	pub name: String,
+	pub size: Vector2<u32>,
+	pub scale: i32,
+}
+impl Default for OutputConfig {
+	fn default() -> Self {
+		OutputConfig {
+			name: "1x".to_string(),
+			size: [2048, 2048].into(),
+			scale: 2,
+		}
+	}
+}
+impl FromStr for OutputConfig {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, mode) = s
+			.split_once('=')
+			.ok_or_else(|| format!("\"{s}\" isn't in the form name=WIDTHxHEIGHT@SCALE"))?;
+		let (size, scale) = mode.split_once('@').unwrap_or((mode, "1"));
+		let (width, height) = size
+			.split_once('x')
+			.ok_or_else(|| format!("\"{size}\" isn't in the form WIDTHxHEIGHT"))?;
+		let parse = |number: &str| -> Result<u32, String> {
+			number
+				.trim()
+				.parse()
+				.map_err(|_| format!("\"{number}\" isn't a valid number"))
+		};
+		let config = OutputConfig {
+			name: name.trim().to_string(),
+			size: [parse(width)?, parse(height)?].into(),
+			scale: parse(scale)? as i32,
+		};
+		if config.name.is_empty() || config.size.x == 0 || config.size.y == 0 || config.scale == 0 {
+			return Err(format!("\"{s}\" needs a name and a nonzero size and scale"));
+		}
+		Ok(config)
+	}
+}
+impl OutputConfig {
+	/// The outputs given on the command line, otherwise the ones in `outputs` in the stardust config dir (one per line, `#` starts a comment), otherwise just the default.
+	pub fn load(cli_outputs: &[OutputConfig]) -> Vec<OutputConfig> {
+		if !cli_outputs.is_empty() {
+			return cli_outputs.to_vec();
+		}
+		let config_outputs = ProjectDirs::from("", "", "stardust")
+			.map(|dirs| dirs.config_dir().join("outputs"))
+			.and_then(|path| std::fs::read_to_string(path).ok())
+			.map(|outputs| OutputConfig::parse_config(&outputs))
+			.unwrap_or_default();
+		if config_outputs.is_empty() {
+			vec![OutputConfig::default()]
+		} else {
+			config_outputs
+		}
+	}
+	/// Every valid output in the config file, skipping comments, blank lines and invalid outputs.
+	fn parse_config(outputs: &str) -> Vec<OutputConfig> {
+		outputs
+			.lines()
+			.map(|line| line.split('#').next().unwrap_or_default().trim())
+			.filter(|line| !line.is_empty())
+			.filter_map(|line| {
+				line.parse()
+					.map_err(|error| warn!(%error, "Invalid output in config"))
+					.ok()
+			})
+			.collect()
+	}
+}
+
+fn mode(size: Vector2<u32>, refresh_rate: f64) -> Mode {
+	Mode {
+		size: (size.x as i32, size.y as i32).into(),
+		refresh: (refresh_rate * 1000.0).round() as i32,
+	}
+}
+
+pub fn create_outputs(display_handle: &DisplayHandle, configs: &[OutputConfig]) {
+	let mut outputs = OUTPUTS.lock();
+	for config in configs {
+		if outputs.iter().any(|output| output.name() == config.name) {
+			warn!(name = %config.name, "Skipped output with duplicate name");
+			continue;
+		}
+		let output = Output::new(
+			config.name.clone(),
+			PhysicalProperties {
+				size: Size::default(),
+				subpixel: Subpixel::None,
+				make: "Virtual XR Display".to_owned(),
+				model: "Your Headset Name Here".to_owned(),
+			},
+		);
+		let _output_global = output.create_global::<WaylandState>(display_handle);
+		let mode = mode(config.size, DEFAULT_REFRESH_RATE);
+		output.change_current_state(
+			Some(mode),
+			Some(Transform::Normal),
+			Some(Scale::Integer(config.scale)),
+			None,
+		);
+		output.set_preferred(mode);
+		info!(?config, "Created output");
+		outputs.push(output);
+	}
+}
+
+pub fn default_output() -> Option<Output> {
+	OUTPUTS.lock().first().cloned()
+}
+pub fn output_infos() -> Vec<OutputInfo> {
+	OUTPUTS
+		.lock()
+		.iter()
+		.filter_map(|output| {
+			let mode = output.current_mode()?;
+			Some(OutputInfo {
+				name: output.name(),
+				size: [mode.size.w as u32, mode.size.h as u32].into(),
+				scale: output.current_scale().integer_scale(),
+			})
+		})
+		.collect()
+}
+
+/// Move all the surfaces (of one panel item usually) to the output with this name.
+pub fn set_surfaces_output(
+	surfaces: impl IntoIterator<Item = WlSurface>,
+	output_name: &str,
+) -> Result<()> {
+	let output = OUTPUTS
+		.lock()
+		.iter()
+		.find(|output| output.name() == output_name)
+		.cloned()
+		.ok_or_else(|| eyre!("No output named {output_name}"))?;
+	for surface in surfaces {
+		if let Some(core_surface) = CoreSurface::from_wl_surface(&surface) {
+			core_surface.set_output(output.clone());
+		}
+	}
+	Ok(())
+}
+
+/// Make this surface what capturing its output shows.
+pub fn set_output_focus(surface: &WlSurface) {
+	let Some(output) = CoreSurface::from_wl_surface(surface)
+		.and_then(|core_surface| core_surface.output())
+		.or_else(default_output)
+	else {
+		return;
+	};
+	output
+		.user_data()
+		.insert_if_missing_threadsafe(OutputFocus::default);
+	let Some(focus) = output.user_data().get::<OutputFocus>() else {
+		return;
+	};
+	*focus.0.lock() = Some(surface.downgrade());
+}
+pub fn output_focus(output: &Output) -> Option<WlSurface> {
+	let focus = output.user_data().get::<OutputFocus>()?.0.lock().clone()?;
+	focus.upgrade().ok()
+}
+
+/// Keeps the outputs' refresh rate matching the headset's display.
+pub struct RefreshRateTracker {
+	/// In mHz, like modes
+	refresh: i32,
+}
+impl Default for RefreshRateTracker {
+	fn default() -> Self {
+		RefreshRateTracker {
+			refresh: mode([0, 0].into(), DEFAULT_REFRESH_RATE).refresh,
+		}
+	}
+}
+impl RefreshRateTracker {
+	pub fn frame(&mut self) {
+		// 0 when the runtime can't tell (e.g. in flatscreen mode)
+		let refresh_rate = unsafe { stereokit::sys::device_display_get_refresh_rate() } as f64;
+		let refresh_rate = if refresh_rate > 0.0 {
+			refresh_rate
+		} else {
+			DEFAULT_REFRESH_RATE
+		};
+		let refresh = mode([0, 0].into(), refresh_rate).refresh;
+		if refresh == self.refresh {
+			return;
+		}
+		self.refresh = refresh;
+		for output in OUTPUTS.lock().iter() {
+			let Some(current_mode) = output.current_mode() else {
+				continue;
+			};
+			// so clients binding later only see the mode that's actually in use
+			output.delete_mode(current_mode);
+			let size = [current_mode.size.w as u32, current_mode.size.h as u32].into();
+			let mode = mode(size, refresh_rate);
+			output.change_current_state(Some(mode), None, None, None);
+			output.set_preferred(mode);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::OutputConfig;
+
+	fn output(name: &str, width: u32, height: u32, scale: i32) -> OutputConfig {
+		OutputConfig {
+			name: name.to_string(),
+			size: [width, height].into(),
+			scale,
+		}
+	}
+
+	#[test]
+	fn parse_output() {
+		assert_eq!(
+			"left=1920x1080@2".parse(),
+			Ok(output("left", 1920, 1080, 2))
+		);
+		assert_eq!(
+			" left = 1920 x 1080 @ 2 ".parse(),
+			Ok(output("left", 1920, 1080, 2))
+		);
+	}
+	#[test]
+	fn scale_defaults_to_1() {
+		assert_eq!("left=1920x1080".parse(), Ok(output("left", 1920, 1080, 1)));
+	}
+	#[test]
+	fn invalid_outputs() {
+		for invalid in [
+			"",
+			"left",
+			"1920x1080@2",
+			"=1920x1080@2",
+			"left=1920",
+			"left=1920x@2",
+			"left=widexhigh",
+			"left=0x1080",
+			"left=1920x0",
+			"left=1920x1080@0",
+			"left=1920x1080@-1",
+		] {
+			assert!(
+				invalid.parse::<OutputConfig>().is_err(),
+				"{invalid:?} shouldn't parse"
+			);
+		}
+	}
+	#[test]
+	fn parse_config() {
+		let config = "\
+# outputs for the desk
+left=1920x1080@2 # the big one
+
+right=1280x720
+broken=0x0
+   # indented comment
+";
+		assert_eq!(
+			OutputConfig::parse_config(config),
+			vec![output("left", 1920, 1080, 2), output("right", 1280, 720, 1)]
+		);
+		assert!(OutputConfig::parse_config("# nothing here\n\n").is_empty());
+	}
+}
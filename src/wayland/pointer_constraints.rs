@@ -0,0 +1,471 @@
+use super::{event_time_us, seat::SeatData, state::WaylandState};
+use crate::{
+	core::task,
+	nodes::items::panel::{
+		Backend, Geometry, PanelItem, PointerConstraint, PointerConstraintKind, SurfaceID,
+	},
+};
+use mint::Vector2;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use smithay::{
+	reexports::{
+		wayland_protocols::wp::{
+			pointer_constraints::zv1::server::{
+				zwp_confined_pointer_v1::{self, ZwpConfinedPointerV1},
+				zwp_locked_pointer_v1::{self, ZwpLockedPointerV1},
+				zwp_pointer_constraints_v1::{self, Lifetime, ZwpPointerConstraintsV1},
+			},
+			relative_pointer::zv1::server::{
+				zwp_relative_pointer_manager_v1::{self, ZwpRelativePointerManagerV1},
+				zwp_relative_pointer_v1::{self, ZwpRelativePointerV1},
+			},
+		},
+		wayland_server::{
+			backend::{ClientId, ObjectId},
+			protocol::{wl_region::WlRegion, wl_surface::WlSurface},
+			Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+		},
+	},
+	wayland::compositor::{self, RectangleKind},
+};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::debug;
+
+fn region_geometry(region: Option<WlRegion>) -> Option<Vec<Geometry>> {
+	let region = region?;
+	Some(
+		compositor::get_region_attributes(&region)
+			.rects
+			.into_iter()
+			// subtracted rectangles are rare enough for constraints that the panel UI only gets the added ones
+			.filter(|(kind, _)| matches!(kind, RectangleKind::Add))
+			.map(|(_, rect)| Geometry {
+				origin: [rect.loc.x, rect.loc.y].into(),
+				size: [rect.size.w.max(0) as u32, rect.size.h.max(0) as u32].into(),
+			})
+			.collect(),
+	)
+}
+
+#[derive(Debug, Clone)]
+enum ConstraintObject {
+	Locked(ZwpLockedPointerV1),
+	Confined(ZwpConfinedPointerV1),
+}
+impl ConstraintObject {
+	fn activate(&self) {
+		match self {
+			ConstraintObject::Locked(locked_pointer) => locked_pointer.locked(),
+			ConstraintObject::Confined(confined_pointer) => confined_pointer.confined(),
+		}
+	}
+	fn deactivate(&self) {
+		match self {
+			ConstraintObject::Locked(locked_pointer) => locked_pointer.unlocked(),
+			ConstraintObject::Confined(confined_pointer) => confined_pointer.unconfined(),
+		}
+	}
+	fn id(&self) -> ObjectId {
+		match self {
+			ConstraintObject::Locked(locked_pointer) => locked_pointer.id(),
+			ConstraintObject::Confined(confined_pointer) => confined_pointer.id(),
+		}
+	}
+}
+
+struct Constraint {
+	object: ConstraintObject,
+	/// Oneshot constraints are gone for good once they're deactivated
+	oneshot: bool,
+	info: PointerConstraint,
+}
+
+/// The data of a locked or confined pointer object
+pub struct ConstraintData {
+	seat: Arc<SeatData>,
+	surface: ObjectId,
+}
+
+/// The pointer constraint and relative pointer half of a seat.
+pub struct SeatPointerConstraints {
+	constraints: Mutex<FxHashMap<ObjectId, Constraint>>,
+	relative_pointers: Mutex<Vec<ZwpRelativePointerV1>>,
+	/// The constraint on each surface that has one
+	info: watch::Sender<FxHashMap<ObjectId, PointerConstraint>>,
+}
+impl Default for SeatPointerConstraints {
+	fn default() -> Self {
+		SeatPointerConstraints {
+			constraints: Mutex::new(FxHashMap::default()),
+			relative_pointers: Mutex::new(Vec::new()),
+			info: watch::channel(FxHashMap::default()).0,
+		}
+	}
+}
+impl SeatPointerConstraints {
+	/// Activate the surface's constraint since the pointer is on it now.
+	pub fn activate(&self, surface: &WlSurface) {
+		let mut constraints = self.constraints.lock();
+		let Some(constraint) = constraints.get_mut(&surface.id()) else {
+			return;
+		};
+		if constraint.info.active {
+			return;
+		}
+		constraint.info.active = true;
+		constraint.object.activate();
+		drop(constraints);
+		self.send_info();
+	}
+	/// Deactivate the constraints on these surfaces, e.g. because their toplevel isn't focused anymore.
+	pub fn deactivate(&self, surfaces: impl IntoIterator<Item = WlSurface>) {
+		let mut constraints = self.constraints.lock();
+		for surface in surfaces {
+			let Some(constraint) = constraints.get_mut(&surface.id()) else {
+				continue;
+			};
+			if !constraint.info.active {
+				continue;
+			}
+			constraint.info.active = false;
+			constraint.object.deactivate();
+			if constraint.oneshot {
+				constraints.remove(&surface.id());
+			}
+		}
+		drop(constraints);
+		self.send_info();
+	}
+	/// Whether the pointer is locked in place on this surface, so absolute motion shouldn't be sent.
+	pub fn is_locked(&self, surface: &WlSurface) -> bool {
+		self.constraints
+			.lock()
+			.get(&surface.id())
+			.map(|constraint| {
+				constraint.info.active && constraint.info.kind == PointerConstraintKind::Lock
+			})
+			.unwrap_or(false)
+	}
+
+	pub fn relative_motion(&self, delta: Vector2<f32>) {
+		let time = event_time_us();
+		for relative_pointer in self.relative_pointers.lock().iter() {
+			// there's no pointer acceleration in XR so both are the same
+			relative_pointer.relative_motion(
+				(time >> 32) as u32,
+				time as u32,
+				delta.x as f64,
+				delta.y as f64,
+				delta.x as f64,
+				delta.y as f64,
+			);
+		}
+	}
+
+	fn add(&self, surface: &WlSurface, constraint: Constraint) -> bool {
+		let mut constraints = self.constraints.lock();
+		if constraints.contains_key(&surface.id()) {
+			return false;
+		}
+		constraints.insert(surface.id(), constraint);
+		drop(constraints);
+		self.send_info();
+		true
+	}
+	fn update(&self, surface: &ObjectId, update: impl FnOnce(&mut PointerConstraint)) {
+		let mut constraints = self.constraints.lock();
+		let Some(constraint) = constraints.get_mut(surface) else {
+			return;
+		};
+		update(&mut constraint.info);
+		drop(constraints);
+		self.send_info();
+	}
+	fn remove(&self, surface: &ObjectId, object: &ObjectId) {
+		let mut constraints = self.constraints.lock();
+		// a oneshot constraint may have been replaced already
+		let is_this_constraint = constraints
+			.get(surface)
+			.map(|constraint| &constraint.object.id() == object)
+			.unwrap_or(false);
+		if !is_this_constraint {
+			return;
+		}
+		constraints.remove(surface);
+		drop(constraints);
+		self.send_info();
+	}
+	fn send_info(&self) {
+		let info = self
+			.constraints
+			.lock()
+			.iter()
+			.map(|(surface, constraint)| (surface.clone(), constraint.info.clone()))
+			.collect::<FxHashMap<_, _>>();
+		self.info.send_if_modified(|old_info| {
+			let changed = *old_info != info;
+			*old_info = info;
+			changed
+		});
+	}
+}
+
+/// Sends `pointer_constraint_changed` to the panel item whenever the constraint on this surface changes.
+pub fn handle_pointer_constraint<B: Backend>(
+	panel_item: &Arc<PanelItem<B>>,
+	seat: &SeatData,
+	wl_surface: &WlSurface,
+	surface_id: SurfaceID,
+) {
+	let panel_item_weak = Arc::downgrade(panel_item);
+	let wl_surface_id = wl_surface.id();
+	let mut info = seat.pointer_constraints.info.subscribe();
+	let _ = task::new(|| "pointer constraint handler", async move {
+		let mut last_constraint = None;
+		while info.changed().await.is_ok() {
+			let Some(panel_item) = panel_item_weak.upgrade() else {
+				break;
+			};
+			let constraint = info.borrow().get(&wl_surface_id).cloned();
+			if constraint != last_constraint {
+				panel_item.pointer_constraint_changed(&surface_id, constraint.as_ref());
+				last_constraint = constraint;
+			}
+		}
+	});
+}
+
+impl GlobalDispatch<ZwpPointerConstraintsV1, (), WaylandState> for WaylandState {
+	fn bind(
+		_state: &mut WaylandState,
+		_handle: &DisplayHandle,
+		_client: &Client,
+		resource: New<ZwpPointerConstraintsV1>,
+		_global_data: &(),
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		data_init.init(resource, ());
+	}
+}
+impl Dispatch<ZwpPointerConstraintsV1, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		pointer_constraints: &ZwpPointerConstraintsV1,
+		request: zwp_pointer_constraints_v1::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		let (surface, seat, region, lifetime, object) = match request {
+			zwp_pointer_constraints_v1::Request::LockPointer {
+				id,
+				surface,
+				pointer,
+				region,
+				lifetime,
+			} => {
+				// every pointer is created with its seat's data
+				let seat = pointer.data::<Arc<SeatData>>().unwrap().clone();
+				let locked_pointer = data_init.init(
+					id,
+					ConstraintData {
+						seat: seat.clone(),
+						surface: surface.id(),
+					},
+				);
+				let object = ConstraintObject::Locked(locked_pointer);
+				(surface, seat, region, lifetime, object)
+			}
+			zwp_pointer_constraints_v1::Request::ConfinePointer {
+				id,
+				surface,
+				pointer,
+				region,
+				lifetime,
+			} => {
+				let seat = pointer.data::<Arc<SeatData>>().unwrap().clone();
+				let confined_pointer = data_init.init(
+					id,
+					ConstraintData {
+						seat: seat.clone(),
+						surface: surface.id(),
+					},
+				);
+				let object = ConstraintObject::Confined(confined_pointer);
+				(surface, seat, region, lifetime, object)
+			}
+			zwp_pointer_constraints_v1::Request::Destroy => return,
+			_ => unreachable!(),
+		};
+		debug!(?surface, ?object, "Pointer constraint");
+
+		let kind = match &object {
+			ConstraintObject::Locked(_) => PointerConstraintKind::Lock,
+			ConstraintObject::Confined(_) => PointerConstraintKind::Confine,
+		};
+		let constraint = Constraint {
+			object,
+			oneshot: !matches!(lifetime, WEnum::Value(Lifetime::Persistent)),
+			info: PointerConstraint {
+				kind,
+				active: false,
+				region: region_geometry(region),
+				cursor_position_hint: None,
+			},
+		};
+		if !seat.pointer_constraints.add(&surface, constraint) {
+			pointer_constraints.post_error(
+				zwp_pointer_constraints_v1::Error::AlreadyConstrained,
+				"Surface already has a pointer constraint",
+			);
+		}
+	}
+}
+
+impl Dispatch<ZwpLockedPointerV1, ConstraintData, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_locked_pointer: &ZwpLockedPointerV1,
+		request: zwp_locked_pointer_v1::Request,
+		data: &ConstraintData,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_locked_pointer_v1::Request::SetCursorPositionHint {
+				surface_x,
+				surface_y,
+			} => {
+				data.seat
+					.pointer_constraints
+					.update(&data.surface, |constraint| {
+						constraint.cursor_position_hint =
+							Some([surface_x as f32, surface_y as f32].into());
+					});
+			}
+			zwp_locked_pointer_v1::Request::SetRegion { region } => {
+				let region = region_geometry(region);
+				data.seat
+					.pointer_constraints
+					.update(&data.surface, |constraint| constraint.region = region);
+			}
+			zwp_locked_pointer_v1::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+
+	fn destroyed(
+		_state: &mut WaylandState,
+		_client: ClientId,
+		locked_pointer: &ZwpLockedPointerV1,
+		data: &ConstraintData,
+	) {
+		data.seat
+			.pointer_constraints
+			.remove(&data.surface, &locked_pointer.id());
+	}
+}
+
+impl Dispatch<ZwpConfinedPointerV1, ConstraintData, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_confined_pointer: &ZwpConfinedPointerV1,
+		request: zwp_confined_pointer_v1::Request,
+		data: &ConstraintData,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_confined_pointer_v1::Request::SetRegion { region } => {
+				let region = region_geometry(region);
+				data.seat
+					.pointer_constraints
+					.update(&data.surface, |constraint| constraint.region = region);
+			}
+			zwp_confined_pointer_v1::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+
+	fn destroyed(
+		_state: &mut WaylandState,
+		_client: ClientId,
+		confined_pointer: &ZwpConfinedPointerV1,
+		data: &ConstraintData,
+	) {
+		data.seat
+			.pointer_constraints
+			.remove(&data.surface, &confined_pointer.id());
+	}
+}
+
+impl GlobalDispatch<ZwpRelativePointerManagerV1, (), WaylandState> for WaylandState {
+	fn bind(
+		_state: &mut WaylandState,
+		_handle: &DisplayHandle,
+		_client: &Client,
+		resource: New<ZwpRelativePointerManagerV1>,
+		_global_data: &(),
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		data_init.init(resource, ());
+	}
+}
+impl Dispatch<ZwpRelativePointerManagerV1, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpRelativePointerManagerV1,
+		request: zwp_relative_pointer_manager_v1::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_relative_pointer_manager_v1::Request::GetRelativePointer { id, pointer } => {
+				let seat = pointer.data::<Arc<SeatData>>().unwrap().clone();
+				let relative_pointer = data_init.init(id, seat.clone());
+				seat.pointer_constraints
+					.relative_pointers
+					.lock()
+					.push(relative_pointer);
+			}
+			zwp_relative_pointer_manager_v1::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl Dispatch<ZwpRelativePointerV1, Arc<SeatData>, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpRelativePointerV1,
+		request: zwp_relative_pointer_v1::Request,
+		_data: &Arc<SeatData>,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_relative_pointer_v1::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+
+	fn destroyed(
+		_state: &mut WaylandState,
+		_client: ClientId,
+		relative_pointer: &ZwpRelativePointerV1,
+		seat: &Arc<SeatData>,
+	) {
+		seat.pointer_constraints
+			.relative_pointers
+			.lock()
+			.retain(|r| r != relative_pointer);
+	}
+}
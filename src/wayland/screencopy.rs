@@ -0,0 +1,236 @@
+use super::{
+	output::output_focus,
+	state::{ClientState, WaylandState},
+	surface::CoreSurface,
+};
+use crate::{core::permissions::Capability, nodes::items::panel::SurfaceCaptureInfo};
+use smithay::{
+	output::Output,
+	reexports::{
+		wayland_protocols_wlr::screencopy::v1::server::{
+			zwlr_screencopy_frame_v1::{self, Flags, ZwlrScreencopyFrameV1},
+			zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+		},
+		wayland_server::{
+			protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm, wl_surface::WlSurface},
+			Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+			Weak as WlWeak,
+		},
+	},
+	utils::{Buffer, Rectangle},
+	wayland::shm,
+};
+use std::{
+	sync::atomic::{AtomicBool, Ordering},
+	time::Duration,
+};
+use tracing::debug;
+
+/// Screencopy timestamps are on the monotonic clock, same as presentation time.
+fn monotonic_time() -> Duration {
+	let mut time = libc::timespec {
+		tv_sec: 0,
+		tv_nsec: 0,
+	};
+	unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+	Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+pub struct ScreencopyFrameData {
+	/// The surface shown on the output and the region of its buffer to copy, unset if there's nothing to capture
+	target: Option<(WlWeak<WlSurface>, Rectangle<i32, Buffer>)>,
+	copied: AtomicBool,
+}
+
+impl GlobalDispatch<ZwlrScreencopyManagerV1, (), WaylandState> for WaylandState {
+	fn bind(
+		_state: &mut WaylandState,
+		_handle: &DisplayHandle,
+		_client: &Client,
+		resource: New<ZwlrScreencopyManagerV1>,
+		_global_data: &(),
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		data_init.init(resource, ());
+	}
+
+	/// Screencopy sees every client's surfaces, so only clients granted screen capture get the global at all
+	fn can_view(client: Client, _global_data: &()) -> bool {
+		client
+			.get_data::<ClientState>()
+			.map(|state| state.permissions.allows(Capability::ScreenCapture))
+			.unwrap_or(false)
+	}
+}
+impl Dispatch<ZwlrScreencopyManagerV1, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwlrScreencopyManagerV1,
+		request: zwlr_screencopy_manager_v1::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		let (frame, output, region) = match request {
+			zwlr_screencopy_manager_v1::Request::CaptureOutput {
+				frame,
+				overlay_cursor: _,
+				output,
+			} => (frame, output, None),
+			zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+				frame,
+				overlay_cursor: _,
+				output,
+				x,
+				y,
+				width,
+				height,
+			} => (
+				frame,
+				output,
+				Some(Rectangle::from_loc_and_size((x, y), (width, height))),
+			),
+			zwlr_screencopy_manager_v1::Request::Destroy => return,
+			_ => unreachable!(),
+		};
+		let target = capture_target(&output, region);
+		let frame = data_init.init(
+			frame,
+			ScreencopyFrameData {
+				target: target.clone(),
+				copied: AtomicBool::new(false),
+			},
+		);
+		let region = target.as_ref().map(|(_, region)| *region);
+		debug!(?frame, ?region, "Screencopy frame");
+
+		let Some((_, region)) = target else {
+			frame.failed();
+			return;
+		};
+		frame.buffer(
+			wl_shm::Format::Abgr8888,
+			region.size.w as u32,
+			region.size.h as u32,
+			region.size.w as u32 * 4,
+		);
+		if frame.version() >= 3 {
+			frame.buffer_done();
+		}
+	}
+}
+
+/// Outputs are captured in buffer pixels of the surface focused on them, placed at the output's origin.
+fn capture_target(
+	output: &WlOutput,
+	region: Option<Rectangle<i32, Buffer>>,
+) -> Option<(WlWeak<WlSurface>, Rectangle<i32, Buffer>)> {
+	let output = Output::from_resource(output)?;
+	let surface = output_focus(&output)?;
+	let buffer_size = CoreSurface::from_wl_surface(&surface)?.buffer_size()?;
+	let bounds = Rectangle::from_loc_and_size((0, 0), (buffer_size.x as i32, buffer_size.y as i32));
+	let region = match region {
+		Some(region) => region.intersection(bounds)?,
+		None => bounds,
+	};
+	Some((surface.downgrade(), region))
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		frame: &ZwlrScreencopyFrameV1,
+		request: zwlr_screencopy_frame_v1::Request,
+		data: &ScreencopyFrameData,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		let (buffer, with_damage) = match request {
+			zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+			zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+			zwlr_screencopy_frame_v1::Request::Destroy => return,
+			_ => unreachable!(),
+		};
+		if data.copied.swap(true, Ordering::Relaxed) {
+			frame.post_error(
+				zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+				"Frame was already copied",
+			);
+			return;
+		}
+		let Some((surface, region)) = &data.target else {
+			frame.failed();
+			return;
+		};
+		let buffer_fits = shm::with_buffer_contents(&buffer, |_, _, buffer_data| {
+			buffer_data.format == wl_shm::Format::Abgr8888
+				&& buffer_data.width == region.size.w
+				&& buffer_data.height == region.size.h
+				&& buffer_data.stride >= region.size.w * 4
+		});
+		if !buffer_fits.unwrap_or(false) {
+			frame.post_error(
+				zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+				"Buffer must be shm with the format and size from the buffer event",
+			);
+			return;
+		}
+		let Some(core_surface) = surface
+			.upgrade()
+			.ok()
+			.and_then(|surface| CoreSurface::from_wl_surface(&surface))
+		else {
+			frame.failed();
+			return;
+		};
+
+		let frame = frame.clone();
+		core_surface.capture(Some(*region), move |capture| {
+			let Ok((info, pixels)) = capture else {
+				frame.failed();
+				return;
+			};
+			if !matches!(write_pixels(&buffer, &info, &pixels), Ok(true)) {
+				frame.failed();
+				return;
+			}
+			frame.flags(Flags::empty());
+			if with_damage {
+				frame.damage(0, 0, info.size.x, info.size.y);
+			}
+			let time = monotonic_time();
+			frame.ready(
+				(time.as_secs() >> 32) as u32,
+				time.as_secs() as u32,
+				time.subsec_nanos(),
+			);
+		});
+	}
+}
+
+fn write_pixels(
+	buffer: &WlBuffer,
+	info: &SurfaceCaptureInfo,
+	pixels: &[u8],
+) -> Result<bool, shm::BufferAccessError> {
+	shm::with_buffer_contents_mut(buffer, |ptr, len, buffer_data| {
+		let row_length = info.size.x as usize * 4;
+		for (row, row_pixels) in pixels
+			.chunks_exact(info.stride as usize)
+			.take(info.size.y as usize)
+			.enumerate()
+		{
+			let offset = buffer_data.offset as usize + row * buffer_data.stride as usize;
+			// the client could have shrunk the pool since the buffer was checked
+			if offset + row_length > len {
+				return false;
+			}
+			unsafe {
+				std::ptr::copy_nonoverlapping(row_pixels.as_ptr(), ptr.add(offset), row_length);
+			}
+		}
+		true
+	})
+}
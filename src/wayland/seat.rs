@@ -1,6 +1,10 @@
 use super::{
+	data_device, event_time,
+	pointer_constraints::SeatPointerConstraints,
 	state::{ClientState, WaylandState},
 	surface::CoreSurface,
+	tablet::SeatTablet,
+	text_input::SeatTextInput,
 	SERIAL_COUNTER,
 };
 use crate::{
@@ -97,7 +101,7 @@ impl KeyboardInfo {
 		} else {
 			KeyState::Released
 		};
-		keyboard.key(SERIAL_COUNTER.inc(), 0, key, wl_key_state);
+		keyboard.key(SERIAL_COUNTER.inc(), event_time(), key, wl_key_state);
 		match wl_key_state {
 			KeyState::Pressed => {
 				self.keys.insert(key);
@@ -178,7 +182,7 @@ impl SurfaceInfo {
 				}
 				(true, PointerEvent::Motion(pos)) => {
 					pointer.motion(
-						0,
+						event_time(),
 						(pos.x as f64).clamp(0.0, focus_size.x as f64),
 						(pos.y as f64).clamp(0.0, focus_size.y as f64),
 					);
@@ -189,7 +193,7 @@ impl SurfaceInfo {
 				(true, PointerEvent::Button { button, state }) => {
 					pointer.button(
 						0,
-						0,
+						event_time(),
 						button,
 						match state {
 							0 => ButtonState::Released,
@@ -208,9 +212,10 @@ impl SurfaceInfo {
 						axis_discrete,
 					},
 				) => {
+					let time = event_time();
 					if let Some(axis_continuous) = axis_continuous {
-						pointer.axis(0, Axis::HorizontalScroll, axis_continuous.x as f64);
-						pointer.axis(0, Axis::VerticalScroll, -axis_continuous.y as f64);
+						pointer.axis(time, Axis::HorizontalScroll, axis_continuous.x as f64);
+						pointer.axis(time, Axis::VerticalScroll, -axis_continuous.y as f64);
 					}
 					if pointer.version() >= wl_pointer::EVT_AXIS_DISCRETE_SINCE {
 						if let Some(axis_discrete) = axis_discrete {
@@ -222,8 +227,8 @@ impl SurfaceInfo {
 						&& axis_discrete.is_none()
 						&& axis_continuous.is_none()
 					{
-						pointer.axis_stop(0, Axis::HorizontalScroll);
-						pointer.axis_stop(0, Axis::VerticalScroll);
+						pointer.axis_stop(time, Axis::HorizontalScroll);
+						pointer.axis_stop(time, Axis::VerticalScroll);
 					}
 					if pointer.version() >= wl_pointer::EVT_FRAME_SINCE {
 						pointer.frame();
@@ -279,23 +284,31 @@ impl SurfaceInfo {
 
 pub struct SeatData {
 	pub client: OnceCell<ClientId>,
+	pub display_handle: DisplayHandle,
 	global_id: OnceCell<GlobalId>,
 	surfaces: Mutex<FxHashMap<ObjectId, SurfaceInfo>>,
 	pointer: OnceCell<(WlPointer, Mutex<ObjectId>)>,
 	keyboard: OnceCell<(WlKeyboard, Mutex<ObjectId>)>,
 	touch: OnceCell<WlTouch>,
 	touches: Mutex<FxHashMap<ObjectId, u32>>,
+	pub text_input: SeatTextInput,
+	pub pointer_constraints: SeatPointerConstraints,
+	pub tablet: SeatTablet,
 }
 impl SeatData {
 	pub fn new(dh: &DisplayHandle) -> Arc<Self> {
 		let seat_data = Arc::new(SeatData {
 			client: OnceCell::new(),
+			display_handle: dh.clone(),
 			global_id: OnceCell::new(),
 			surfaces: Mutex::new(FxHashMap::default()),
 			pointer: OnceCell::new(),
 			keyboard: OnceCell::new(),
 			touch: OnceCell::new(),
 			touches: Mutex::new(FxHashMap::default()),
+			text_input: SeatTextInput::default(),
+			pointer_constraints: SeatPointerConstraints::default(),
+			tablet: SeatTablet::default(),
 		});
 
 		let _ = seat_data
@@ -331,12 +344,24 @@ impl SeatData {
 	}
 
 	pub fn pointer_event(&self, surface: &WlSurface, event: PointerEvent) {
+		if data_device::drag_pointer_event(surface, &event) {
+			return;
+		}
+		self.pointer_constraints.activate(surface);
+		// a locked pointer stays put, the client only gets relative motion
+		if matches!(event, PointerEvent::Motion(_)) && self.pointer_constraints.is_locked(surface) {
+			return;
+		}
 		let mut surfaces = self.surfaces.lock();
 		let Some(surface_info) = surfaces.get_mut(&surface.id()) else {return};
 		surface_info.pointer_queue.push_back(event);
 		drop(surfaces);
 		self.handle_pointer_events();
 	}
+	pub fn pointer_relative_motion(&self, surface: &WlSurface, delta: Vector2<f32>) {
+		self.pointer_constraints.activate(surface);
+		self.pointer_constraints.relative_motion(delta);
+	}
 	pub fn keyboard_event(&self, surface: &WlSurface, event: KeyboardEvent) {
 		let mut surfaces = self.surfaces.lock();
 		let Some(surface_info) = surfaces.get_mut(&surface.id()) else {return};
@@ -394,6 +419,10 @@ impl SeatData {
 			}
 			// If there's still none, guess we're done with keyboard events for the time being
 			let Some(surface_info) = surfaces.get_mut(&keyboard_focus) else {break};
+			if !locked && surface_info.keyboard_info.is_some() {
+				// The selection has to be offered before the keyboard enters
+				data_device::keyboard_focus_entered(self);
+			}
 			if surface_info.handle_keyboard_events(keyboard, locked) {
 				// We haven't gotten to a point where we can switch the focus
 				break;
@@ -403,6 +432,13 @@ impl SeatData {
 		}
 	}
 
+	pub fn has_keyboard_focus(&self) -> bool {
+		self.keyboard
+			.get()
+			.map(|(_, focus)| !focus.lock().is_null())
+			.unwrap_or(false)
+	}
+
 	pub fn new_surface(&self, surface: &WlSurface) -> watch::Receiver<Option<CursorInfo>> {
 		let (tx, rx) = watch::channel(None);
 		self.surfaces
@@ -426,13 +462,14 @@ impl SeatData {
 			}
 		}
 		self.touches.lock().remove(&surface.id());
+		self.tablet.drop_surface(surface);
 	}
 
 	pub fn touch_down(&self, surface: &WlSurface, id: u32, position: Vector2<f32>) {
 		let Some(touch) = self.touch.get() else {return};
 		touch.down(
 			SERIAL_COUNTER.inc(),
-			0,
+			event_time(),
 			surface,
 			id as i32,
 			position.x as f64,
@@ -442,18 +479,18 @@ impl SeatData {
 	}
 	pub fn touch_move(&self, id: u32, position: Vector2<f32>) {
 		let Some(touch) = self.touch.get() else {return};
-		touch.motion(0, id as i32, position.x as f64, position.y as f64);
+		touch.motion(event_time(), id as i32, position.x as f64, position.y as f64);
 	}
 	pub fn touch_up(&self, id: u32) {
 		let Some(touch) = self.touch.get() else {return};
-		touch.up(SERIAL_COUNTER.inc(), 0, id as i32);
+		touch.up(SERIAL_COUNTER.inc(), event_time(), id as i32);
 		let mut touches = self.touches.lock();
 		touches.retain(|_, tid| *tid != id);
 	}
 	pub fn reset_touches(&self) {
 		let Some(touch) = self.touch.get() else {return};
 		for (_, touch_id) in self.touches.lock().drain() {
-			touch.up(SERIAL_COUNTER.inc(), 0, touch_id as i32);
+			touch.up(SERIAL_COUNTER.inc(), event_time(), touch_id as i32);
 		}
 	}
 }
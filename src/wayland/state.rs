@@ -1,5 +1,12 @@
 use super::DisplayWrapper;
-use crate::wayland::{drm::wl_drm::WlDrm, seat::SeatData};
+use crate::{
+	core::permissions::Permissions,
+	wayland::{
+		drm::wl_drm::WlDrm,
+		output::{create_outputs, OutputConfig},
+		seat::SeatData,
+	},
+};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use smithay::{
@@ -9,28 +16,44 @@ use smithay::{
 		renderer::gles::GlesRenderer,
 	},
 	delegate_dmabuf, delegate_output, delegate_shm,
-	output::{Mode, Output, Scale, Subpixel},
 	reexports::{
-		wayland_protocols::xdg::{
-			decoration::zv1::server::zxdg_decoration_manager_v1::ZxdgDecorationManagerV1,
-			shell::server::xdg_wm_base::XdgWmBase,
+		wayland_protocols::{
+			wp::{
+				pointer_constraints::zv1::server::zwp_pointer_constraints_v1::ZwpPointerConstraintsV1,
+				relative_pointer::zv1::server::zwp_relative_pointer_manager_v1::ZwpRelativePointerManagerV1,
+				tablet::zv2::server::zwp_tablet_manager_v2::ZwpTabletManagerV2,
+				text_input::zv3::server::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+			},
+			xdg::{
+				decoration::zv1::server::zxdg_decoration_manager_v1::ZxdgDecorationManagerV1,
+				shell::server::xdg_wm_base::XdgWmBase,
+			},
+		},
+		wayland_protocols_misc::{
+			server_decoration::server::org_kde_kwin_server_decoration_manager::Mode as DecorationMode,
+			zwp_input_method_v2::server::zwp_input_method_manager_v2::ZwpInputMethodManagerV2,
+		},
+		wayland_protocols_wlr::{
+			layer_shell::v1::server::zwlr_layer_shell_v1::ZwlrLayerShellV1,
+			screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
 		},
-		wayland_protocols_misc::server_decoration::server::org_kde_kwin_server_decoration_manager::Mode as DecorationMode,
 		wayland_server::{
 			backend::{ClientData, ClientId, DisconnectReason},
 			protocol::{wl_buffer::WlBuffer, wl_data_device_manager::WlDataDeviceManager},
 			DisplayHandle,
 		},
 	},
-	utils::{Size, Transform},
 	wayland::{
 		buffer::BufferHandler,
 		compositor::{CompositorClientState, CompositorState},
 		dmabuf::{
 			self, DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState,
 		},
+		fractional_scale::FractionalScaleManagerState,
 		shell::kde::decoration::KdeDecorationState,
 		shm::{ShmHandler, ShmState},
+		viewporter::ViewporterState,
+		xdg_activation::XdgActivationState,
 	},
 };
 use std::sync::{Arc, Weak};
@@ -42,6 +65,7 @@ pub struct ClientState {
 	pub compositor_state: CompositorClientState,
 	pub display: Weak<DisplayWrapper>,
 	pub seat: Arc<SeatData>,
+	pub permissions: Permissions,
 }
 impl ClientState {
 	pub fn flush(&self) {
@@ -70,13 +94,14 @@ pub struct WaylandState {
 	pub display_handle: DisplayHandle,
 
 	pub compositor_state: CompositorState,
-	// pub xdg_activation_state: XdgActivationState,
+	pub fractional_scale_manager_state: FractionalScaleManagerState,
+	pub viewporter_state: ViewporterState,
+	pub xdg_activation_state: XdgActivationState,
 	pub kde_decoration_state: KdeDecorationState,
 	pub shm_state: ShmState,
 	dmabuf_state: (DmabufState, DmabufGlobal, Option<DmabufFeedback>),
 	pub drm_formats: Vec<Fourcc>,
 	pub dmabuf_tx: UnboundedSender<(Dmabuf, Option<dmabuf::ImportNotifier>)>,
-	pub output: Output,
 }
 
 impl WaylandState {
@@ -84,9 +109,13 @@ impl WaylandState {
 		display_handle: DisplayHandle,
 		renderer: &GlesRenderer,
 		dmabuf_tx: UnboundedSender<(Dmabuf, Option<dmabuf::ImportNotifier>)>,
+		output_configs: &[OutputConfig],
 	) -> Arc<Mutex<Self>> {
 		let compositor_state = CompositorState::new::<Self>(&display_handle);
-		// let xdg_activation_state = XdgActivationState::new::<Self, _>(&display_handle);
+		let fractional_scale_manager_state =
+			FractionalScaleManagerState::new::<Self>(&display_handle);
+		let viewporter_state = ViewporterState::new::<Self>(&display_handle);
+		let xdg_activation_state = XdgActivationState::new::<Self>(&display_handle);
 		let kde_decoration_state =
 			KdeDecorationState::new::<Self>(&display_handle, DecorationMode::Server);
 		let shm_state = ShmState::new::<Self>(&display_handle, vec![]);
@@ -129,31 +158,18 @@ impl WaylandState {
 			(dmabuf_state, dmabuf_global, None)
 		};
 
-		let output = Output::new(
-			"1x".to_owned(),
-			smithay::output::PhysicalProperties {
-				size: Size::default(),
-				subpixel: Subpixel::None,
-				make: "Virtual XR Display".to_owned(),
-				model: "Your Headset Name Here".to_owned(),
-			},
-		);
-		let _output_global = output.create_global::<Self>(&display_handle);
-		let mode = Mode {
-			size: (2048, 2048).into(),
-			refresh: 60000,
-		};
-		output.change_current_state(
-			Some(mode),
-			Some(Transform::Normal),
-			Some(Scale::Integer(2)),
-			None,
-		);
-		output.set_preferred(mode);
+		create_outputs(&display_handle, output_configs);
 		display_handle.create_global::<Self, WlDataDeviceManager, _>(3, ());
 		display_handle.create_global::<Self, XdgWmBase, _>(5, ());
 		display_handle.create_global::<Self, ZxdgDecorationManagerV1, _>(1, ());
 		display_handle.create_global::<Self, WlDrm, _>(2, ());
+		display_handle.create_global::<Self, ZwlrLayerShellV1, _>(4, ());
+		display_handle.create_global::<Self, ZwpTextInputManagerV3, _>(1, ());
+		display_handle.create_global::<Self, ZwpInputMethodManagerV2, _>(1, ());
+		display_handle.create_global::<Self, ZwpPointerConstraintsV1, _>(1, ());
+		display_handle.create_global::<Self, ZwpRelativePointerManagerV1, _>(1, ());
+		display_handle.create_global::<Self, ZwpTabletManagerV2, _>(1, ());
+		display_handle.create_global::<Self, ZwlrScreencopyManagerV1, _>(3, ());
 
 		info!("Init Wayland compositor");
 
@@ -163,13 +179,14 @@ impl WaylandState {
 				display_handle,
 
 				compositor_state,
-				// xdg_activation_state,
+				fractional_scale_manager_state,
+				viewporter_state,
+				xdg_activation_state,
 				kde_decoration_state,
 				shm_state,
 				drm_formats,
 				dmabuf_state,
 				dmabuf_tx,
-				output,
 			})
 		})
 	}
@@ -1,21 +1,29 @@
-use super::state::WaylandState;
+use super::{fractional_scale::send_preferred_scale, output::default_output, state::WaylandState};
 use crate::{
-	core::{delta::Delta, destroy_queue, registry::Registry},
-	nodes::drawable::{model::ModelPart, shaders::PANEL_SHADER_BYTES},
+	core::{delta::Delta, destroy_queue, registry::Registry, shm::memfd_with},
+	nodes::{
+		drawable::{model::ModelPart, shaders::PANEL_SHADER_BYTES},
+		items::panel::{SurfaceCapture, SurfaceCaptureInfo},
+	},
 };
+use color_eyre::eyre::{eyre, Result};
 use mint::Vector2;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use send_wrapper::SendWrapper;
 use smithay::{
-	backend::renderer::{
-		gles::{GlesRenderer, GlesTexture},
-		utils::{import_surface_tree, on_commit_buffer_handler, RendererSurfaceStateUserData},
-		Renderer, Texture,
+	backend::{
+		allocator::Fourcc,
+		renderer::{
+			gles::{GlesRenderer, GlesTexture},
+			utils::{import_surface_tree, on_commit_buffer_handler, RendererSurfaceStateUserData},
+			ExportMem, Renderer, Texture,
+		},
 	},
 	desktop::utils::send_frames_surface_tree,
 	output::Output,
 	reexports::wayland_server::{self, protocol::wl_surface::WlSurface, DisplayHandle, Resource},
+	utils::{Buffer, Logical, Rectangle, Size},
 	wayland::compositor::{self, SurfaceData},
 };
 use std::{cell::RefCell, ffi::c_void, sync::Arc, time::Duration};
@@ -23,12 +31,18 @@ use stereokit::{
 	Material, Shader, StereoKitDraw, Tex, TextureAddress, TextureFormat, TextureSample,
 	TextureType, Transparency,
 };
+use tokio::sync::oneshot;
 
 pub static CORE_SURFACES: Registry<CoreSurface> = Registry::new();
 
+/// Gets the pixels of a region of the surface's buffer in [SURFACE_CAPTURE_FORMAT], tightly packed.
+type CaptureCallback = Box<dyn FnOnce(Result<(SurfaceCaptureInfo, Vec<u8>)>) + Send>;
+pub const SURFACE_CAPTURE_FORMAT: Fourcc = Fourcc::Abgr8888;
+
 pub struct CoreSurfaceData {
 	wl_tex: Option<SendWrapper<GlesTexture>>,
 	pub size: Vector2<u32>,
+	pub buffer_size: Vector2<u32>,
 }
 impl Drop for CoreSurfaceData {
 	fn drop(&mut self) {
@@ -46,6 +60,14 @@ pub struct CoreSurface {
 	on_mapped: Box<dyn Fn() + Send + Sync>,
 	on_commit: Box<dyn Fn(u32) + Send + Sync>,
 	pub pending_material_applications: Registry<ModelPart>,
+	/// The surface this one is shown relative to, like a popup's parent, so it starts out on the same output
+	parent: Mutex<Option<wayland_server::Weak<WlSurface>>>,
+	/// Set to the parent's output (or the default one) on the first frame if nothing chose one before that
+	output: Mutex<Option<Output>>,
+	/// Overrides the output's scale, e.g. because the panel is far away and doesn't need as many pixels
+	preferred_scale: Mutex<Option<f64>>,
+	/// Copies of the buffer waiting on the next frame, as the renderer is only available then
+	pending_captures: Mutex<Vec<(Option<Rectangle<i32, Buffer>>, CaptureCallback)>>,
 }
 
 impl CoreSurface {
@@ -67,6 +89,10 @@ impl CoreSurface {
 					on_mapped: Box::new(on_mapped) as Box<dyn Fn() + Send + Sync>,
 					on_commit: Box::new(on_commit) as Box<dyn Fn(u32) + Send + Sync>,
 					pending_material_applications: Registry::new(),
+					parent: Mutex::new(None),
+					output: Mutex::new(None),
+					preferred_scale: Mutex::new(None),
+					pending_captures: Mutex::new(Vec::new()),
 				})
 			});
 		});
@@ -148,10 +174,21 @@ impl CoreSurface {
 			if let Some(material_offset) = self.material_offset.lock().delta() {
 				sk.material_set_queue_offset(sk_mat.as_ref().as_ref(), *material_offset as i32);
 			}
+			self.send_captures(renderer, &smithay_tex);
+			// wp_viewporter can crop the buffer, which the panel shader does with its UVs
+			if let (Some(view), Some(buffer_size)) = (
+				renderer_surface_state.view(),
+				renderer_surface_state.buffer_size(),
+			) {
+				let (uv_offset, uv_scale) = viewport_uv(view.src, buffer_size);
+				sk.material_set_vector2(sk_mat.as_ref().as_ref(), "uv_offset", uv_offset);
+				sk.material_set_vector2(sk_mat.as_ref().as_ref(), "uv_scale", uv_scale);
+			}
 
 			let Some(surface_size) = renderer_surface_state.surface_size() else {return};
 			let new_mapped_data = CoreSurfaceData {
 				size: Vector2::from([surface_size.w as u32, surface_size.h as u32]),
+				buffer_size: Vector2::from([smithay_tex.width(), smithay_tex.height()]),
 				wl_tex: Some(SendWrapper::new(smithay_tex)),
 			};
 			*mapped_data = Some(new_mapped_data);
@@ -163,8 +200,13 @@ impl CoreSurface {
 		self.apply_surface_materials();
 	}
 
-	pub fn frame(&self, sk: &impl StereoKitDraw, output: Output) {
+	pub fn frame(&self, sk: &impl StereoKitDraw) {
 		let Some(wl_surface) = self.wl_surface() else {return};
+		if self.output.lock().is_none() {
+			let Some(output) = self.parent_output().or_else(default_output) else {return};
+			self.set_output(output);
+		}
+		let Some(output) = self.output.lock().clone() else {return};
 
 		send_frames_surface_tree(
 			&wl_surface,
@@ -175,6 +217,94 @@ impl CoreSurface {
 		);
 	}
 
+	pub fn set_parent(&self, parent: &WlSurface) {
+		*self.parent.lock() = Some(parent.downgrade());
+	}
+	/// The output of the closest surface above this one with an output, either its parent or the surface it's a subsurface of.
+	fn parent_output(&self) -> Option<Output> {
+		let parent = self
+			.parent
+			.lock()
+			.as_ref()
+			.and_then(|parent| parent.upgrade().ok());
+		let mut parent = parent.or_else(|| compositor::get_parent(&self.wl_surface()?));
+		while let Some(surface) = parent {
+			if let Some(output) = CoreSurface::from_wl_surface(&surface)
+				.and_then(|core_surface| core_surface.output())
+			{
+				return Some(output);
+			}
+			parent = compositor::get_parent(&surface);
+		}
+		None
+	}
+
+	pub fn set_output(&self, output: Output) {
+		let Some(wl_surface) = self.wl_surface() else {return};
+		let mut current_output = self.output.lock();
+		if current_output.as_ref() == Some(&output) {
+			return;
+		}
+		if let Some(old_output) = current_output.take() {
+			old_output.leave(&wl_surface);
+		}
+		output.enter(&wl_surface);
+		if self.preferred_scale.lock().is_none() {
+			send_preferred_scale(&wl_surface, output.current_scale().fractional_scale());
+		}
+		*current_output = Some(output);
+	}
+
+	/// The scale of the default output, for surfaces that aren't on any output yet.
+	pub fn default_scale() -> f64 {
+		default_output()
+			.map(|output| output.current_scale().fractional_scale())
+			.unwrap_or(1.0)
+	}
+	pub fn preferred_scale(&self) -> f64 {
+		self.preferred_scale
+			.lock()
+			.or_else(|| {
+				self.output
+					.lock()
+					.as_ref()
+					.map(|output| output.current_scale().fractional_scale())
+			})
+			.unwrap_or_else(Self::default_scale)
+	}
+	pub fn set_preferred_scale(&self, scale: f64) {
+		*self.preferred_scale.lock() = Some(scale);
+		let Some(wl_surface) = self.wl_surface() else {return};
+		send_preferred_scale(&wl_surface, scale);
+	}
+
+	/// Copy a region (or all) of the latest committed buffer on the next frame.
+	pub fn capture(
+		&self,
+		region: Option<Rectangle<i32, Buffer>>,
+		callback: impl FnOnce(Result<(SurfaceCaptureInfo, Vec<u8>)>) + Send + 'static,
+	) {
+		self.pending_captures
+			.lock()
+			.push((region, Box::new(callback)));
+	}
+	/// Copy the whole buffer into shared memory clients can map.
+	pub fn capture_to_fd(&self) -> SurfaceCapture {
+		let (tx, rx) = oneshot::channel();
+		self.capture(None, move |capture| {
+			let _ = tx.send(capture.and_then(|(info, pixels)| {
+				Ok((info, memfd_with("stardust-surface-capture", &pixels)?))
+			}));
+		});
+		rx
+	}
+	fn send_captures(&self, renderer: &mut GlesRenderer, texture: &GlesTexture) {
+		let captures = std::mem::take(&mut *self.pending_captures.lock());
+		for (region, callback) in captures {
+			callback(copy_texture(renderer, texture, region));
+		}
+	}
+
 	pub fn set_material_offset(&self, material_offset: u32) {
 		*self.material_offset.lock().value_mut() = material_offset;
 	}
@@ -207,12 +337,110 @@ impl CoreSurface {
 	pub fn size(&self) -> Option<Vector2<u32>> {
 		self.mapped_data.lock().as_ref().map(|d| d.size)
 	}
+	pub fn buffer_size(&self) -> Option<Vector2<u32>> {
+		self.mapped_data.lock().as_ref().map(|d| d.buffer_size)
+	}
+	pub fn output(&self) -> Option<Output> {
+		self.output.lock().clone()
+	}
 }
 impl Drop for CoreSurface {
 	fn drop(&mut self) {
 		CORE_SURFACES.remove(self);
+		for (_, callback) in self.pending_captures.get_mut().drain(..) {
+			callback(Err(eyre!("Surface was destroyed first")));
+		}
 
 		destroy_queue::add(self.sk_tex.take());
 		destroy_queue::add(self.sk_mat.take());
 	}
 }
+
+/// The panel shader's `uv_offset` and `uv_scale` to only show the viewport's source rectangle, it samples at `(uv + uv_offset) * uv_scale`.
+fn viewport_uv(
+	src: Rectangle<f64, Logical>,
+	buffer_size: Size<i32, Logical>,
+) -> (Vector2<f32>, Vector2<f32>) {
+	if src.size.w <= 0.0 || src.size.h <= 0.0 || buffer_size.w <= 0 || buffer_size.h <= 0 {
+		return ([0.0; 2].into(), [1.0; 2].into());
+	}
+	let uv_offset = [
+		(src.loc.x / src.size.w) as f32,
+		(src.loc.y / src.size.h) as f32,
+	];
+	let uv_scale = [
+		(src.size.w / buffer_size.w as f64) as f32,
+		(src.size.h / buffer_size.h as f64) as f32,
+	];
+	(uv_offset.into(), uv_scale.into())
+}
+
+fn copy_texture(
+	renderer: &mut GlesRenderer,
+	texture: &GlesTexture,
+	region: Option<Rectangle<i32, Buffer>>,
+) -> Result<(SurfaceCaptureInfo, Vec<u8>)> {
+	let bounds = Rectangle::from_loc_and_size((0, 0), texture.size());
+	let region = region
+		.unwrap_or(bounds)
+		.intersection(bounds)
+		.ok_or_else(|| eyre!("Capture region is outside the surface"))?;
+	let mapping = renderer.copy_texture(texture, region, SURFACE_CAPTURE_FORMAT)?;
+	let pixels = renderer.map_texture(&mapping)?.to_vec();
+	let info = SurfaceCaptureInfo {
+		format: SURFACE_CAPTURE_FORMAT as u32,
+		size: [region.size.w as u32, region.size.h as u32].into(),
+		stride: region.size.w as u32 * 4,
+	};
+	Ok((info, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::viewport_uv;
+	use smithay::utils::{Logical, Rectangle, Size};
+
+	fn sample(uv: [f32; 2], offset: mint::Vector2<f32>, scale: mint::Vector2<f32>) -> [f32; 2] {
+		[(uv[0] + offset.x) * scale.x, (uv[1] + offset.y) * scale.y]
+	}
+	fn assert_uv_eq(a: [f32; 2], b: [f32; 2]) {
+		assert!(
+			(a[0] - b[0]).abs() < 0.0001 && (a[1] - b[1]).abs() < 0.0001,
+			"{a:?} != {b:?}"
+		);
+	}
+
+	#[test]
+	fn whole_buffer() {
+		let (offset, scale) = viewport_uv(
+			Rectangle::from_loc_and_size((0.0, 0.0), (100.0, 80.0)),
+			Size::<i32, Logical>::from((100, 80)),
+		);
+		assert_uv_eq([offset.x, offset.y], [0.0, 0.0]);
+		assert_uv_eq([scale.x, scale.y], [1.0, 1.0]);
+	}
+	#[test]
+	fn cropped_buffer() {
+		// the 50x40 region at (10, 20) of a 100x80 buffer
+		let (offset, scale) = viewport_uv(
+			Rectangle::from_loc_and_size((10.0, 20.0), (50.0, 40.0)),
+			Size::<i32, Logical>::from((100, 80)),
+		);
+		assert_uv_eq(sample([0.0, 0.0], offset, scale), [0.1, 0.25]);
+		assert_uv_eq(sample([1.0, 1.0], offset, scale), [0.6, 0.75]);
+	}
+	#[test]
+	fn empty_sizes_show_everything() {
+		for (src, buffer_size) in [
+			(((0.0, 0.0), (0.0, 40.0)), (100, 80)),
+			(((10.0, 20.0), (50.0, 0.0)), (100, 80)),
+			(((0.0, 0.0), (50.0, 40.0)), (0, 80)),
+			(((0.0, 0.0), (50.0, 40.0)), (100, 0)),
+		] {
+			let src: Rectangle<f64, Logical> = Rectangle::from_loc_and_size(src.0, src.1);
+			let (offset, scale) = viewport_uv(src, buffer_size.into());
+			assert_uv_eq([offset.x, offset.y], [0.0, 0.0]);
+			assert_uv_eq([scale.x, scale.y], [1.0, 1.0]);
+		}
+	}
+}
@@ -0,0 +1,268 @@
+use super::{event_time, seat::SeatData, state::WaylandState, SERIAL_COUNTER};
+use crate::nodes::items::panel::TabletToolEvent;
+use mint::Vector2;
+use parking_lot::Mutex;
+use smithay::reexports::{
+	wayland_protocols::wp::tablet::zv2::server::{
+		zwp_tablet_manager_v2::{self, ZwpTabletManagerV2},
+		zwp_tablet_seat_v2::{self, ZwpTabletSeatV2},
+		zwp_tablet_tool_v2::{self, ButtonState, ZwpTabletToolV2},
+		zwp_tablet_v2::{self, ZwpTabletV2},
+	},
+	wayland_server::{
+		backend::ClientId, protocol::wl_surface::WlSurface, Client, DataInit, Dispatch,
+		DisplayHandle, GlobalDispatch, New, Resource, Weak as WlWeak,
+	},
+};
+use std::sync::Arc;
+use tracing::debug;
+
+/// The virtual tablet and pen a tablet seat object was given
+struct TabletSeatObjects {
+	tablet_seat: ZwpTabletSeatV2,
+	tablet: ZwpTabletV2,
+	tool: ZwpTabletToolV2,
+}
+
+/// Take the pen away from the surface it's over, lifting its tip first if it's down.
+fn proximity_out(tablet_seats: &[TabletSeatObjects], tip_down: bool, time: u32) {
+	for objects in tablet_seats.iter() {
+		if tip_down {
+			objects.tool.up();
+		}
+		objects.tool.proximity_out();
+		objects.tool.frame(time);
+	}
+}
+
+/// The tablet half of a seat, every client gets one virtual tablet with one pen for the panel UI to drive.
+#[derive(Default)]
+pub struct SeatTablet {
+	tablet_seats: Mutex<Vec<TabletSeatObjects>>,
+	/// The surface the pen is in proximity of and whether its tip is down
+	proximity: Mutex<Option<(WlWeak<WlSurface>, bool)>>,
+}
+impl SeatTablet {
+	pub fn tool_event(&self, surface: &WlSurface, event: TabletToolEvent) {
+		let tablet_seats = self.tablet_seats.lock();
+		let mut proximity = self.proximity.lock();
+		let proximity_surface = proximity
+			.as_ref()
+			.and_then(|(surface, _)| surface.upgrade().ok());
+		let time = event_time();
+
+		if let TabletToolEvent::Proximity(false) = event {
+			let Some((_, tip_down)) = proximity.take() else {
+				return;
+			};
+			proximity_out(&tablet_seats, tip_down, time);
+			return;
+		}
+		// anything else brings the pen into proximity of the surface
+		if proximity_surface.as_ref() != Some(surface) {
+			if let Some((_, tip_down)) = proximity.take().filter(|_| proximity_surface.is_some()) {
+				proximity_out(&tablet_seats, tip_down, time);
+			}
+			let serial = SERIAL_COUNTER.inc();
+			for objects in tablet_seats.iter() {
+				objects.tool.proximity_in(serial, &objects.tablet, surface);
+			}
+			*proximity = Some((surface.downgrade(), false));
+		}
+		let Some((_, tip_down)) = proximity.as_mut() else {
+			return;
+		};
+
+		match event {
+			TabletToolEvent::Proximity(_) => (),
+			TabletToolEvent::Motion(position) => {
+				for objects in tablet_seats.iter() {
+					objects.tool.motion(position.x as f64, position.y as f64);
+				}
+			}
+			TabletToolEvent::Tip(down) if *tip_down != down => {
+				*tip_down = down;
+				let serial = SERIAL_COUNTER.inc();
+				for objects in tablet_seats.iter() {
+					if down {
+						objects.tool.down(serial);
+					} else {
+						objects.tool.up();
+					}
+				}
+			}
+			// the tip is already there
+			TabletToolEvent::Tip(_) => (),
+			TabletToolEvent::Pressure(pressure) => {
+				let pressure = (pressure.clamp(0.0, 1.0) * 65535.0) as u32;
+				for objects in tablet_seats.iter() {
+					objects.tool.pressure(pressure);
+				}
+			}
+			TabletToolEvent::Tilt(tilt) => {
+				for objects in tablet_seats.iter() {
+					objects.tool.tilt(tilt.x as f64, tilt.y as f64);
+				}
+			}
+			TabletToolEvent::Button { button, pressed } => {
+				let serial = SERIAL_COUNTER.inc();
+				let state = if pressed {
+					ButtonState::Pressed
+				} else {
+					ButtonState::Released
+				};
+				for objects in tablet_seats.iter() {
+					objects.tool.button(serial, button, state);
+				}
+			}
+		}
+		for objects in tablet_seats.iter() {
+			objects.tool.frame(time);
+		}
+	}
+	pub fn drop_surface(&self, surface: &WlSurface) {
+		let mut proximity = self.proximity.lock();
+		if proximity
+			.as_ref()
+			.map(|(proximity_surface, _)| {
+				proximity_surface.upgrade().ok().as_ref() == Some(surface)
+			})
+			.unwrap_or(false)
+		{
+			*proximity = None;
+		}
+	}
+}
+
+impl GlobalDispatch<ZwpTabletManagerV2, (), WaylandState> for WaylandState {
+	fn bind(
+		_state: &mut WaylandState,
+		_handle: &DisplayHandle,
+		_client: &Client,
+		resource: New<ZwpTabletManagerV2>,
+		_global_data: &(),
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		data_init.init(resource, ());
+	}
+}
+impl Dispatch<ZwpTabletManagerV2, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		client: &Client,
+		_resource: &ZwpTabletManagerV2,
+		request: zwp_tablet_manager_v2::Request,
+		_data: &(),
+		dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_tablet_manager_v2::Request::GetTabletSeat { tablet_seat, seat } => {
+				// every seat is created with its data
+				let seat_data = seat.data::<Arc<SeatData>>().unwrap().clone();
+				let tablet_seat = data_init.init(tablet_seat, seat_data.clone());
+				let Ok(tablet) = client.create_resource::<ZwpTabletV2, _, WaylandState>(
+					dhandle,
+					tablet_seat.version(),
+					(),
+				) else {
+					return;
+				};
+				tablet_seat.tablet_added(&tablet);
+				tablet.name("Stardust XR Virtual Tablet".to_string());
+				tablet.done();
+
+				let Ok(tool) = client.create_resource::<ZwpTabletToolV2, _, WaylandState>(
+					dhandle,
+					tablet_seat.version(),
+					(),
+				) else {
+					return;
+				};
+				tablet_seat.tool_added(&tool);
+				tool._type(zwp_tablet_tool_v2::Type::Pen);
+				tool.capability(zwp_tablet_tool_v2::Capability::Pressure);
+				tool.capability(zwp_tablet_tool_v2::Capability::Tilt);
+				tool.done();
+
+				debug!(?tablet_seat, "Create tablet seat");
+				seat_data
+					.tablet
+					.tablet_seats
+					.lock()
+					.push(TabletSeatObjects {
+						tablet_seat,
+						tablet,
+						tool,
+					});
+			}
+			zwp_tablet_manager_v2::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl Dispatch<ZwpTabletSeatV2, Arc<SeatData>, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpTabletSeatV2,
+		request: zwp_tablet_seat_v2::Request,
+		_data: &Arc<SeatData>,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_tablet_seat_v2::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+
+	fn destroyed(
+		_state: &mut WaylandState,
+		_client: ClientId,
+		tablet_seat: &ZwpTabletSeatV2,
+		seat: &Arc<SeatData>,
+	) {
+		seat.tablet
+			.tablet_seats
+			.lock()
+			.retain(|objects| &objects.tablet_seat != tablet_seat);
+	}
+}
+
+impl Dispatch<ZwpTabletV2, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpTabletV2,
+		request: zwp_tablet_v2::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_tablet_v2::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl Dispatch<ZwpTabletToolV2, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpTabletToolV2,
+		request: zwp_tablet_tool_v2::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			// the pen is visible in XR already, so there's no cursor to draw for it
+			zwp_tablet_tool_v2::Request::SetCursor { .. } => (),
+			zwp_tablet_tool_v2::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
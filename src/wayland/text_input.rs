@@ -0,0 +1,630 @@
+use super::{
+	seat::SeatData,
+	state::{ClientState, WaylandState},
+};
+use crate::{
+	core::{permissions::Capability, task},
+	nodes::items::panel::{
+		Backend, ContentHint, ContentPurpose, Geometry, PanelItem, Preedit, TextInputCommit,
+		TextInputInfo,
+	},
+};
+use parking_lot::{const_mutex, Mutex};
+use smithay::reexports::{
+	wayland_protocols::wp::text_input::zv3::server::{
+		zwp_text_input_manager_v3::{self, ZwpTextInputManagerV3},
+		zwp_text_input_v3::{self, ChangeCause, ZwpTextInputV3},
+	},
+	wayland_protocols_misc::zwp_input_method_v2::server::{
+		zwp_input_method_keyboard_grab_v2::{self, ZwpInputMethodKeyboardGrabV2},
+		zwp_input_method_manager_v2::{self, ZwpInputMethodManagerV2},
+		zwp_input_method_v2::{self, ZwpInputMethodV2},
+		zwp_input_popup_surface_v2::{self, ZwpInputPopupSurfaceV2},
+	},
+	wayland_server::{
+		backend::{ClientId, ObjectId},
+		protocol::wl_surface::WlSurface,
+		Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+		Weak as WlWeak,
+	},
+};
+use std::sync::{
+	atomic::{AtomicU32, Ordering},
+	Arc,
+};
+use tokio::sync::watch;
+use tracing::debug;
+
+/// Only one input method can type at a time, any others are told they're unavailable
+static INPUT_METHOD: Mutex<Option<ZwpInputMethodV2>> = const_mutex(None);
+/// The enabled text input in the most recently focused panel, which the input method types into
+static ACTIVE_TEXT_INPUT: Mutex<Option<ZwpTextInputV3>> = const_mutex(None);
+
+impl From<zwp_text_input_v3::ContentPurpose> for ContentPurpose {
+	fn from(purpose: zwp_text_input_v3::ContentPurpose) -> Self {
+		match purpose {
+			zwp_text_input_v3::ContentPurpose::Alpha => ContentPurpose::Alpha,
+			zwp_text_input_v3::ContentPurpose::Digits => ContentPurpose::Digits,
+			zwp_text_input_v3::ContentPurpose::Number => ContentPurpose::Number,
+			zwp_text_input_v3::ContentPurpose::Phone => ContentPurpose::Phone,
+			zwp_text_input_v3::ContentPurpose::Url => ContentPurpose::Url,
+			zwp_text_input_v3::ContentPurpose::Email => ContentPurpose::Email,
+			zwp_text_input_v3::ContentPurpose::Name => ContentPurpose::Name,
+			zwp_text_input_v3::ContentPurpose::Password => ContentPurpose::Password,
+			zwp_text_input_v3::ContentPurpose::Pin => ContentPurpose::Pin,
+			zwp_text_input_v3::ContentPurpose::Date => ContentPurpose::Date,
+			zwp_text_input_v3::ContentPurpose::Time => ContentPurpose::Time,
+			zwp_text_input_v3::ContentPurpose::Datetime => ContentPurpose::Datetime,
+			zwp_text_input_v3::ContentPurpose::Terminal => ContentPurpose::Terminal,
+			_ => ContentPurpose::Normal,
+		}
+	}
+}
+fn content_hints(hint: zwp_text_input_v3::ContentHint) -> Vec<ContentHint> {
+	use zwp_text_input_v3::ContentHint as Flag;
+	[
+		(Flag::Completion, ContentHint::Completion),
+		(Flag::Spellcheck, ContentHint::Spellcheck),
+		(Flag::AutoCapitalization, ContentHint::AutoCapitalization),
+		(Flag::Lowercase, ContentHint::Lowercase),
+		(Flag::Uppercase, ContentHint::Uppercase),
+		(Flag::Titlecase, ContentHint::Titlecase),
+		(Flag::HiddenText, ContentHint::HiddenText),
+		(Flag::SensitiveData, ContentHint::SensitiveData),
+		(Flag::Latin, ContentHint::Latin),
+		(Flag::Multiline, ContentHint::Multiline),
+	]
+	.into_iter()
+	.filter(|(flag, _)| hint.contains(*flag))
+	.map(|(_, content_hint)| content_hint)
+	.collect()
+}
+
+/// Everything the client said about its text field, double buffered until it commits.
+#[derive(Debug, Clone)]
+struct TextInputState {
+	enabled: bool,
+	/// Text, cursor and anchor
+	surrounding_text: Option<(String, i32, i32)>,
+	change_cause: ChangeCause,
+	content_hint: zwp_text_input_v3::ContentHint,
+	content_purpose: zwp_text_input_v3::ContentPurpose,
+	cursor_rectangle: Option<Geometry>,
+}
+impl Default for TextInputState {
+	fn default() -> Self {
+		TextInputState {
+			enabled: false,
+			surrounding_text: None,
+			change_cause: ChangeCause::InputMethod,
+			content_hint: zwp_text_input_v3::ContentHint::None,
+			content_purpose: zwp_text_input_v3::ContentPurpose::Normal,
+			cursor_rectangle: None,
+		}
+	}
+}
+impl TextInputState {
+	fn info(&self) -> TextInputInfo {
+		let (surrounding_text, cursor, anchor) = match &self.surrounding_text {
+			Some((text, cursor, anchor)) => (Some(text.clone()), *cursor, *anchor),
+			None => (None, 0, 0),
+		};
+		TextInputInfo {
+			surrounding_text,
+			cursor,
+			anchor,
+			content_hints: content_hints(self.content_hint),
+			content_purpose: self.content_purpose.into(),
+			cursor_rectangle: self.cursor_rectangle,
+		}
+	}
+}
+
+pub struct TextInputData {
+	seat: Arc<SeatData>,
+	pending: Mutex<TextInputState>,
+	current: Mutex<TextInputState>,
+	/// How many times the client has committed, `done` has to echo this back
+	commits: AtomicU32,
+}
+impl TextInputData {
+	fn get(text_input: &ZwpTextInputV3) -> Option<&Self> {
+		text_input.data::<TextInputData>()
+	}
+}
+
+/// The text input half of a seat.
+///
+/// Text input focus follows whichever toplevel has focused visuals rather than the keyboard focus,
+/// since the keyboard only focuses a surface while keys are held down.
+pub struct SeatTextInput {
+	text_inputs: Mutex<Vec<ZwpTextInputV3>>,
+	focus: Mutex<Option<WlWeak<WlSurface>>>,
+	/// The focused surface and its text field, if a text input on it is enabled
+	info: watch::Sender<Option<(ObjectId, TextInputInfo)>>,
+}
+impl Default for SeatTextInput {
+	fn default() -> Self {
+		SeatTextInput {
+			text_inputs: Mutex::new(Vec::new()),
+			focus: Mutex::new(None),
+			info: watch::channel(None).0,
+		}
+	}
+}
+impl SeatTextInput {
+	fn focus(&self) -> Option<WlSurface> {
+		self.focus.lock().as_ref()?.upgrade().ok()
+	}
+
+	pub fn set_focused(&self, surface: &WlSurface, focused: bool) {
+		let mut focus = self.focus.lock();
+		let old_focus = focus.as_ref().and_then(|focus| focus.upgrade().ok());
+		if focused == (old_focus.as_ref() == Some(surface)) {
+			return;
+		}
+		for text_input in self.text_inputs.lock().iter() {
+			if let Some(old_focus) = &old_focus {
+				text_input.leave(old_focus);
+				// leaving disables the text input until the client enables it again
+				if let Some(data) = TextInputData::get(text_input) {
+					data.current.lock().enabled = false;
+				}
+			}
+			if focused {
+				text_input.enter(surface);
+			}
+		}
+		*focus = focused.then(|| surface.downgrade());
+		drop(focus);
+		self.update();
+	}
+
+	/// The text field focused in this surface, if there is one.
+	pub fn info(&self, surface: &WlSurface) -> Option<TextInputInfo> {
+		self.info
+			.borrow()
+			.as_ref()
+			.filter(|(surface_id, _)| surface_id == &surface.id())
+			.map(|(_, info)| info.clone())
+	}
+
+	/// Type into the text field focused in this surface, if there is one.
+	pub fn commit(&self, surface: &WlSurface, commit: &TextInputCommit) {
+		if self.focus().as_ref() != Some(surface) {
+			return;
+		}
+		for text_input in self.text_inputs.lock().iter() {
+			let Some(data) = TextInputData::get(text_input) else {
+				continue;
+			};
+			if data.current.lock().enabled {
+				send_commit(text_input, commit);
+			}
+		}
+	}
+
+	fn add(&self, text_input: ZwpTextInputV3) {
+		if let Some(focus) = self.focus() {
+			text_input.enter(&focus);
+		}
+		self.text_inputs.lock().push(text_input);
+	}
+	fn remove(&self, text_input: &ZwpTextInputV3) {
+		self.text_inputs.lock().retain(|t| t != text_input);
+		deactivate_input_method(text_input);
+		self.update();
+	}
+
+	/// Let the panel item and input method know about the focused text field's new state.
+	fn update(&self) {
+		let focus = self.focus();
+		let enabled_text_input = focus.as_ref().and_then(|_| {
+			self.text_inputs
+				.lock()
+				.iter()
+				.find(|text_input| {
+					TextInputData::get(text_input)
+						.map(|data| data.current.lock().enabled)
+						.unwrap_or(false)
+				})
+				.cloned()
+		});
+		let info = enabled_text_input
+			.as_ref()
+			.and_then(TextInputData::get)
+			.zip(focus)
+			.map(|(data, focus)| (focus.id(), data.current.lock().info()));
+		self.info.send_if_modified(|old_info| {
+			let changed = *old_info != info;
+			*old_info = info;
+			changed
+		});
+
+		match enabled_text_input {
+			Some(text_input) => activate_input_method(&text_input),
+			None => {
+				for text_input in self.text_inputs.lock().iter() {
+					deactivate_input_method(text_input);
+				}
+			}
+		}
+	}
+}
+
+/// Sends `text_input_changed` to the panel item whenever the text field focused in its surface changes.
+pub fn handle_text_input<B: Backend>(
+	panel_item: &Arc<PanelItem<B>>,
+	seat: &SeatData,
+	wl_surface: &WlSurface,
+) {
+	let panel_item_weak = Arc::downgrade(panel_item);
+	let surface_id = wl_surface.id();
+	let mut info = seat.text_input.info.subscribe();
+	let _ = task::new(|| "text input handler", async move {
+		let mut last_info = None;
+		while info.changed().await.is_ok() {
+			let Some(panel_item) = panel_item_weak.upgrade() else {
+				break;
+			};
+			let new_info = info
+				.borrow()
+				.as_ref()
+				.filter(|(id, _)| id == &surface_id)
+				.map(|(_, info)| info.clone());
+			if new_info != last_info {
+				panel_item.text_input_changed(new_info.as_ref());
+				last_info = new_info;
+			}
+		}
+	});
+}
+
+fn send_commit(text_input: &ZwpTextInputV3, commit: &TextInputCommit) {
+	let Some(data) = TextInputData::get(text_input) else {
+		return;
+	};
+	if let Some((before_length, after_length)) = commit.delete_surrounding {
+		text_input.delete_surrounding_text(before_length, after_length);
+	}
+	if let Some(commit_string) = &commit.commit_string {
+		text_input.commit_string(Some(commit_string.clone()));
+	}
+	if let Some(preedit) = &commit.preedit {
+		text_input.preedit_string(
+			Some(preedit.text.clone()),
+			preedit.cursor_begin,
+			preedit.cursor_end,
+		);
+	}
+	text_input.done(data.commits.load(Ordering::Relaxed));
+}
+
+fn activate_input_method(text_input: &ZwpTextInputV3) {
+	let newly_active = ACTIVE_TEXT_INPUT
+		.lock()
+		.replace(text_input.clone())
+		.as_ref()
+		!= Some(text_input);
+	let Some(input_method) = INPUT_METHOD.lock().clone() else {
+		return;
+	};
+	if newly_active {
+		input_method.activate();
+	}
+	send_input_method_state(&input_method, text_input);
+}
+fn send_input_method_state(input_method: &ZwpInputMethodV2, text_input: &ZwpTextInputV3) {
+	let Some(data) = TextInputData::get(text_input) else {
+		return;
+	};
+	let state = data.current.lock().clone();
+	if let Some((text, cursor, anchor)) = state.surrounding_text {
+		input_method.surrounding_text(text, cursor.max(0) as u32, anchor.max(0) as u32);
+	}
+	input_method.text_change_cause(state.change_cause);
+	input_method.content_type(state.content_hint, state.content_purpose);
+	input_method.done();
+}
+/// Only does anything if this was the active text input.
+fn deactivate_input_method(text_input: &ZwpTextInputV3) {
+	let mut active_text_input = ACTIVE_TEXT_INPUT.lock();
+	if active_text_input.as_ref() != Some(text_input) {
+		return;
+	}
+	*active_text_input = None;
+	drop(active_text_input);
+	let Some(input_method) = INPUT_METHOD.lock().clone() else {
+		return;
+	};
+	input_method.deactivate();
+	input_method.done();
+}
+
+impl GlobalDispatch<ZwpTextInputManagerV3, (), WaylandState> for WaylandState {
+	fn bind(
+		_state: &mut WaylandState,
+		_handle: &DisplayHandle,
+		_client: &Client,
+		resource: New<ZwpTextInputManagerV3>,
+		_global_data: &(),
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		data_init.init(resource, ());
+	}
+}
+impl Dispatch<ZwpTextInputManagerV3, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpTextInputManagerV3,
+		request: zwp_text_input_manager_v3::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_text_input_manager_v3::Request::GetTextInput { id, seat } => {
+				// every seat is created with its data
+				let seat_data = seat.data::<Arc<SeatData>>().unwrap().clone();
+				let text_input = data_init.init(
+					id,
+					TextInputData {
+						seat: seat_data.clone(),
+						pending: Mutex::new(TextInputState::default()),
+						current: Mutex::new(TextInputState::default()),
+						commits: AtomicU32::new(0),
+					},
+				);
+				debug!(?text_input, "Create text input");
+				seat_data.text_input.add(text_input);
+			}
+			zwp_text_input_manager_v3::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl Dispatch<ZwpTextInputV3, TextInputData, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		text_input: &ZwpTextInputV3,
+		request: zwp_text_input_v3::Request,
+		data: &TextInputData,
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_text_input_v3::Request::Enable => {
+				// enabling resets everything else
+				*data.pending.lock() = TextInputState {
+					enabled: true,
+					..Default::default()
+				};
+			}
+			zwp_text_input_v3::Request::Disable => {
+				data.pending.lock().enabled = false;
+			}
+			zwp_text_input_v3::Request::SetSurroundingText {
+				text,
+				cursor,
+				anchor,
+			} => {
+				data.pending.lock().surrounding_text = Some((text, cursor, anchor));
+			}
+			zwp_text_input_v3::Request::SetTextChangeCause { cause } => {
+				if let WEnum::Value(cause) = cause {
+					data.pending.lock().change_cause = cause;
+				}
+			}
+			zwp_text_input_v3::Request::SetContentType { hint, purpose } => {
+				let mut pending = data.pending.lock();
+				pending.content_hint = match hint {
+					WEnum::Value(hint) => hint,
+					WEnum::Unknown(bits) => {
+						zwp_text_input_v3::ContentHint::from_bits_truncate(bits)
+					}
+				};
+				if let WEnum::Value(purpose) = purpose {
+					pending.content_purpose = purpose;
+				}
+			}
+			zwp_text_input_v3::Request::SetCursorRectangle {
+				x,
+				y,
+				width,
+				height,
+			} => {
+				data.pending.lock().cursor_rectangle = Some(Geometry {
+					origin: [x, y].into(),
+					size: [width.max(0) as u32, height.max(0) as u32].into(),
+				});
+			}
+			zwp_text_input_v3::Request::Commit => {
+				data.commits.fetch_add(1, Ordering::Relaxed);
+				let mut pending = data.pending.lock();
+				*data.current.lock() = pending.clone();
+				// the change cause only applies to the one commit
+				pending.change_cause = ChangeCause::InputMethod;
+				drop(pending);
+				debug!(?text_input, state = ?data.current.lock(), "Text input commit");
+				data.seat.text_input.update();
+			}
+			zwp_text_input_v3::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+
+	fn destroyed(
+		_state: &mut WaylandState,
+		_client: ClientId,
+		text_input: &ZwpTextInputV3,
+		data: &TextInputData,
+	) {
+		data.seat.text_input.remove(text_input);
+	}
+}
+
+impl GlobalDispatch<ZwpInputMethodManagerV2, (), WaylandState> for WaylandState {
+	fn bind(
+		_state: &mut WaylandState,
+		_handle: &DisplayHandle,
+		_client: &Client,
+		resource: New<ZwpInputMethodManagerV2>,
+		_global_data: &(),
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		data_init.init(resource, ());
+	}
+
+	/// An input method reads every focused text field and types into every client, so it needs the input method capability
+	fn can_view(client: Client, _global_data: &()) -> bool {
+		client
+			.get_data::<ClientState>()
+			.map(|state| state.permissions.allows(Capability::InputMethod))
+			.unwrap_or(false)
+	}
+}
+impl Dispatch<ZwpInputMethodManagerV2, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpInputMethodManagerV2,
+		request: zwp_input_method_manager_v2::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			// every client gets its own seat, so the input method types into whichever seat's text input is active
+			zwp_input_method_manager_v2::Request::GetInputMethod {
+				seat: _,
+				input_method,
+			} => {
+				let input_method =
+					data_init.init(input_method, Mutex::new(TextInputCommit::default()));
+				let mut current_input_method = INPUT_METHOD.lock();
+				if current_input_method
+					.as_ref()
+					.map(Resource::is_alive)
+					.unwrap_or(false)
+				{
+					input_method.unavailable();
+					return;
+				}
+				debug!(?input_method, "Create input method");
+				*current_input_method = Some(input_method.clone());
+				drop(current_input_method);
+
+				if let Some(text_input) = ACTIVE_TEXT_INPUT.lock().clone() {
+					input_method.activate();
+					send_input_method_state(&input_method, &text_input);
+				}
+			}
+			zwp_input_method_manager_v2::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl Dispatch<ZwpInputMethodV2, Mutex<TextInputCommit>, WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		input_method: &ZwpInputMethodV2,
+		request: zwp_input_method_v2::Request,
+		pending: &Mutex<TextInputCommit>,
+		_dhandle: &DisplayHandle,
+		data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_input_method_v2::Request::CommitString { text } => {
+				pending.lock().commit_string = Some(text);
+			}
+			zwp_input_method_v2::Request::SetPreeditString {
+				text,
+				cursor_begin,
+				cursor_end,
+			} => {
+				pending.lock().preedit = Some(Preedit {
+					text,
+					cursor_begin,
+					cursor_end,
+				});
+			}
+			zwp_input_method_v2::Request::DeleteSurroundingText {
+				before_length,
+				after_length,
+			} => {
+				pending.lock().delete_surrounding = Some((before_length, after_length));
+			}
+			zwp_input_method_v2::Request::Commit { serial: _ } => {
+				let commit = std::mem::take(&mut *pending.lock());
+				if INPUT_METHOD.lock().as_ref() != Some(input_method) {
+					return;
+				}
+				let Some(text_input) = ACTIVE_TEXT_INPUT.lock().clone() else {
+					return;
+				};
+				send_commit(&text_input, &commit);
+			}
+			// the panel UI places the keyboard, so popups never get shown
+			zwp_input_method_v2::Request::GetInputPopupSurface { id, surface: _ } => {
+				data_init.init(id, ());
+			}
+			// keys come from the panel UI rather than a physical keyboard, so there's nothing to grab
+			zwp_input_method_v2::Request::GrabKeyboard { keyboard } => {
+				data_init.init(keyboard, ());
+			}
+			zwp_input_method_v2::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+
+	fn destroyed(
+		_state: &mut WaylandState,
+		_client: ClientId,
+		input_method: &ZwpInputMethodV2,
+		_data: &Mutex<TextInputCommit>,
+	) {
+		let mut current_input_method = INPUT_METHOD.lock();
+		if current_input_method.as_ref() == Some(input_method) {
+			*current_input_method = None;
+		}
+	}
+}
+
+impl Dispatch<ZwpInputPopupSurfaceV2, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpInputPopupSurfaceV2,
+		request: zwp_input_popup_surface_v2::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_input_popup_surface_v2::Request::Destroy => (),
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl Dispatch<ZwpInputMethodKeyboardGrabV2, (), WaylandState> for WaylandState {
+	fn request(
+		_state: &mut WaylandState,
+		_client: &Client,
+		_resource: &ZwpInputMethodKeyboardGrabV2,
+		request: zwp_input_method_keyboard_grab_v2::Request,
+		_data: &(),
+		_dhandle: &DisplayHandle,
+		_data_init: &mut DataInit<'_, WaylandState>,
+	) {
+		match request {
+			zwp_input_method_keyboard_grab_v2::Request::Release => (),
+			_ => unreachable!(),
+		}
+	}
+}
@@ -1,13 +1,79 @@
+use super::{state::WaylandState, xdg_shell::surface_panel_item};
+use crate::core::client::get_env;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
 use smithay::{
 	delegate_xdg_activation,
-	reexports::wayland_server::protocol::wl_surface::WlSurface,
-	wayland::xdg_activation::{XdgActivationHandler, XdgActivationToken, XdgActivationTokenData},
+	reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+	wayland::xdg_activation::{
+		XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
+	},
 };
+use std::{
+	sync::{Arc, Weak},
+	time::Duration,
+};
+use tracing::debug;
+
+/// Tokens are meant to be used right after the interaction that made them, stale ones are ignored
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Apps can take a while to start up before they show their first window
+const LAUNCH_TOKEN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Launches happen outside the wayland loop, so they need their own way to reach the activation state
+static WAYLAND_STATE: OnceCell<Weak<Mutex<WaylandState>>> = OnceCell::new();
+lazy_static::lazy_static! {
+	/// The startup token of each launch by the activation token it was given
+	static ref LAUNCH_TOKENS: Mutex<FxHashMap<String, String>> = Default::default();
+}
 
-use super::state::WaylandState;
+pub(super) fn set_wayland_state(state: &Arc<Mutex<WaylandState>>) {
+	let _ = WAYLAND_STATE.set(Arc::downgrade(state));
+}
+
+/// Make an activation token for a client about to be launched, so its first window can be activated as the result of the launch.
+pub fn launch_activation_token(startup_token: &str) -> Option<String> {
+	let state = WAYLAND_STATE.get()?.upgrade()?;
+	let mut state = state.lock();
+	state.remove_stale_tokens();
+	let (token, _) = state.xdg_activation_state.create_external_token(None);
+	let token = String::from(token.clone());
+	LAUNCH_TOKENS
+		.lock()
+		.insert(token.clone(), startup_token.to_string());
+	Some(token)
+}
+
+impl WaylandState {
+	/// Tokens that were never used would otherwise pile up forever.
+	fn remove_stale_tokens(&mut self) {
+		let mut launch_tokens = LAUNCH_TOKENS.lock();
+		self.xdg_activation_state
+			.retain_tokens(|token, token_data| {
+				let token = String::from(token.clone());
+				let timeout = match launch_tokens.contains_key(&token) {
+					true => LAUNCH_TOKEN_TIMEOUT,
+					false => TOKEN_TIMEOUT,
+				};
+				let stale = token_data.timestamp.elapsed() > timeout;
+				if stale {
+					launch_tokens.remove(&token);
+				}
+				!stale
+			});
+	}
+
+	/// The `STARDUST_STARTUP_TOKEN` the surface's client was launched with, so the shell can tell which launch it came from.
+	fn startup_token(&self, surface: &WlSurface) -> Option<String> {
+		let client = surface.client()?;
+		let pid = client.get_credentials(&self.display_handle).ok()?.pid;
+		get_env(pid).ok()?.remove("STARDUST_STARTUP_TOKEN")
+	}
+}
 
 impl XdgActivationHandler for WaylandState {
-	fn activation_state(&mut self) -> &mut smithay::wayland::xdg_activation::XdgActivationState {
+	fn activation_state(&mut self) -> &mut XdgActivationState {
 		&mut self.xdg_activation_state
 	}
 
@@ -15,20 +81,38 @@ impl XdgActivationHandler for WaylandState {
 		&mut self,
 		token: XdgActivationToken,
 		token_data: XdgActivationTokenData,
-		_surface: WlSurface,
+		surface: WlSurface,
 	) {
-		dbg!(token);
-		dbg!(token_data);
+		// tokens are single use
+		self.xdg_activation_state.remove_request(&token);
+		self.remove_stale_tokens();
+		let launch_token = LAUNCH_TOKENS.lock().remove(&String::from(token.clone()));
+		let timeout = match launch_token {
+			Some(_) => LAUNCH_TOKEN_TIMEOUT,
+			None => TOKEN_TIMEOUT,
+		};
+		if token_data.timestamp.elapsed() > timeout {
+			debug!(?token, "Ignored activation with stale token");
+			return;
+		}
+		let Some(panel_item) = surface_panel_item(&surface) else {
+			debug!(
+				?token,
+				?surface,
+				"Ignored activation of surface without a panel item"
+			);
+			return;
+		};
+		let startup_token = launch_token.or_else(|| self.startup_token(&surface));
+		panel_item.toplevel_activation_request(&String::from(token), startup_token.as_deref());
 	}
 
 	fn destroy_activation(
 		&mut self,
-		token: XdgActivationToken,
-		token_data: XdgActivationTokenData,
+		_token: XdgActivationToken,
+		_token_data: XdgActivationTokenData,
 		_surface: WlSurface,
 	) {
-		dbg!(token);
-		dbg!(token_data);
 	}
 }
 delegate_xdg_activation!(WaylandState);
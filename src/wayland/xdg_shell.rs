@@ -1,4 +1,6 @@
 use super::{
+	fractional_scale::set_surfaces_preferred_scale,
+	output::{output_infos, set_output_focus, set_surfaces_output},
 	seat::{CursorInfo, KeyboardEvent, PointerEvent, SeatData},
 	state::{ClientState, WaylandState},
 	surface::CoreSurface,
@@ -9,10 +11,14 @@ use crate::{
 		data::KEYMAPS,
 		drawable::model::ModelPart,
 		items::panel::{
-			Backend, ChildInfo, Geometry, PanelItem, PanelItemInitData, SurfaceID, ToplevelInfo,
+			Backend, ChildInfo, Geometry, PanelItem, PanelItemInitData, SurfaceCapture, SurfaceID,
+			TabletToolEvent, TextInputCommit, TextInputInfo, ToplevelInfo,
 		},
 	},
-	wayland::seat::handle_cursor,
+	wayland::{
+		pointer_constraints::handle_pointer_constraint, seat::handle_cursor,
+		text_input::handle_text_input,
+	},
 };
 use color_eyre::eyre::{bail, eyre, Result};
 use mint::Vector2;
@@ -33,6 +39,7 @@ use smithay::reexports::{
 		DisplayHandle, GlobalDispatch, New, Resource, WEnum, Weak as WlWeak,
 	},
 };
+use smithay::wayland::compositor;
 use std::{
 	fmt::Debug,
 	sync::{Arc, Weak},
@@ -294,10 +301,19 @@ impl Dispatch<XdgPositioner, Mutex<PositionerData>, WaylandState> for WaylandSta
 	}
 }
 
+/// Lets protocols that only get a `wl_surface` (like xdg-activation) find the toplevel's panel item.
+struct SurfacePanelItem(Weak<PanelItem<XDGBackend>>);
+pub fn surface_panel_item(wl_surface: &WlSurface) -> Option<Arc<PanelItem<XDGBackend>>> {
+	compositor::with_states(wl_surface, |data| {
+		data.data_map.get::<SurfacePanelItem>()?.0.upgrade()
+	})
+}
+
 pub struct XdgSurfaceData {
 	wl_surface: WlWeak<WlSurface>,
 	surface_id: SurfaceID,
-	panel_item: Weak<PanelItem<XDGBackend>>,
+	/// The panel item the surface is part of, set once it's a toplevel or a popup with a parent
+	popup_parent: Option<Weak<dyn PopupParent>>,
 	geometry: Option<Geometry>,
 }
 impl XdgSurfaceData {
@@ -305,7 +321,7 @@ impl XdgSurfaceData {
 		XdgSurfaceData {
 			wl_surface: wl_surface.downgrade(),
 			surface_id: SurfaceID::Toplevel,
-			panel_item: Weak::new(),
+			popup_parent: None,
 			geometry: None,
 		}
 	}
@@ -315,8 +331,8 @@ impl XdgSurfaceData {
 	pub fn wl_surface(&self) -> Option<WlSurface> {
 		self.wl_surface.upgrade().ok()
 	}
-	pub fn panel_item(&self) -> Option<Arc<PanelItem<XDGBackend>>> {
-		self.panel_item.upgrade()
+	pub fn popup_parent(&self) -> Option<Arc<dyn PopupParent>> {
+		self.popup_parent.as_ref()?.upgrade()
 	}
 }
 // impl Clone for XdgSurfaceData {
@@ -381,12 +397,33 @@ impl Dispatch<XdgSurface, Mutex<XdgSurfaceData>, WaylandState> for WaylandState
 								Box::new(backend),
 								client_credentials.map(|c| c.pid),
 							);
-							xdg_surface_data.lock().panel_item = Arc::downgrade(&panel_item);
+							xdg_surface_data.lock().popup_parent =
+								Some(Arc::downgrade(&panel_item) as Weak<dyn PopupParent>);
+							if let Some(wl_surface) = xdg_surface_data.lock().wl_surface() {
+								compositor::with_states(&wl_surface, |data| {
+									data.data_map.insert_if_missing_threadsafe(|| {
+										SurfacePanelItem(Arc::downgrade(&panel_item))
+									});
+								});
+							}
 							let _ = toplevel_data
 								.lock()
 								.panel_item
 								.set(Arc::downgrade(&panel_item));
 							handle_cursor(&panel_item, panel_item.backend.cursor.clone());
+							if let Some(wl_surface) = panel_item.backend.toplevel_wl_surface() {
+								handle_text_input(
+									&panel_item,
+									&panel_item.backend.seat,
+									&wl_surface,
+								);
+								handle_pointer_constraint(
+									&panel_item,
+									&panel_item.backend.seat,
+									&wl_surface,
+									SurfaceID::Toplevel,
+								);
+							}
 						}
 					},
 					{
@@ -424,50 +461,40 @@ impl Dispatch<XdgSurface, Mutex<XdgSurfaceData>, WaylandState> for WaylandState
 				parent,
 				positioner,
 			} => {
-				let Some(parent) = parent else {return};
-				let Some(parent_data) = parent.data::<Mutex<XdgSurfaceData>>() else {return};
-				let parent_data = parent_data.lock();
+				// without a parent here, another protocol (like layer shell) gives the popup its parent before it's mapped
+				let parent_data = parent.as_ref().and_then(XdgSurfaceData::get);
+				let (parent_id, parent_wl_surface, popup_parent) = match parent_data {
+					Some(parent_data) => {
+						let parent_data = parent_data.lock();
+						(
+							parent_data.surface_id.clone(),
+							parent_data.wl_surface(),
+							parent_data.popup_parent(),
+						)
+					}
+					None => (SurfaceID::Toplevel, None, None),
+				};
 
 				let uid = nanoid!();
-				let popup_data = Mutex::new(PopupData::new(
-					uid.clone(),
-					xdg_surface,
-					parent_data.surface_id.clone(),
-					positioner,
-				));
-				let Some(panel_item) = parent_data.panel_item() else {return};
-				let Some(popup_wl_surface) = popup_data.lock().wl_surface() else {return};
-				handle_cursor(
-					&panel_item,
-					panel_item.backend.seat.new_surface(&popup_wl_surface),
+				let xdg_popup = data_init.init(
+					id,
+					Mutex::new(PopupData::new(
+						uid.clone(),
+						xdg_surface,
+						parent_id,
+						positioner,
+					)),
 				);
-				let xdg_popup = data_init.init(id, popup_data);
 				xdg_surface_data.lock().surface_id = SurfaceID::Child(uid);
-
-				xdg_surface_data.lock().panel_item = Arc::downgrade(&panel_item);
 				debug!(?xdg_popup, ?xdg_surface, "Create XDG popup");
 
-				let xdg_surface = xdg_surface.downgrade();
-				let xdg_popup: WlWeak<XdgPopup> = xdg_popup.downgrade();
-				let Ok(wl_surface) = xdg_surface_data.lock().wl_surface.upgrade() else {return};
-				CoreSurface::add_to(
-					state.display_handle.clone(),
-					&wl_surface,
-					move || {
-						let Ok(xdg_popup) = xdg_popup.upgrade() else {return};
-						let Some(popup_data) = PopupData::get(&xdg_popup) else {return};
-						let popup_data = popup_data.lock();
-						panel_item
-							.backend
-							.new_popup(&panel_item, &xdg_popup, &*popup_data);
-					},
-					move |commit_count| {
-						if commit_count == 0 {
-							if let Ok(xdg_surface) = xdg_surface.upgrade() {
-								xdg_surface.configure(SERIAL_COUNTER.inc())
-							}
-						}
-					},
+				let Some(parent_wl_surface) = parent_wl_surface else {return};
+				let Some(popup_parent) = popup_parent else {return};
+				attach_popup(
+					&state.display_handle,
+					&xdg_popup,
+					&parent_wl_surface,
+					popup_parent,
 				);
 			}
 			xdg_surface::Request::SetWindowGeometry {
@@ -664,10 +691,10 @@ impl PopupData {
 		self.xdg_surface.upgrade().ok()
 	}
 
-	fn panel_item(&self) -> Option<Arc<PanelItem<XDGBackend>>> {
+	fn popup_parent(&self) -> Option<Arc<dyn PopupParent>> {
 		XdgSurfaceData::get(&self.xdg_surface()?)?
 			.lock()
-			.panel_item()
+			.popup_parent()
 	}
 	// fn get_parent(&self) -> Option<XdgSurface> {
 	// 	self.parent.as_ref()?.upgrade().ok()
@@ -712,23 +739,22 @@ impl Dispatch<XdgPopup, Mutex<PopupData>, WaylandState> for WaylandState {
 				let mut data = data.lock();
 				data.grabbed = true;
 				debug!(?xdg_popup, ?seat, serial, "XDG popup grab");
-				let Some(panel_item) = data.panel_item() else {return};
-				panel_item.grab_keyboard(Some(SurfaceID::Child(data.uid.clone())));
+				let Some(popup_parent) = data.popup_parent() else {return};
+				popup_parent.grab_keyboard(Some(SurfaceID::Child(data.uid.clone())));
 			}
 			xdg_popup::Request::Reposition { positioner, token } => {
 				let mut data = data.lock();
 				debug!(?xdg_popup, ?positioner, token, "XDG popup reposition");
 				data.positioner = positioner;
-				let Some(panel_item) = data.panel_item() else {return};
-
-				panel_item.backend.reposition_popup(&panel_item, &*data)
+				let Some(popup_parent) = data.popup_parent() else {return};
+				popup_parent.reposition_popup(&data)
 			}
 			xdg_popup::Request::Destroy => {
 				let data = data.lock();
 				debug!(?xdg_popup, "Destroy XDG popup");
 				if data.grabbed {
-					let Some(panel_item) = data.panel_item() else {return};
-					panel_item.grab_keyboard(None);
+					let Some(popup_parent) = data.popup_parent() else {return};
+					popup_parent.grab_keyboard(None);
 				}
 			}
 			_ => unreachable!(),
@@ -742,8 +768,137 @@ impl Dispatch<XdgPopup, Mutex<PopupData>, WaylandState> for WaylandState {
 		data: &Mutex<PopupData>,
 	) {
 		let data = data.lock();
-		let Some(panel_item) = data.panel_item() else {return};
-		panel_item.backend.drop_popup(&panel_item, &data.uid);
+		let Some(popup_parent) = data.popup_parent() else {return};
+		popup_parent.drop_popup(&data.uid);
+	}
+}
+
+/// Show a popup as a child of its parent's panel item once it's mapped.
+pub fn attach_popup(
+	display_handle: &DisplayHandle,
+	xdg_popup: &XdgPopup,
+	parent_wl_surface: &WlSurface,
+	popup_parent: Arc<dyn PopupParent>,
+) {
+	let Some(popup_data) = PopupData::get(xdg_popup) else {return};
+	let popup_data = popup_data.lock();
+	let uid = popup_data.uid.clone();
+	let Some(xdg_surface) = popup_data.xdg_surface() else {return};
+	let Some(wl_surface) = popup_data.wl_surface() else {return};
+	drop(popup_data);
+	let Some(xdg_surface_data) = XdgSurfaceData::get(&xdg_surface) else {return};
+	xdg_surface_data.lock().popup_parent = Some(Arc::downgrade(&popup_parent));
+	popup_parent.clone().add_popup_surface(&wl_surface, &uid);
+
+	let xdg_surface = xdg_surface.downgrade();
+	let xdg_popup = xdg_popup.downgrade();
+	CoreSurface::add_to(
+		display_handle.clone(),
+		&wl_surface,
+		move || {
+			let Ok(xdg_popup) = xdg_popup.upgrade() else {return};
+			let Some(popup_data) = PopupData::get(&xdg_popup) else {return};
+			popup_parent.new_popup(&xdg_popup, &popup_data.lock());
+		},
+		move |commit_count| {
+			if commit_count == 0 {
+				if let Ok(xdg_surface) = xdg_surface.upgrade() {
+					xdg_surface.configure(SERIAL_COUNTER.inc())
+				}
+			}
+		},
+	);
+	if let Some(core_surface) = CoreSurface::from_wl_surface(&wl_surface) {
+		core_surface.set_parent(parent_wl_surface);
+	}
+}
+
+/// Panel items that popups can be attached to, so layer surfaces can have popups as well as toplevels.
+pub trait PopupParent: Send + Sync {
+	/// Track the cursor and pointer constraints of a popup's surface
+	fn add_popup_surface(self: Arc<Self>, wl_surface: &WlSurface, uid: &str);
+	fn new_popup(&self, popup: &XdgPopup, data: &PopupData);
+	fn reposition_popup(&self, data: &PopupData);
+	fn drop_popup(&self, uid: &str);
+	fn grab_keyboard(&self, surface: Option<SurfaceID>);
+}
+/// Backends whose panel items can have popups.
+pub trait PopupBackend: Backend {
+	fn seat(&self) -> &Arc<SeatData>;
+	fn popups(&self) -> &Popups;
+}
+impl<B: PopupBackend> PopupParent for PanelItem<B> {
+	fn add_popup_surface(self: Arc<Self>, wl_surface: &WlSurface, uid: &str) {
+		handle_cursor(&self, self.backend.seat().new_surface(wl_surface));
+		handle_pointer_constraint(
+			&self,
+			self.backend.seat(),
+			wl_surface,
+			SurfaceID::Child(uid.to_string()),
+		);
+	}
+	fn new_popup(&self, popup: &XdgPopup, data: &PopupData) {
+		self.backend
+			.popups()
+			.0
+			.lock()
+			.insert(data.uid.clone(), popup.downgrade());
+
+		let Some(positioner_data) = data.positioner_data() else {return};
+		self.new_child(
+			&data.uid,
+			ChildInfo {
+				parent: data.parent_id.clone(),
+				geometry: positioner_data.into(),
+			},
+		)
+	}
+	fn reposition_popup(&self, data: &PopupData) {
+		let Some(positioner_data) = data.positioner_data() else {return};
+		self.reposition_child(&data.uid, positioner_data.into())
+	}
+	fn drop_popup(&self, uid: &str) {
+		self.drop_child(uid);
+		let Some(popup) = self.backend.popups().0.lock().remove(uid) else {return};
+		let Some(popup) = popup.upgrade().ok() else {return};
+		let Some(wl_surface) = PopupData::get(&popup).and_then(|data| data.lock().wl_surface()) else {return};
+		self.backend.seat().drop_surface(&wl_surface);
+	}
+	fn grab_keyboard(&self, surface: Option<SurfaceID>) {
+		PanelItem::grab_keyboard(self, surface)
+	}
+}
+
+/// A panel item's popups by their uid.
+#[derive(Default)]
+pub struct Popups(Mutex<FxHashMap<String, WlWeak<XdgPopup>>>);
+impl Popups {
+	pub fn wl_surface(&self, uid: &str) -> Option<WlSurface> {
+		let popup = self.0.lock().get(uid)?.upgrade().ok()?;
+		let wl_surface = PopupData::get(&popup)?.lock().wl_surface();
+		wl_surface
+	}
+	pub fn wl_surfaces(&self) -> Vec<WlSurface> {
+		self.0
+			.lock()
+			.values()
+			.filter_map(|popup| popup.upgrade().ok())
+			.filter_map(|popup| PopupData::get(&popup)?.lock().wl_surface())
+			.collect()
+	}
+	pub fn child_data(&self) -> FxHashMap<String, ChildInfo> {
+		FxHashMap::from_iter(self.0.lock().values().filter_map(|v| {
+			let popup = v.upgrade().ok()?;
+			let data = PopupData::get(&popup)?;
+			let data_lock = data.lock();
+			Some((
+				data_lock.uid.clone(),
+				ChildInfo {
+					parent: data_lock.parent_id.clone(),
+					geometry: data_lock.positioner_data()?.into(),
+				},
+			))
+		}))
 	}
 }
 
@@ -756,7 +911,7 @@ pub struct XDGBackend {
 	toplevel: WlWeak<XdgToplevel>,
 	toplevel_wl_surface: WlWeak<WlSurface>,
 	toplevel_state: Mutex<XdgToplevelState>,
-	popups: Mutex<FxHashMap<String, WlWeak<XdgPopup>>>,
+	popups: Popups,
 	cursor: watch::Receiver<Option<CursorInfo>>,
 	seat: Arc<SeatData>,
 	pointer_grab: Mutex<Option<SurfaceID>>,
@@ -778,7 +933,7 @@ impl XDGBackend {
 				fullscreen: false,
 				activated: false,
 			}),
-			popups: Mutex::new(FxHashMap::default()),
+			popups: Popups::default(),
 			cursor,
 			seat,
 			pointer_grab: Mutex::new(None),
@@ -789,12 +944,7 @@ impl XDGBackend {
 		match id {
 			SurfaceID::Cursor => self.cursor.borrow().as_ref()?.surface.upgrade().ok(),
 			SurfaceID::Toplevel => self.toplevel_wl_surface(),
-			SurfaceID::Child(popup) => {
-				let popups = self.popups.lock();
-				let popup = popups.get(popup)?.upgrade().ok()?;
-				let wl_surface = PopupData::get(&popup)?.lock().wl_surface();
-				wl_surface
-			}
+			SurfaceID::Child(popup) => self.popups.wl_surface(popup),
 		}
 	}
 	fn toplevel(&self) -> Option<XdgToplevel> {
@@ -808,6 +958,13 @@ impl XDGBackend {
 	fn toplevel_wl_surface(&self) -> Option<WlSurface> {
 		self.toplevel_wl_surface.upgrade().ok()
 	}
+	/// The toplevel's surface and all the popups' surfaces
+	fn wl_surfaces(&self) -> Vec<WlSurface> {
+		self.toplevel_wl_surface()
+			.into_iter()
+			.chain(self.popups.wl_surfaces())
+			.collect()
+	}
 
 	fn configure(&self, size: Option<Vector2<u32>>) {
 		let Ok(xdg_toplevel) = self.toplevel.upgrade() else {return};
@@ -839,56 +996,6 @@ impl XDGBackend {
 		states
 	}
 
-	pub fn new_popup(
-		&self,
-		panel_item: &PanelItem<XDGBackend>,
-		popup: &XdgPopup,
-		data: &PopupData,
-	) {
-		self.popups
-			.lock()
-			.insert(data.uid.clone(), popup.downgrade());
-
-		let Some(positioner_data) = data.positioner_data() else {return};
-		panel_item.new_child(
-			&data.uid,
-			ChildInfo {
-				parent: data.parent_id.clone(),
-				geometry: positioner_data.into(),
-			},
-		)
-	}
-	pub fn reposition_popup(&self, panel_item: &PanelItem<XDGBackend>, popup_state: &PopupData) {
-		let Some(positioner_data) = popup_state.positioner_data() else {return};
-		panel_item.reposition_child(&popup_state.uid, positioner_data.into())
-	}
-	pub fn drop_popup(&self, panel_item: &PanelItem<XDGBackend>, uid: &str) {
-		panel_item.drop_child(uid);
-		let Some(popup) = self
-				.popups
-				.lock()
-				.remove(uid) else {return};
-		let Some(popup) = popup.upgrade().ok() else {return};
-		let Some(popup) = popup.data::<Arc<PopupData>>().cloned() else {return};
-		let Some(wl_surface) = popup.wl_surface() else {return};
-		self.seat.drop_surface(&wl_surface);
-	}
-
-	fn child_data(&self) -> FxHashMap<String, ChildInfo> {
-		FxHashMap::from_iter(self.popups.lock().values().filter_map(|v| {
-			let popup = v.upgrade().ok()?;
-			let data = PopupData::get(&popup)?;
-			let data_lock = data.lock();
-			Some((
-				data_lock.uid.clone(),
-				ChildInfo {
-					parent: data_lock.parent_id.clone(),
-					geometry: data_lock.positioner_data()?.into(),
-				},
-			))
-		}))
-	}
-
 	fn flush_client(&self) {
 		let Some(client) = self.toplevel_wl_surface().and_then(|s| s.client()) else {return};
 		if let Some(client_state) = client.get_data::<ClientState>() {
@@ -903,6 +1010,14 @@ impl Drop for XDGBackend {
 		debug!("Dropped panel item gracefully");
 	}
 }
+impl PopupBackend for XDGBackend {
+	fn seat(&self) -> &Arc<SeatData> {
+		&self.seat
+	}
+	fn popups(&self) -> &Popups {
+		&self.popups
+	}
+}
 impl Backend for XDGBackend {
 	fn start_data(&self) -> Result<PanelItemInitData> {
 		let toplevel = self.toplevel();
@@ -953,9 +1068,12 @@ impl Backend for XDGBackend {
 		Ok(PanelItemInitData {
 			cursor: self.cursor.borrow().as_ref().and_then(|c| c.cursor_data()),
 			toplevel,
-			children: self.child_data(),
+			children: self.popups.child_data(),
 			pointer_grab,
 			keyboard_grab,
+			layer_surface: None,
+			outputs: output_infos(),
+			text_input: self.seat.text_input.info(&wl_surface),
 		})
 	}
 
@@ -965,6 +1083,13 @@ impl Backend for XDGBackend {
 
 		core_surface.apply_material(model_part);
 	}
+	fn capture_surface(&self, surface: &SurfaceID) -> Result<SurfaceCapture> {
+		let core_surface = self
+			.wl_surface_from_id(surface)
+			.and_then(|wl_surface| CoreSurface::from_wl_surface(&wl_surface))
+			.ok_or_else(|| eyre!("Surface not found"))?;
+		Ok(core_surface.capture_to_fd())
+	}
 
 	fn close_toplevel(&self) {
 		let Ok(xdg_toplevel) = self.toplevel.upgrade() else {return};
@@ -979,6 +1104,21 @@ impl Backend for XDGBackend {
 	fn set_toplevel_focused_visuals(&self, focused: bool) {
 		self.toplevel_state.lock().activated = focused;
 		self.configure(None);
+		if let Some(wl_surface) = self.toplevel_wl_surface() {
+			self.seat.text_input.set_focused(&wl_surface, focused);
+			if focused {
+				set_output_focus(&wl_surface);
+			}
+		}
+		if !focused {
+			self.seat.pointer_constraints.deactivate(self.wl_surfaces());
+		}
+	}
+	fn set_output(&self, output: &str) -> Result<()> {
+		set_surfaces_output(self.wl_surfaces(), output)
+	}
+	fn set_preferred_scale(&self, scale: f64) {
+		set_surfaces_preferred_scale(self.wl_surfaces(), scale)
 	}
 
 	fn pointer_motion(&self, surface: &SurfaceID, position: Vector2<f32>) {
@@ -1012,6 +1152,11 @@ impl Backend for XDGBackend {
 		)
 	}
 
+	fn pointer_relative_motion(&self, surface: &SurfaceID, delta: Vector2<f32>) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {return};
+		self.seat.pointer_relative_motion(&surface, delta);
+	}
+
 	fn keyboard_keys(&self, surface: &SurfaceID, keymap_id: &str, keys: Vec<i32>) {
 		let Some(surface) = self.wl_surface_from_id(surface) else {return};
 		let keymaps = KEYMAPS.lock();
@@ -1030,6 +1175,14 @@ impl Backend for XDGBackend {
 		}
 	}
 
+	fn text_input_state(&self) -> Option<TextInputInfo> {
+		self.seat.text_input.info(&self.toplevel_wl_surface()?)
+	}
+	fn text_input_commit(&self, commit: TextInputCommit) {
+		let Some(wl_surface) = self.toplevel_wl_surface() else {return};
+		self.seat.text_input.commit(&wl_surface, &commit);
+	}
+
 	fn touch_down(&self, surface: &SurfaceID, id: u32, position: Vector2<f32>) {
 		let Some(surface) = self.wl_surface_from_id(surface) else {return};
 		self.seat.touch_down(&surface, id, position)
@@ -1043,4 +1196,8 @@ impl Backend for XDGBackend {
 	fn reset_touches(&self) {
 		self.seat.reset_touches()
 	}
+	fn tablet_tool_event(&self, surface: &SurfaceID, event: TabletToolEvent) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {return};
+		self.seat.tablet.tool_event(&surface, event);
+	}
 }
@@ -1,4 +1,7 @@
 use super::{
+	fractional_scale::set_surfaces_preferred_scale,
+	output::{output_infos, set_output_focus, set_surfaces_output},
+	pointer_constraints::handle_pointer_constraint,
 	seat::{KeyboardEvent, PointerEvent, SeatData},
 	X_DISPLAY,
 };
@@ -6,11 +9,14 @@ use crate::{
 	nodes::{
 		data::KEYMAPS,
 		drawable::model::ModelPart,
-		items::panel::{Backend, Geometry, PanelItem, PanelItemInitData, SurfaceID, ToplevelInfo},
+		items::panel::{
+			Backend, Geometry, PanelItem, PanelItemInitData, SurfaceCapture, SurfaceID,
+			TabletToolEvent, TextInputCommit, TextInputInfo, ToplevelInfo,
+		},
 	},
 	wayland::surface::CoreSurface,
 };
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Result};
 use mint::Vector2;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
@@ -163,6 +169,13 @@ impl XwmHandler for XWaylandHandler {
 								.and_then(|c| c.get_credentials(&dh).ok())
 								.map(|c| c.pid),
 						);
+						// Xwayland uses pointer constraints for X clients that grab or warp the pointer
+						handle_pointer_constraint(
+							&panel_item,
+							&panel_item.backend.seat,
+							&wl_surface,
+							SurfaceID::Toplevel,
+						);
 						panel_item
 					});
 				}
@@ -323,6 +336,9 @@ impl Backend for X11Backend {
 			children: FxHashMap::default(),
 			pointer_grab: self._pointer_grab.lock().clone(),
 			keyboard_grab: self._keyboard_grab.lock().clone(),
+			layer_surface: None,
+			outputs: output_infos(),
+			text_input: None,
 		})
 	}
 	fn close_toplevel(&self) {
@@ -340,6 +356,21 @@ impl Backend for X11Backend {
 	}
 	fn set_toplevel_focused_visuals(&self, focused: bool) {
 		let _ = self.toplevel.set_activated(focused);
+		if focused {
+			if let Some(wl_surface) = self.toplevel.wl_surface() {
+				set_output_focus(&wl_surface);
+			}
+		} else {
+			self.seat
+				.pointer_constraints
+				.deactivate(self.toplevel.wl_surface());
+		}
+	}
+	fn set_output(&self, output: &str) -> Result<()> {
+		set_surfaces_output(self.toplevel.wl_surface(), output)
+	}
+	fn set_preferred_scale(&self, scale: f64) {
+		set_surfaces_preferred_scale(self.toplevel.wl_surface(), scale)
 	}
 
 	fn apply_surface_material(&self, surface: SurfaceID, model_part: &Arc<ModelPart>) {
@@ -352,6 +383,13 @@ impl Backend for X11Backend {
 
 		core_surface.apply_material(model_part);
 	}
+	fn capture_surface(&self, surface: &SurfaceID) -> Result<SurfaceCapture> {
+		let core_surface = self
+			.wl_surface_from_id(surface)
+			.and_then(|wl_surface| CoreSurface::from_wl_surface(&wl_surface))
+			.ok_or_else(|| eyre!("Surface not found"))?;
+		Ok(core_surface.capture_to_fd())
+	}
 
 	fn pointer_motion(&self, surface: &SurfaceID, position: Vector2<f32>) {
 		let Some(surface) = self.wl_surface_from_id(surface) else {
@@ -390,6 +428,13 @@ impl Backend for X11Backend {
 		)
 	}
 
+	fn pointer_relative_motion(&self, surface: &SurfaceID, delta: Vector2<f32>) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat.pointer_relative_motion(&surface, delta);
+	}
+
 	fn keyboard_keys(&self, surface: &SurfaceID, keymap_id: &str, keys: Vec<i32>) {
 		let Some(surface) = self.wl_surface_from_id(surface) else {
 			return;
@@ -412,6 +457,12 @@ impl Backend for X11Backend {
 		}
 	}
 
+	// X clients use X input methods rather than text-input-v3
+	fn text_input_state(&self) -> Option<TextInputInfo> {
+		None
+	}
+	fn text_input_commit(&self, _commit: TextInputCommit) {}
+
 	fn touch_down(&self, surface: &SurfaceID, id: u32, position: Vector2<f32>) {
 		let Some(surface) = self.wl_surface_from_id(surface) else {
 			return;
@@ -427,4 +478,10 @@ impl Backend for X11Backend {
 	fn reset_touches(&self) {
 		self.seat.reset_touches()
 	}
+	fn tablet_tool_event(&self, surface: &SurfaceID, event: TabletToolEvent) {
+		let Some(surface) = self.wl_surface_from_id(surface) else {
+			return;
+		};
+		self.seat.tablet.tool_event(&surface, event);
+	}
 }
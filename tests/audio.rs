@@ -0,0 +1,54 @@
+mod common;
+
+use color_eyre::eyre::Result;
+use common::{TestServer, Transform};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+
+#[derive(Serialize)]
+struct CreateSoundInfo<'a> {
+	name: &'a str,
+	parent_path: &'a str,
+	transform: Transform,
+}
+#[derive(Deserialize)]
+struct BoundingBox {}
+
+#[tokio::test]
+async fn create_sound_from_fd() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	// headless never decodes it, so only the header has to look like a WAV
+	let sound_path = server.dir().join("sound");
+	std::fs::write(&sound_path, b"RIFF\0\0\0\0WAVE")?;
+	client.signal_with_fds(
+		"/audio",
+		"create_sound_from_fd",
+		CreateSoundInfo {
+			name: "beep",
+			parent_path: "/",
+			transform: Transform::none(),
+		},
+		vec![File::open(&sound_path)?.into()],
+	)?;
+	let _: BoundingBox = client
+		.method("/audio/sound/beep", "get_local_bounding_box", ())
+		.await?;
+
+	// without an fd there's nothing to play, so no sound should be made
+	client.signal(
+		"/audio",
+		"create_sound_from_fd",
+		CreateSoundInfo {
+			name: "silence",
+			parent_path: "/",
+			transform: Transform::none(),
+		},
+	)?;
+	let bounding_box: Result<BoundingBox> = client
+		.method("/audio/sound/silence", "get_local_bounding_box", ())
+		.await;
+	assert!(bounding_box.is_err());
+	Ok(())
+}
@@ -0,0 +1,304 @@
+//! Harness for the integration tests: runs the server headless on a throwaway
+//! runtime dir and talks to it over the real socket like any other client would.
+#![allow(dead_code)]
+
+use color_eyre::eyre::{bail, eyre, Result};
+use mint::{Quaternion, Vector3};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use stardust_xr::{
+	messenger::{self, MessageSenderHandle},
+	scenegraph::{Scenegraph, ScenegraphError},
+	schemas::flex::{deserialize, flexbuffers, serialize},
+	values::Datamap,
+};
+use std::{
+	os::{fd::OwnedFd, unix::fs::FileTypeExt},
+	path::{Path, PathBuf},
+	process::{Child, Command, Stdio},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tokio::{net::UnixStream, sync::oneshot, task::JoinHandle};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const SIGNAL_TIMEOUT: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct TestServer {
+	child: Child,
+	dir: PathBuf,
+	pub socket_path: PathBuf,
+}
+impl TestServer {
+	pub async fn start() -> Result<Self> {
+		Self::start_with(|_| Ok(())).await
+	}
+	/// Start the server after `setup` has had a chance to put files (config, saved state...) in its directories.
+	pub async fn start_with(setup: impl FnOnce(&Path) -> Result<()>) -> Result<Self> {
+		let dir = std::env::temp_dir().join(format!("stardust-test-{}", nanoid::nanoid!()));
+		for subdir in ["runtime", "config", "state"] {
+			std::fs::create_dir_all(dir.join(subdir))?;
+		}
+		setup(&dir)?;
+
+		let child = Command::new(env!("CARGO_BIN_EXE_stardust-xr-server"))
+			.arg("--headless")
+			.env("XDG_RUNTIME_DIR", dir.join("runtime"))
+			.env("XDG_CONFIG_HOME", dir.join("config"))
+			.env("XDG_STATE_HOME", dir.join("state"))
+			.env_remove("STARDUST_INSTANCE")
+			.env_remove("STARDUST_STARTUP_TOKEN")
+			.env_remove("WAYLAND_DISPLAY")
+			.stdin(Stdio::null())
+			.spawn()?;
+		let mut server = TestServer {
+			child,
+			dir,
+			socket_path: PathBuf::new(),
+		};
+
+		let deadline = Instant::now() + STARTUP_TIMEOUT;
+		while Instant::now() < deadline {
+			if let Some(status) = server.child.try_wait()? {
+				bail!("Server exited early with {status}");
+			}
+			if let Some(socket_path) = server.find_socket() {
+				server.socket_path = socket_path;
+				return Ok(server);
+			}
+			tokio::time::sleep(POLL_INTERVAL).await;
+		}
+		bail!("Server never created its socket")
+	}
+
+	fn find_socket(&self) -> Option<PathBuf> {
+		std::fs::read_dir(self.dir.join("runtime"))
+			.ok()?
+			.filter_map(|entry| entry.ok())
+			.find(|entry| {
+				entry.file_name().to_string_lossy().starts_with("stardust-")
+					&& entry.file_type().map(|t| t.is_socket()).unwrap_or(false)
+			})
+			.map(|entry| entry.path())
+	}
+
+	/// Root of the server's throwaway directories, containing `runtime`, `config` and `state`.
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	pub async fn connect(&self) -> Result<TestClient> {
+		TestClient::connect(&self.socket_path).await
+	}
+}
+impl Drop for TestServer {
+	fn drop(&mut self) {
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+		let _ = std::fs::remove_dir_all(&self.dir);
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Signal {
+	pub path: String,
+	pub method: String,
+	pub data: Vec<u8>,
+}
+impl Signal {
+	pub fn deserialize<D: DeserializeOwned>(&self) -> Result<D> {
+		Ok(deserialize(&self.data)?)
+	}
+}
+
+/// Records every signal the server sends so tests can wait on them.
+#[derive(Default)]
+struct SignalRecorder {
+	signals: Mutex<Vec<Signal>>,
+}
+impl Scenegraph for SignalRecorder {
+	fn send_signal(
+		&self,
+		path: &str,
+		method: &str,
+		data: &[u8],
+		_fds: Vec<OwnedFd>,
+	) -> Result<(), ScenegraphError> {
+		self.signals.lock().push(Signal {
+			path: path.to_string(),
+			method: method.to_string(),
+			data: data.to_vec(),
+		});
+		Ok(())
+	}
+	fn execute_method(
+		&self,
+		_path: &str,
+		_method: &str,
+		_data: &[u8],
+		_fds: Vec<OwnedFd>,
+		response: oneshot::Sender<Result<(Vec<u8>, Vec<OwnedFd>), ScenegraphError>>,
+	) {
+		let _ = response.send(Err(ScenegraphError::MethodNotFound));
+	}
+}
+
+pub struct TestClient {
+	handle: MessageSenderHandle,
+	recorder: Arc<SignalRecorder>,
+	dispatch_task: JoinHandle<()>,
+	flush_task: JoinHandle<()>,
+}
+impl TestClient {
+	pub async fn connect(socket_path: &Path) -> Result<Self> {
+		let connection = UnixStream::connect(socket_path).await?;
+		let (mut messenger_tx, mut messenger_rx) = messenger::create(connection);
+		let recorder = Arc::new(SignalRecorder::default());
+
+		let handle = messenger_tx.handle();
+		let dispatch_task = tokio::spawn({
+			let recorder = recorder.clone();
+			async move { while messenger_rx.dispatch(&*recorder).await.is_ok() {} }
+		});
+		let flush_task = tokio::spawn(async move { while messenger_tx.flush().await.is_ok() {} });
+
+		Ok(TestClient {
+			handle,
+			recorder,
+			dispatch_task,
+			flush_task,
+		})
+	}
+
+	pub fn signal<S: Serialize>(&self, path: &str, method: &str, args: S) -> Result<()> {
+		self.handle
+			.signal(path, method, &serialize(args)?, Vec::new())?;
+		Ok(())
+	}
+	/// Like `signal`, but sends fds along with it.
+	pub fn signal_with_fds<S: Serialize>(
+		&self,
+		path: &str,
+		method: &str,
+		args: S,
+		fds: Vec<OwnedFd>,
+	) -> Result<()> {
+		self.handle.signal(path, method, &serialize(args)?, fds)?;
+		Ok(())
+	}
+
+	pub async fn method<S: Serialize, D: DeserializeOwned>(
+		&self,
+		path: &str,
+		method: &str,
+		args: S,
+	) -> Result<D> {
+		let (result, _fds) = self.method_with_fds(path, method, args).await?;
+		Ok(result)
+	}
+	/// Like `method`, but keeps any fds the server sent back.
+	pub async fn method_with_fds<S: Serialize, D: DeserializeOwned>(
+		&self,
+		path: &str,
+		method: &str,
+		args: S,
+	) -> Result<(D, Vec<OwnedFd>)> {
+		let result = self
+			.handle
+			.method(path, method, &serialize(args)?, Vec::new())?
+			.await
+			.map_err(|e| eyre!(e))?;
+		let (message, fds) = result.into_components();
+		Ok((deserialize(&message)?, fds))
+	}
+
+	/// Wait for the server to send `method` to the node at `path`, taking it out of the recorded signals.
+	pub async fn wait_for_signal(&self, path: &str, method: &str) -> Result<Signal> {
+		self.wait_for_signal_timeout(path, method, SIGNAL_TIMEOUT)
+			.await
+	}
+	/// Like `wait_for_signal`, for signals that take longer than usual to come.
+	pub async fn wait_for_signal_timeout(
+		&self,
+		path: &str,
+		method: &str,
+		timeout: Duration,
+	) -> Result<Signal> {
+		let deadline = Instant::now() + timeout;
+		loop {
+			if let Some(signal) = self.take_signal(path, method) {
+				return Ok(signal);
+			}
+			if Instant::now() > deadline {
+				bail!("Timed out waiting for signal {method} on {path}");
+			}
+			tokio::time::sleep(POLL_INTERVAL).await;
+		}
+	}
+	pub fn take_signal(&self, path: &str, method: &str) -> Option<Signal> {
+		let mut signals = self.recorder.signals.lock();
+		let index = signals
+			.iter()
+			.position(|s| s.path == path && s.method == method)?;
+		Some(signals.remove(index))
+	}
+}
+impl Drop for TestClient {
+	fn drop(&mut self) {
+		self.dispatch_task.abort();
+		self.flush_task.abort();
+	}
+}
+
+/// Mirrors the protocol's `Transform` struct.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Transform {
+	pub translation: Option<Vector3<f32>>,
+	pub rotation: Option<Quaternion<f32>>,
+	pub scale: Option<Vector3<f32>>,
+}
+impl Transform {
+	pub fn none() -> Self {
+		Self::default()
+	}
+	pub fn from_translation(translation: [f32; 3]) -> Self {
+		Transform {
+			translation: Some(translation.into()),
+			..Default::default()
+		}
+	}
+	pub fn from_rotation(rotation: [f32; 4]) -> Self {
+		Transform {
+			rotation: Some(rotation.into()),
+			..Default::default()
+		}
+	}
+	pub fn from_translation_scale(translation: [f32; 3], scale: [f32; 3]) -> Self {
+		Transform {
+			translation: Some(translation.into()),
+			scale: Some(scale.into()),
+			..Default::default()
+		}
+	}
+}
+
+pub fn datamap(keys: &[&str]) -> Datamap {
+	let mut fbb = flexbuffers::Builder::default();
+	let mut map = fbb.start_map();
+	for key in keys {
+		map.push(key, true);
+	}
+	map.end_map();
+	Datamap::from_raw(fbb.view().to_vec()).unwrap()
+}
+
+pub fn assert_approx_eq(a: f32, b: f32) {
+	assert!((a - b).abs() < 0.001, "{a} != {b}");
+}
+pub fn assert_vec_approx_eq(a: Vector3<f32>, b: [f32; 3]) {
+	assert!(
+		(a.x - b[0]).abs() < 0.001 && (a.y - b[1]).abs() < 0.001 && (a.z - b[2]).abs() < 0.001,
+		"{a:?} != {b:?}"
+	);
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use color_eyre::eyre::{eyre, Result};
+use common::{datamap, TestServer, Transform};
+use serde::Serialize;
+use stardust_xr::values::Datamap;
+use std::io::Read;
+
+#[tokio::test]
+async fn pulse_sender_finds_receiver() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("receiver_field", "/", [0.0_f32, 0.0, 0.0], 0.1_f32),
+	)?;
+	client.signal(
+		"/data",
+		"create_pulse_receiver",
+		(
+			"receiver",
+			"/",
+			Transform::none(),
+			"/field/receiver_field",
+			datamap(&["test"]),
+		),
+	)?;
+	client.signal(
+		"/data",
+		"create_pulse_sender",
+		("sender", "/", Transform::none(), datamap(&["test"])),
+	)?;
+
+	let new_receiver = client
+		.wait_for_signal("/data/sender/sender", "new_receiver")
+		.await?;
+	let (uid, receiver_path, field_path): (String, String, String) = new_receiver.deserialize()?;
+	assert_eq!(receiver_path, format!("/data/sender/sender/{uid}"));
+	assert_eq!(field_path, format!("/data/sender/sender/{uid}/field"));
+
+	// the receiver's field is usable through its alias
+	let distance: f32 = client
+		.method(&field_path, "distance", ("/", [0.0_f32, 0.0, 1.0]))
+		.await?;
+	assert!((distance - 0.9).abs() < 0.001);
+
+	client.signal(
+		&receiver_path,
+		"send_data",
+		("/data/sender/sender", datamap(&["test", "extra"])),
+	)?;
+	let data = client
+		.wait_for_signal("/data/receiver/receiver", "data")
+		.await?;
+	let (_sender_uid, _data): (String, Datamap) = data.deserialize()?;
+	Ok(())
+}
+
+#[tokio::test]
+async fn pulse_sender_ignores_mismatched_mask() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("receiver_field", "/", [0.0_f32, 0.0, 0.0], 0.1_f32),
+	)?;
+	client.signal(
+		"/data",
+		"create_pulse_receiver",
+		(
+			"receiver",
+			"/",
+			Transform::none(),
+			"/field/receiver_field",
+			datamap(&["something_else"]),
+		),
+	)?;
+	client.signal(
+		"/data",
+		"create_pulse_sender",
+		("sender", "/", Transform::none(), datamap(&["test"])),
+	)?;
+
+	assert!(client
+		.wait_for_signal("/data/sender/sender", "new_receiver")
+		.await
+		.is_err());
+	Ok(())
+}
+
+#[tokio::test]
+async fn keymaps() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	let keymap = "xkb_keymap { }".to_string();
+	let id: String = client.method("/data", "register_keymap", &keymap).await?;
+	let same_id: String = client.method("/data", "register_keymap", &keymap).await?;
+	assert_eq!(id, same_id);
+
+	let fetched: String = client.method("/data", "get_keymap", &id).await?;
+	assert_eq!(fetched, keymap);
+	assert!(client
+		.method::<_, String>("/data", "get_keymap", "nonexistent")
+		.await
+		.is_err());
+	Ok(())
+}
+
+#[derive(Serialize)]
+struct SetClipboardInfo {
+	contents: Vec<(String, Vec<u8>)>,
+}
+
+#[tokio::test]
+async fn clipboard() -> Result<()> {
+	let server = TestServer::start().await?;
+	let copier = server.connect().await?;
+	let paster = server.connect().await?;
+
+	let mime_types: Vec<String> = paster
+		.method("/data", "get_clipboard_mime_types", ())
+		.await?;
+	assert!(mime_types.is_empty());
+
+	copier.signal(
+		"/data",
+		"set_clipboard",
+		SetClipboardInfo {
+			contents: vec![
+				("text/plain".to_string(), b"hello".to_vec()),
+				("text/html".to_string(), b"<b>hello</b>".to_vec()),
+			],
+		},
+	)?;
+	// the copier's own round trip makes sure the signal went through before the paster looks
+	let mime_types: Vec<String> = copier
+		.method("/data", "get_clipboard_mime_types", ())
+		.await?;
+	assert_eq!(mime_types, ["text/plain", "text/html"]);
+	let mime_types: Vec<String> = paster
+		.method("/data", "get_clipboard_mime_types", ())
+		.await?;
+	assert_eq!(mime_types, ["text/plain", "text/html"]);
+
+	let ((), fds) = paster
+		.method_with_fds("/data", "read_clipboard", "text/html")
+		.await?;
+	let fd = fds
+		.into_iter()
+		.next()
+		.ok_or_else(|| eyre!("No fd to read the clipboard from"))?;
+	let mut contents = String::new();
+	std::fs::File::from(fd).read_to_string(&mut contents)?;
+	assert_eq!(contents, "<b>hello</b>");
+
+	assert!(paster
+		.method_with_fds::<_, ()>("/data", "read_clipboard", "image/png")
+		.await
+		.is_err());
+
+	copier.signal(
+		"/data",
+		"set_clipboard",
+		SetClipboardInfo { contents: vec![] },
+	)?;
+	let mime_types: Vec<String> = copier
+		.method("/data", "get_clipboard_mime_types", ())
+		.await?;
+	assert!(mime_types.is_empty());
+	Ok(())
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use color_eyre::eyre::Result;
+use common::{assert_vec_approx_eq, TestServer, Transform};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct ClientInfo {
+	pid: Option<i32>,
+	nodes: Vec<NodeInfo>,
+}
+#[derive(Debug, Deserialize)]
+struct NodeInfo {
+	path: String,
+	aspects: Vec<String>,
+	spatial: Option<SpatialInfo>,
+	alias_of: Option<AliasInfo>,
+}
+#[derive(Debug, Deserialize)]
+struct SpatialInfo {
+	parents: Vec<String>,
+	global_transform: Transform,
+}
+#[derive(Debug, Deserialize)]
+struct AliasInfo {
+	original_path: String,
+}
+
+#[tokio::test]
+async fn list_clients() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"parent",
+			"/",
+			Transform::from_translation([0.0, 1.0, 0.0]),
+			false,
+		),
+	)?;
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		(
+			"field",
+			"/spatial/spatial/parent",
+			[1.0_f32, 0.0, 0.0],
+			0.5_f32,
+		),
+	)?;
+
+	let clients: Vec<ClientInfo> = client.method("/debug", "list_clients", ()).await?;
+	let this_client = clients
+		.iter()
+		.find(|c| c.pid == Some(std::process::id() as i32))
+		.expect("Couldn't find this client in the list");
+
+	let field = this_client
+		.nodes
+		.iter()
+		.find(|n| n.path == "/field/field")
+		.expect("Field node missing");
+	assert!(field.aspects.iter().any(|a| a == "Spatial"));
+	assert!(field.aspects.iter().any(|a| a == "Field"));
+	let field_spatial = field.spatial.as_ref().unwrap();
+	assert_eq!(field_spatial.parents, ["/spatial/spatial/parent", "/"]);
+	assert_vec_approx_eq(
+		field_spatial.global_transform.translation.unwrap(),
+		[1.0, 1.0, 0.0],
+	);
+
+	// the hmd is an alias to a node owned by the server
+	let hmd = this_client
+		.nodes
+		.iter()
+		.find(|n| n.path == "/hmd")
+		.expect("HMD alias missing");
+	assert!(hmd.alias_of.is_some());
+	assert!(hmd
+		.alias_of
+		.as_ref()
+		.unwrap()
+		.original_path
+		.starts_with('/'));
+	Ok(())
+}
+
+#[tokio::test]
+async fn debug_is_privileged() -> Result<()> {
+	let server = TestServer::start_with(|dir| {
+		let config_dir = dir.join("config").join("stardust");
+		std::fs::create_dir_all(&config_dir)?;
+		std::fs::write(config_dir.join("permissions"), "* zone\n")?;
+		Ok(())
+	})
+	.await?;
+	let client = server.connect().await?;
+
+	let result: Result<Vec<ClientInfo>> = client.method("/debug", "list_clients", ()).await;
+	assert!(result.is_err());
+	Ok(())
+}
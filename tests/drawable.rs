@@ -0,0 +1,254 @@
+mod common;
+
+use color_eyre::eyre::Result;
+use common::{assert_vec_approx_eq, TestClient, TestServer, Transform};
+use mint::Vector3;
+use serde::{Deserialize, Serialize};
+use stardust_xr::schemas::flex::serialize;
+use std::fs::File;
+
+/// Mirrors the server's mesh data, leaving out the optional attributes.
+#[derive(Serialize)]
+struct MeshData {
+	positions: Vec<[f32; 3]>,
+	normals: Option<Vec<[f32; 3]>>,
+	uvs: Option<Vec<[f32; 2]>>,
+	colors: Option<Vec<[f32; 4]>>,
+	indices: Vec<u32>,
+}
+impl MeshData {
+	fn triangle(positions: [[f32; 3]; 3]) -> Self {
+		MeshData {
+			positions: positions.to_vec(),
+			normals: None,
+			uvs: None,
+			colors: None,
+			indices: vec![0, 1, 2],
+		}
+	}
+}
+#[derive(Serialize)]
+struct CreateMeshInfo<'a> {
+	name: &'a str,
+	parent_path: &'a str,
+	transform: Transform,
+	mesh: Option<MeshData>,
+}
+
+#[derive(Deserialize)]
+struct BoundingBox {
+	center: Vector3<f32>,
+	size: Vector3<f32>,
+}
+async fn bounds(client: &TestClient, path: &str) -> Result<BoundingBox> {
+	client.method(path, "get_local_bounding_box", ()).await
+}
+
+#[tokio::test]
+async fn create_mesh() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/drawable",
+		"create_mesh",
+		CreateMeshInfo {
+			name: "triangle",
+			parent_path: "/",
+			transform: Transform::none(),
+			mesh: Some(MeshData::triangle([
+				[0.0, 0.0, 0.0],
+				[1.0, 0.0, 0.0],
+				[0.0, 2.0, 0.0],
+			])),
+		},
+	)?;
+	let bounding_box = bounds(&client, "/drawable/mesh/triangle").await?;
+	assert_vec_approx_eq(bounding_box.center, [0.5, 1.0, 0.0]);
+	assert_vec_approx_eq(bounding_box.size, [1.0, 2.0, 0.0]);
+
+	client.signal(
+		"/drawable/mesh/triangle",
+		"update_mesh",
+		Some(MeshData::triangle([
+			[0.0, 0.0, 0.0],
+			[-2.0, 0.0, 0.0],
+			[0.0, 0.0, -4.0],
+		])),
+	)?;
+	let bounding_box = bounds(&client, "/drawable/mesh/triangle").await?;
+	assert_vec_approx_eq(bounding_box.center, [-1.0, 0.0, -2.0]);
+	assert_vec_approx_eq(bounding_box.size, [2.0, 0.0, 4.0]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn create_mesh_from_fd() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	let mesh_path = server.dir().join("triangle.flex");
+	std::fs::write(
+		&mesh_path,
+		serialize(MeshData::triangle([
+			[0.0, 0.0, 0.0],
+			[0.0, 1.0, 0.0],
+			[0.0, 0.0, 3.0],
+		]))?,
+	)?;
+	client.signal_with_fds(
+		"/drawable",
+		"create_mesh",
+		CreateMeshInfo {
+			name: "triangle",
+			parent_path: "/",
+			transform: Transform::none(),
+			mesh: None,
+		},
+		vec![File::open(&mesh_path)?.into()],
+	)?;
+	let bounding_box = bounds(&client, "/drawable/mesh/triangle").await?;
+	assert_vec_approx_eq(bounding_box.center, [0.0, 0.5, 1.5]);
+	assert_vec_approx_eq(bounding_box.size, [0.0, 1.0, 3.0]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn mesh_from_device_fd() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	// /dev/zero never ends, so it has to be turned away instead of read
+	client.signal_with_fds(
+		"/drawable",
+		"create_mesh",
+		CreateMeshInfo {
+			name: "endless",
+			parent_path: "/",
+			transform: Transform::none(),
+			mesh: None,
+		},
+		vec![File::open("/dev/zero")?.into()],
+	)?;
+	assert!(bounds(&client, "/drawable/mesh/endless").await.is_err());
+	// and the server is still there to answer
+	client.signal(
+		"/drawable",
+		"create_mesh",
+		CreateMeshInfo {
+			name: "triangle",
+			parent_path: "/",
+			transform: Transform::none(),
+			mesh: Some(MeshData::triangle([[0.0; 3]; 3])),
+		},
+	)?;
+	bounds(&client, "/drawable/mesh/triangle").await?;
+	Ok(())
+}
+
+#[tokio::test]
+async fn invalid_mesh() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	// index 3 is past the last vertex, so no mesh should be made
+	let mut mesh = MeshData::triangle([[0.0; 3]; 3]);
+	mesh.indices = vec![0, 1, 3];
+	client.signal(
+		"/drawable",
+		"create_mesh",
+		CreateMeshInfo {
+			name: "broken",
+			parent_path: "/",
+			transform: Transform::none(),
+			mesh: Some(mesh),
+		},
+	)?;
+	assert!(bounds(&client, "/drawable/mesh/broken").await.is_err());
+	Ok(())
+}
+
+/// Mirrors the server's image sources, leaving out resources.
+#[derive(Serialize)]
+enum ImageSource {
+	Texture(String),
+}
+#[derive(Serialize)]
+struct CreateImageInfo<'a> {
+	name: &'a str,
+	parent_path: &'a str,
+	transform: Transform,
+	source: ImageSource,
+	size: [f32; 2],
+}
+#[derive(Serialize)]
+struct CreateShmTextureInfo<'a> {
+	name: &'a str,
+	size: [u32; 2],
+	stride: u32,
+	format: u32,
+}
+const FORMAT_ABGR8888: u32 = u32::from_le_bytes(*b"AB24");
+
+fn create_shm_texture(
+	server: &TestServer,
+	client: &TestClient,
+	name: &str,
+	buffer_len: usize,
+) -> Result<()> {
+	let buffer_path = server.dir().join(name);
+	std::fs::write(&buffer_path, vec![255_u8; buffer_len])?;
+	client.signal_with_fds(
+		"/drawable",
+		"create_shm_texture",
+		CreateShmTextureInfo {
+			name,
+			size: [2, 2],
+			stride: 8,
+			format: FORMAT_ABGR8888,
+		},
+		vec![File::open(&buffer_path)?.into()],
+	)
+}
+fn create_image(client: &TestClient, name: &str, texture_path: &str) -> Result<()> {
+	client.signal(
+		"/drawable",
+		"create_image",
+		CreateImageInfo {
+			name,
+			parent_path: "/",
+			transform: Transform::none(),
+			source: ImageSource::Texture(texture_path.to_string()),
+			size: [0.5, 0.25],
+		},
+	)
+}
+
+#[tokio::test]
+async fn image_from_shm_texture() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	create_shm_texture(&server, &client, "pixels", 16)?;
+	create_image(&client, "picture", "/drawable/texture/pixels")?;
+	let bounding_box = bounds(&client, "/drawable/image/picture").await?;
+	assert_vec_approx_eq(bounding_box.center, [0.0, 0.0, 0.0]);
+	assert_vec_approx_eq(bounding_box.size, [0.5, 0.25, 0.0]);
+
+	client.signal("/drawable/image/picture", "set_size", [1.0_f32, 2.0])?;
+	let bounding_box = bounds(&client, "/drawable/image/picture").await?;
+	assert_vec_approx_eq(bounding_box.size, [1.0, 2.0, 0.0]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn shm_texture_too_small() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	// 2x2 pixels with a stride of 8 needs 16 bytes, so no texture should be made
+	create_shm_texture(&server, &client, "pixels", 12)?;
+	create_image(&client, "picture", "/drawable/texture/pixels")?;
+	assert!(bounds(&client, "/drawable/image/picture").await.is_err());
+	Ok(())
+}
@@ -0,0 +1,953 @@
+mod common;
+
+use color_eyre::eyre::{bail, Result};
+use common::{assert_approx_eq, assert_vec_approx_eq, datamap, TestClient, TestServer, Transform};
+use mint::Vector3;
+use serde::{Deserialize, Serialize};
+use stardust_xr::values::ResourceID;
+
+#[tokio::test]
+async fn sphere_field() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("sphere", "/", [0.0_f32, 0.0, 0.0], 0.5_f32),
+	)?;
+
+	let distance: f32 = client
+		.method("/field/sphere", "distance", ("/", [1.0_f32, 0.0, 0.0]))
+		.await?;
+	assert_approx_eq(distance, 0.5);
+	let distance: f32 = client
+		.method("/field/sphere", "distance", ("/", [0.0_f32, 0.0, 0.0]))
+		.await?;
+	assert_approx_eq(distance, -0.5);
+
+	let closest_point: Vector3<f32> = client
+		.method("/field/sphere", "closest_point", ("/", [0.0_f32, 0.0, 2.0]))
+		.await?;
+	assert_vec_approx_eq(closest_point, [0.0, 0.0, 0.5]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn sphere_normal_points_outward() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("sphere", "/", [0.0_f32, 1.0, 0.0], 0.5_f32),
+	)?;
+
+	// the normal is the distance gradient, so it points away from the center outside and inside the sphere
+	assert_vec_approx_eq(
+		normal(&client, "/field/sphere", [0.0, 3.0, 0.0]).await?,
+		[0.0, 1.0, 0.0],
+	);
+	assert_vec_approx_eq(
+		normal(&client, "/field/sphere", [2.0, 1.0, 0.0]).await?,
+		[1.0, 0.0, 0.0],
+	);
+	assert_vec_approx_eq(
+		normal(&client, "/field/sphere", [0.0, 0.75, 0.0]).await?,
+		[0.0, -1.0, 0.0],
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn box_field() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/field",
+		"create_box_field",
+		(
+			"box",
+			"/",
+			Transform::from_translation([0.0, 0.0, -1.0]),
+			[1.0_f32, 1.0, 1.0],
+		),
+	)?;
+
+	let distance: f32 = client
+		.method("/field/box", "distance", ("/", [0.0_f32, 0.0, 0.0]))
+		.await?;
+	assert_approx_eq(distance, 0.5);
+
+	client.signal("/field/box", "set_size", [1.0_f32, 1.0, 3.0])?;
+	let distance: f32 = client
+		.method("/field/box", "distance", ("/", [0.0_f32, 0.0, 1.0]))
+		.await?;
+	assert_approx_eq(distance, 0.5);
+	Ok(())
+}
+
+#[tokio::test]
+async fn field_follows_its_parent() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		("anchor", "/", Transform::none(), false),
+	)?;
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		(
+			"sphere",
+			"/spatial/spatial/anchor",
+			[0.0_f32, 0.0, 0.0],
+			0.5_f32,
+		),
+	)?;
+	client.signal(
+		"/spatial/spatial/anchor",
+		"set_local_transform",
+		Transform::from_translation([3.0, 0.0, 0.0]),
+	)?;
+
+	let distance: f32 = client
+		.method("/field/sphere", "distance", ("/", [0.0_f32, 0.0, 0.0]))
+		.await?;
+	assert_approx_eq(distance, 2.5);
+	Ok(())
+}
+
+#[derive(Serialize)]
+struct CreateCompositeFieldInfo<'a> {
+	name: &'a str,
+	parent_path: &'a str,
+	transform: Transform,
+	operation: u32,
+	smoothness: f32,
+	field_paths: Vec<&'a str>,
+}
+const UNION: u32 = 0;
+const INTERSECTION: u32 = 1;
+const SUBTRACTION: u32 = 2;
+
+fn create_sphere(client: &TestClient, name: &str, position: [f32; 3], radius: f32) -> Result<()> {
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		(name, "/", position, radius),
+	)
+}
+fn create_composite(
+	client: &TestClient,
+	name: &str,
+	operation: u32,
+	smoothness: f32,
+	field_paths: Vec<&str>,
+) -> Result<()> {
+	client.signal(
+		"/field",
+		"create_composite_field",
+		CreateCompositeFieldInfo {
+			name,
+			parent_path: "/",
+			transform: Transform::none(),
+			operation,
+			smoothness,
+			field_paths,
+		},
+	)
+}
+async fn distance(client: &TestClient, path: &str, point: [f32; 3]) -> Result<f32> {
+	client.method(path, "distance", ("/", point)).await
+}
+#[tokio::test]
+async fn composite_fields() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	create_sphere(&client, "left", [-1.0, 0.0, 0.0], 0.5)?;
+	create_sphere(&client, "right", [1.0, 0.0, 0.0], 0.5)?;
+	create_composite(
+		&client,
+		"union",
+		UNION,
+		0.0,
+		vec!["/field/left", "/field/right"],
+	)?;
+	assert_approx_eq(
+		distance(&client, "/field/union", [0.0, 0.0, 0.0]).await?,
+		0.5,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/union", [1.0, 0.0, 0.0]).await?,
+		-0.5,
+	);
+
+	create_composite(
+		&client,
+		"smooth_union",
+		UNION,
+		0.5,
+		vec!["/field/left", "/field/right"],
+	)?;
+	assert_approx_eq(
+		distance(&client, "/field/smooth_union", [0.0, 0.0, 0.0]).await?,
+		0.375,
+	);
+
+	create_sphere(&client, "big", [0.0, 0.0, 0.0], 1.0)?;
+	create_sphere(&client, "small", [0.0, 0.0, 0.0], 0.5)?;
+	create_composite(
+		&client,
+		"shell",
+		SUBTRACTION,
+		0.0,
+		vec!["/field/big", "/field/small"],
+	)?;
+	assert_approx_eq(
+		distance(&client, "/field/shell", [0.0, 0.0, 0.0]).await?,
+		0.5,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/shell", [0.75, 0.0, 0.0]).await?,
+		-0.25,
+	);
+
+	create_composite(
+		&client,
+		"intersection",
+		INTERSECTION,
+		0.0,
+		vec!["/field/big", "/field/right"],
+	)?;
+	assert_approx_eq(
+		distance(&client, "/field/intersection", [0.75, 0.0, 0.0]).await?,
+		-0.25,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/intersection", [-0.75, 0.0, 0.0]).await?,
+		1.25,
+	);
+
+	// composites can be ray marched like any other field
+	#[derive(Deserialize)]
+	struct RayMarchResult {
+		min_distance: f32,
+	}
+	let result: RayMarchResult = client
+		.method(
+			"/field/union",
+			"ray_march",
+			("/", [-3.0_f32, 0.0, 0.0], [1.0_f32, 0.0, 0.0]),
+		)
+		.await?;
+	assert!(result.min_distance <= 0.0);
+
+	// nested composites work too
+	create_composite(
+		&client,
+		"nested",
+		UNION,
+		0.0,
+		vec!["/field/shell", "/field/union"],
+	)?;
+	assert_approx_eq(
+		distance(&client, "/field/nested", [0.0, 0.0, 0.0]).await?,
+		0.5,
+	);
+	Ok(())
+}
+
+/// Writes a 1m cube centered on the origin as a glTF with an external buffer.
+fn write_cube_gltf(dir: &std::path::Path) -> Result<std::path::PathBuf> {
+	let positions: [[f32; 3]; 8] = [
+		[-0.5, -0.5, -0.5],
+		[0.5, -0.5, -0.5],
+		[0.5, 0.5, -0.5],
+		[-0.5, 0.5, -0.5],
+		[-0.5, -0.5, 0.5],
+		[0.5, -0.5, 0.5],
+		[0.5, 0.5, 0.5],
+		[-0.5, 0.5, 0.5],
+	];
+	let indices: [u16; 36] = [
+		0, 2, 1, 0, 3, 2, // back
+		4, 5, 6, 4, 6, 7, // front
+		0, 1, 5, 0, 5, 4, // bottom
+		3, 7, 6, 3, 6, 2, // top
+		0, 4, 7, 0, 7, 3, // left
+		1, 2, 6, 1, 6, 5, // right
+	];
+	let mut buffer = Vec::new();
+	for position in positions.iter().flatten() {
+		buffer.extend_from_slice(&position.to_le_bytes());
+	}
+	for index in indices {
+		buffer.extend_from_slice(&index.to_le_bytes());
+	}
+	std::fs::write(dir.join("cube.bin"), &buffer)?;
+
+	let gltf = format!(
+		r#"{{
+	"asset": {{ "version": "2.0" }},
+	"scene": 0,
+	"scenes": [{{ "nodes": [0] }}],
+	"nodes": [{{ "mesh": 0 }}],
+	"meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}] }}],
+	"buffers": [{{ "uri": "cube.bin", "byteLength": {} }}],
+	"bufferViews": [
+		{{ "buffer": 0, "byteOffset": 0, "byteLength": 96 }},
+		{{ "buffer": 0, "byteOffset": 96, "byteLength": 72 }}
+	],
+	"accessors": [
+		{{ "bufferView": 0, "componentType": 5126, "count": 8, "type": "VEC3", "min": [-0.5, -0.5, -0.5], "max": [0.5, 0.5, 0.5] }},
+		{{ "bufferView": 1, "componentType": 5123, "count": 36, "type": "SCALAR" }}
+	]
+}}"#,
+		buffer.len()
+	);
+	let path = dir.join("cube.gltf");
+	std::fs::write(&path, gltf)?;
+	Ok(path)
+}
+
+#[tokio::test]
+async fn mesh_field() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let cube_path = write_cube_gltf(server.dir())?;
+
+	#[derive(Serialize)]
+	struct CreateMeshFieldInfo<'a> {
+		name: &'a str,
+		parent_path: &'a str,
+		transform: Transform,
+		model: ResourceID,
+	}
+	// the method only returns once the field is ready
+	client
+		.method::<_, ()>(
+			"/field",
+			"create_mesh_field",
+			CreateMeshFieldInfo {
+				name: "cube",
+				parent_path: "/",
+				transform: Transform::none(),
+				model: ResourceID::Direct(cube_path),
+			},
+		)
+		.await?;
+
+	assert_approx_eq(
+		distance(&client, "/field/cube", [1.0, 0.0, 0.0]).await?,
+		0.5,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/cube", [0.0, 0.0, 0.0]).await?,
+		-0.5,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/cube", [0.0, 0.25, 0.0]).await?,
+		-0.25,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/cube", [1.0, 1.0, 0.0]).await?,
+		0.5_f32.sqrt(),
+	);
+
+	// a model that doesn't exist shouldn't make a field, and the caller hears why
+	let missing = client
+		.method::<_, ()>(
+			"/field",
+			"create_mesh_field",
+			CreateMeshFieldInfo {
+				name: "missing",
+				parent_path: "/",
+				transform: Transform::none(),
+				model: ResourceID::Direct(server.dir().join("missing.glb")),
+			},
+		)
+		.await;
+	assert!(missing.is_err());
+	assert!(distance(&client, "/field/missing", [0.0, 0.0, 0.0])
+		.await
+		.is_err());
+	Ok(())
+}
+
+/// Parents a spatial under a 90° turn around Z with its local Y stretched 2x,
+/// so its local X points along the reference's Y and its local Y along the reference's -X at double length.
+fn create_stretched_parent(client: &TestClient) -> Result<&'static str> {
+	let half_sqrt_2 = 0.5_f32.sqrt();
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"turned",
+			"/",
+			Transform::from_rotation([0.0, 0.0, half_sqrt_2, half_sqrt_2]),
+			false,
+		),
+	)?;
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"stretched",
+			"/spatial/spatial/turned",
+			Transform::from_translation_scale([0.0; 3], [1.0, 2.0, 1.0]),
+			false,
+		),
+	)?;
+	Ok("/spatial/spatial/stretched")
+}
+async fn normal(client: &TestClient, path: &str, point: [f32; 3]) -> Result<Vector3<f32>> {
+	client.method(path, "normal", ("/", point)).await
+}
+
+#[tokio::test]
+async fn sphere_field_under_scale() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let parent = create_stretched_parent(&client)?;
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("sphere", parent, [0.0_f32, 0.0, 0.0], 0.5_f32),
+	)?;
+
+	// an ellipsoid reaching 1m along X and 0.5m along Y and Z
+	assert_approx_eq(
+		distance(&client, "/field/sphere", [3.0, 0.0, 0.0]).await?,
+		2.0,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/sphere", [0.0, 3.0, 0.0]).await?,
+		2.5,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/sphere", [0.0, 0.0, 3.0]).await?,
+		2.5,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/sphere", [0.0, 0.0, 0.0]).await?,
+		-0.5,
+	);
+
+	assert_vec_approx_eq(
+		normal(&client, "/field/sphere", [3.0, 0.0, 0.0]).await?,
+		[1.0, 0.0, 0.0],
+	);
+	assert_vec_approx_eq(
+		normal(&client, "/field/sphere", [0.0, -3.0, 0.0]).await?,
+		[0.0, -1.0, 0.0],
+	);
+	let closest_point: Vector3<f32> = client
+		.method("/field/sphere", "closest_point", ("/", [3.0_f32, 0.0, 0.0]))
+		.await?;
+	assert_vec_approx_eq(closest_point, [1.0, 0.0, 0.0]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn box_field_under_scale() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let parent = create_stretched_parent(&client)?;
+	client.signal(
+		"/field",
+		"create_box_field",
+		("box", parent, Transform::none(), [1.0_f32, 1.0, 1.0]),
+	)?;
+
+	// 2m along X, 1m along Y and Z
+	assert_approx_eq(distance(&client, "/field/box", [3.0, 0.0, 0.0]).await?, 2.0);
+	assert_approx_eq(distance(&client, "/field/box", [0.0, 3.0, 0.0]).await?, 2.5);
+	assert_approx_eq(
+		distance(&client, "/field/box", [0.75, 0.0, 0.0]).await?,
+		-0.25,
+	);
+
+	assert_vec_approx_eq(
+		normal(&client, "/field/box", [3.0, 0.0, 0.0]).await?,
+		[1.0, 0.0, 0.0],
+	);
+	assert_vec_approx_eq(
+		normal(&client, "/field/box", [0.0, 0.0, 3.0]).await?,
+		[0.0, 0.0, 1.0],
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn cylinder_field_under_scale() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let parent = create_stretched_parent(&client)?;
+	client.signal(
+		"/field",
+		"create_cylinder_field",
+		("cylinder", parent, Transform::none(), 1.0_f32, 0.5_f32),
+	)?;
+
+	// 1m long along Z, with an elliptical cross section reaching 1m along X and 0.5m along Y
+	assert_approx_eq(
+		distance(&client, "/field/cylinder", [0.0, 0.0, 2.0]).await?,
+		1.5,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/cylinder", [3.0, 0.0, 0.0]).await?,
+		2.0,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/cylinder", [0.0, 3.0, 0.0]).await?,
+		2.5,
+	);
+
+	assert_vec_approx_eq(
+		normal(&client, "/field/cylinder", [0.0, 0.0, 2.0]).await?,
+		[0.0, 0.0, 1.0],
+	);
+	assert_vec_approx_eq(
+		normal(&client, "/field/cylinder", [-3.0, 0.0, 0.0]).await?,
+		[-1.0, 0.0, 0.0],
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn torus_field_under_scale() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let parent = create_stretched_parent(&client)?;
+	client.signal(
+		"/field",
+		"create_torus_field",
+		("torus", parent, Transform::none(), 0.5_f32, 0.1_f32),
+	)?;
+
+	// the ring lies in the YZ plane and its tube is twice as thick along X
+	assert_approx_eq(
+		distance(&client, "/field/torus", [0.0, 0.0, 2.0]).await?,
+		1.4,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/torus", [1.0, 0.0, 0.5]).await?,
+		0.8,
+	);
+
+	assert_vec_approx_eq(
+		normal(&client, "/field/torus", [1.0, 0.0, 0.5]).await?,
+		[1.0, 0.0, 0.0],
+	);
+	assert_vec_approx_eq(
+		normal(&client, "/field/torus", [0.0, 0.0, 2.0]).await?,
+		[0.0, 0.0, 1.0],
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn ray_march_under_scale() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let parent = create_stretched_parent(&client)?;
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("sphere", parent, [0.0_f32, 0.0, 0.0], 0.5_f32),
+	)?;
+
+	#[derive(Deserialize)]
+	struct RayMarchResult {
+		min_distance: f32,
+		deepest_point_distance: f32,
+	}
+	let assert_close = |a: f32, b: f32, tolerance: f32| {
+		assert!((a - b).abs() < tolerance, "{a} != {b}");
+	};
+
+	// passing the ellipsoid along its stretched axis, lengths are in the reference space's units
+	let result: RayMarchResult = client
+		.method(
+			"/field/sphere",
+			"ray_march",
+			("/", [-5.0_f32, 0.75, 0.0], [1.0_f32, 0.0, 0.0]),
+		)
+		.await?;
+	assert_close(result.min_distance, 0.25, 0.05);
+	assert_close(result.deepest_point_distance, 5.0, 0.3);
+
+	// passing it across its stretched axis, distances are too
+	let result: RayMarchResult = client
+		.method(
+			"/field/sphere",
+			"ray_march",
+			("/", [1.5_f32, -5.0, 0.0], [0.0_f32, 1.0, 0.0]),
+		)
+		.await?;
+	assert_close(result.min_distance, 0.5, 0.05);
+	assert_close(result.deepest_point_distance, 5.0, 0.3);
+	Ok(())
+}
+
+/// A spatial turned 45° around Z then stretched 2x along its own X, so the field's axes don't line up with the reference space.
+fn create_tilted_parent(client: &TestClient) -> Result<&'static str> {
+	let half_angle = std::f32::consts::FRAC_PI_8;
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"tilted",
+			"/",
+			Transform::from_rotation([0.0, 0.0, half_angle.sin(), half_angle.cos()]),
+			false,
+		),
+	)?;
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"widened",
+			"/spatial/spatial/tilted",
+			Transform::from_translation_scale([0.0; 3], [2.0, 1.0, 1.0]),
+			false,
+		),
+	)?;
+	Ok("/spatial/spatial/widened")
+}
+
+#[tokio::test]
+async fn box_field_under_tilted_scale() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let parent = create_tilted_parent(&client)?;
+	client.signal(
+		"/field",
+		"create_box_field",
+		("box", parent, Transform::none(), [1.0_f32, 1.0, 1.0]),
+	)?;
+
+	// 2m along the diagonal between X and Y, 1m across it and along Z
+	let d = 0.5_f32.sqrt();
+	assert_approx_eq(
+		distance(&client, "/field/box", [3.0 * d, 3.0 * d, 0.0]).await?,
+		2.0,
+	);
+	assert_approx_eq(
+		distance(&client, "/field/box", [-3.0 * d, 3.0 * d, 0.0]).await?,
+		2.5,
+	);
+	assert_approx_eq(distance(&client, "/field/box", [0.0, 0.0, 2.0]).await?, 1.5);
+
+	assert_vec_approx_eq(
+		normal(&client, "/field/box", [3.0 * d, 3.0 * d, 0.0]).await?,
+		[d, d, 0.0],
+	);
+	assert_vec_approx_eq(
+		normal(&client, "/field/box", [3.0 * d, -3.0 * d, 0.0]).await?,
+		[d, -d, 0.0],
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn ray_march_under_tilted_scale() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+	let parent = create_tilted_parent(&client)?;
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("sphere", parent, [0.0_f32, 0.0, 0.0], 0.5_f32),
+	)?;
+
+	#[derive(Deserialize)]
+	struct RayMarchResult {
+		min_distance: f32,
+		deepest_point_distance: f32,
+	}
+	let assert_close = |a: f32, b: f32, tolerance: f32| {
+		assert!((a - b).abs() < tolerance, "{a} != {b}");
+	};
+
+	// passing 0.75m to the side of the ellipsoid's long diagonal axis, which only reaches 0.5m to the side
+	let d = 0.5_f32.sqrt();
+	let origin = [-5.0 * d - 0.75 * d, -5.0 * d + 0.75 * d, 0.0];
+	let result: RayMarchResult = client
+		.method("/field/sphere", "ray_march", ("/", origin, [d, d, 0.0]))
+		.await?;
+	assert_close(result.min_distance, 0.25, 0.05);
+	assert_close(result.deepest_point_distance, 5.0, 0.3);
+
+	// passing 1.5m along the long axis, across it
+	let origin = [1.5 * d + 5.0 * d, 1.5 * d - 5.0 * d, 0.0];
+	let result: RayMarchResult = client
+		.method("/field/sphere", "ray_march", ("/", origin, [-d, d, 0.0]))
+		.await?;
+	assert_close(result.min_distance, 0.5, 0.05);
+	assert_close(result.deepest_point_distance, 5.0, 0.3);
+	Ok(())
+}
+
+/// Mirrors the server's `FieldShape`.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FieldShape {
+	Box {
+		size: Vector3<f32>,
+	},
+	Cylinder {
+		length: f32,
+		radius: f32,
+	},
+	Sphere {
+		radius: f32,
+	},
+	Torus {
+		radius_a: f32,
+		radius_b: f32,
+	},
+	Composite {
+		operation: u32,
+		smoothness: f32,
+		fields: Vec<CompositeChildShape>,
+	},
+}
+#[derive(Debug, PartialEq, Deserialize)]
+struct CompositeChildShape {
+	shape: FieldShape,
+	position: Vector3<f32>,
+	rotation: mint::Quaternion<f32>,
+	scale: Vector3<f32>,
+}
+impl CompositeChildShape {
+	fn at(shape: FieldShape, position: [f32; 3]) -> Self {
+		CompositeChildShape {
+			shape,
+			position: position.into(),
+			rotation: [0.0, 0.0, 0.0, 1.0].into(),
+			scale: [1.0; 3].into(),
+		}
+	}
+}
+async fn shape(client: &TestClient, path: &str) -> Result<FieldShape> {
+	client.method(path, "get_shape", ()).await
+}
+
+#[tokio::test]
+async fn field_shapes() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/field",
+		"create_box_field",
+		("box", "/", Transform::none(), [1.0_f32, 2.0, 3.0]),
+	)?;
+	assert_eq!(
+		shape(&client, "/field/box").await?,
+		FieldShape::Box {
+			size: [1.0, 2.0, 3.0].into()
+		}
+	);
+	client.signal("/field/box", "set_size", [3.0_f32, 2.0, 1.0])?;
+	assert_eq!(
+		shape(&client, "/field/box").await?,
+		FieldShape::Box {
+			size: [3.0, 2.0, 1.0].into()
+		}
+	);
+
+	client.signal(
+		"/field",
+		"create_cylinder_field",
+		("cylinder", "/", Transform::none(), 1.0_f32, 0.25_f32),
+	)?;
+	assert_eq!(
+		shape(&client, "/field/cylinder").await?,
+		FieldShape::Cylinder {
+			length: 1.0,
+			radius: 0.25
+		}
+	);
+
+	create_sphere(&client, "sphere", [0.0, 1.0, 0.0], 0.5)?;
+	assert_eq!(
+		shape(&client, "/field/sphere").await?,
+		FieldShape::Sphere { radius: 0.5 }
+	);
+
+	client.signal(
+		"/field",
+		"create_torus_field",
+		("torus", "/", Transform::none(), 0.5_f32, 0.125_f32),
+	)?;
+	assert_eq!(
+		shape(&client, "/field/torus").await?,
+		FieldShape::Torus {
+			radius_a: 0.5,
+			radius_b: 0.125
+		}
+	);
+
+	create_composite(
+		&client,
+		"composite",
+		SUBTRACTION,
+		0.25,
+		vec!["/field/box", "/field/sphere"],
+	)?;
+	assert_eq!(
+		shape(&client, "/field/composite").await?,
+		FieldShape::Composite {
+			operation: SUBTRACTION,
+			smoothness: 0.25,
+			fields: vec![
+				CompositeChildShape::at(
+					FieldShape::Box {
+						size: [3.0, 2.0, 1.0].into()
+					},
+					[0.0; 3]
+				),
+				CompositeChildShape::at(FieldShape::Sphere { radius: 0.5 }, [0.0, 1.0, 0.0]),
+			]
+		}
+	);
+
+	// composites inside composites are described all the way down
+	create_composite(
+		&client,
+		"nested",
+		UNION,
+		0.0,
+		vec!["/field/composite", "/field/torus"],
+	)?;
+	let FieldShape::Composite { fields, .. } = shape(&client, "/field/nested").await? else {
+		bail!("Nested composite isn't a composite");
+	};
+	assert_eq!(fields.len(), 2);
+	assert_eq!(fields[0].shape, shape(&client, "/field/composite").await?);
+	assert_eq!(
+		fields[1],
+		CompositeChildShape::at(
+			FieldShape::Torus {
+				radius_a: 0.5,
+				radius_b: 0.125
+			},
+			[0.0; 3]
+		)
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn field_change_subscriptions() -> Result<()> {
+	let server = TestServer::start().await?;
+	let owner = server.connect().await?;
+	let subscriber = server.connect().await?;
+
+	// the subscriber gets an alias of the owner's field through a pulse receiver
+	owner.signal(
+		"/spatial",
+		"create_spatial",
+		("anchor", "/", Transform::none(), false),
+	)?;
+	owner.signal(
+		"/field",
+		"create_sphere_field",
+		(
+			"receiver_field",
+			"/spatial/spatial/anchor",
+			[0.0_f32, 0.0, 0.0],
+			0.1_f32,
+		),
+	)?;
+	owner.signal(
+		"/data",
+		"create_pulse_receiver",
+		(
+			"receiver",
+			"/",
+			Transform::none(),
+			"/field/receiver_field",
+			datamap(&["test"]),
+		),
+	)?;
+	subscriber.signal(
+		"/data",
+		"create_pulse_sender",
+		("sender", "/", Transform::none(), datamap(&["test"])),
+	)?;
+	let new_receiver = subscriber
+		.wait_for_signal("/data/sender/sender", "new_receiver")
+		.await?;
+	let (_uid, _receiver_path, field_path): (String, String, String) =
+		new_receiver.deserialize()?;
+
+	assert_eq!(
+		shape(&subscriber, &field_path).await?,
+		FieldShape::Sphere { radius: 0.1 }
+	);
+
+	#[derive(Serialize)]
+	struct SubscribeChangesInfo<'a> {
+		reference_space_path: &'a str,
+	}
+	subscriber.signal(
+		&field_path,
+		"subscribe_changes",
+		SubscribeChangesInfo {
+			reference_space_path: "/",
+		},
+	)?;
+
+	// the current state is sent right away
+	let shape_changed: FieldShape = subscriber
+		.wait_for_signal(&field_path, "shape_changed")
+		.await?
+		.deserialize()?;
+	assert_eq!(shape_changed, FieldShape::Sphere { radius: 0.1 });
+	let transform_changed: Transform = subscriber
+		.wait_for_signal(&field_path, "transform_changed")
+		.await?
+		.deserialize()?;
+	assert_vec_approx_eq(transform_changed.translation.unwrap(), [0.0, 0.0, 0.0]);
+
+	owner.signal("/field/receiver_field", "set_radius", 0.2_f32)?;
+	let shape_changed: FieldShape = subscriber
+		.wait_for_signal(&field_path, "shape_changed")
+		.await?
+		.deserialize()?;
+	assert_eq!(shape_changed, FieldShape::Sphere { radius: 0.2 });
+
+	owner.signal(
+		"/spatial/spatial/anchor",
+		"set_local_transform",
+		Transform::from_translation([0.0, 1.0, 0.0]),
+	)?;
+	let transform_changed: Transform = subscriber
+		.wait_for_signal(&field_path, "transform_changed")
+		.await?
+		.deserialize()?;
+	assert_vec_approx_eq(transform_changed.translation.unwrap(), [0.0, 1.0, 0.0]);
+
+	subscriber.signal(&field_path, "unsubscribe_changes", ())?;
+	// make sure the unsubscribe went through before changing anything
+	let _ = shape(&subscriber, &field_path).await?;
+	owner.signal("/field/receiver_field", "set_radius", 0.3_f32)?;
+	assert_eq!(
+		shape(&owner, "/field/receiver_field").await?,
+		FieldShape::Sphere { radius: 0.3 }
+	);
+	tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+	assert!(subscriber
+		.take_signal(&field_path, "shape_changed")
+		.is_none());
+	Ok(())
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use color_eyre::eyre::Result;
+use common::{TestServer, Transform};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct CreateInputHandlerInfo<'a> {
+	name: &'a str,
+	parent_path: &'a str,
+	transform: Transform,
+	field_path: &'a str,
+}
+#[derive(Serialize)]
+struct CreateTipInfo<'a> {
+	name: &'a str,
+	parent_path: &'a str,
+	transform: Transform,
+	radius: f32,
+	datamap: Option<Vec<u8>>,
+}
+
+#[tokio::test]
+async fn tip_reaches_handler() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("handler_field", "/", [0.0_f32, 0.0, 0.0], 0.5_f32),
+	)?;
+	client.signal(
+		"/input",
+		"create_input_handler",
+		CreateInputHandlerInfo {
+			name: "handler",
+			parent_path: "/",
+			transform: Transform::none(),
+			field_path: "/field/handler_field",
+		},
+	)?;
+	client.signal(
+		"/input",
+		"create_input_method_tip",
+		CreateTipInfo {
+			name: "tip",
+			parent_path: "/",
+			transform: Transform::from_translation([0.0, 0.0, 0.25]),
+			radius: 0.01,
+			datamap: None,
+		},
+	)?;
+
+	let handler_created = client
+		.wait_for_signal("/input/method/tip/tip", "handler_created")
+		.await?;
+	let handler_uid: String = handler_created.deserialize()?;
+	assert!(!handler_uid.is_empty());
+
+	// the headless frame loop processes input, so the handler should get the tip soon
+	client
+		.wait_for_signal("/input/handler/handler", "input")
+		.await?;
+
+	client.signal("/input/handler/handler", "destroy", ())?;
+	let handler_destroyed = client
+		.wait_for_signal("/input/method/tip/tip", "handler_destroyed")
+		.await?;
+	assert_eq!(handler_destroyed.deserialize::<String>()?, handler_uid);
+	Ok(())
+}
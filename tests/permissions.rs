@@ -0,0 +1,81 @@
+mod common;
+
+use color_eyre::eyre::Result;
+use common::{TestServer, Transform};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct CreateTipInfo<'a> {
+	name: &'a str,
+	parent_path: &'a str,
+	transform: Transform,
+	radius: f32,
+	datamap: Option<Vec<u8>>,
+}
+
+fn write_policy(dir: &Path, policy: &str) -> Result<()> {
+	let config_dir = dir.join("config").join("stardust");
+	std::fs::create_dir_all(&config_dir)?;
+	std::fs::write(config_dir.join("permissions"), policy)?;
+	Ok(())
+}
+
+fn create_tip(client: &common::TestClient) -> Result<()> {
+	client.signal(
+		"/input",
+		"create_input_method_tip",
+		CreateTipInfo {
+			name: "tip",
+			parent_path: "/",
+			transform: Transform::none(),
+			radius: 0.01,
+			datamap: None,
+		},
+	)
+}
+
+#[tokio::test]
+async fn denied_without_capability() -> Result<()> {
+	let server = TestServer::start_with(|dir| write_policy(dir, "* zone\n")).await?;
+	let client = server.connect().await?;
+
+	create_tip(&client)?;
+	let tip_transform: Result<Transform> = client
+		.method("/input/method/tip/tip", "get_transform", "/")
+		.await;
+	assert!(tip_transform.is_err());
+
+	client.signal(
+		"/field",
+		"create_sphere_field",
+		("zone_field", "/", [0.0_f32, 0.0, 0.0], 1.0_f32),
+	)?;
+	client.signal(
+		"/spatial",
+		"create_zone",
+		("zone", "/", Transform::none(), "/field/zone_field"),
+	)?;
+	let _: Transform = client
+		.method("/spatial/zone/zone", "get_transform", "/")
+		.await?;
+	Ok(())
+}
+
+#[tokio::test]
+async fn granted_by_exe() -> Result<()> {
+	let exe = std::env::current_exe()?;
+	let policy = format!(
+		"{} input_method\n{} item_ui # by name\n",
+		exe.display(),
+		exe.file_name().unwrap().to_string_lossy()
+	);
+	let server = TestServer::start_with(|dir| write_policy(dir, &policy)).await?;
+	let client = server.connect().await?;
+
+	create_tip(&client)?;
+	let _: Transform = client
+		.method("/input/method/tip/tip", "get_transform", "/")
+		.await?;
+	Ok(())
+}
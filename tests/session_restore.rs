@@ -0,0 +1,83 @@
+mod common;
+
+use color_eyre::eyre::{bail, Result};
+use common::TestServer;
+use glam::Mat4;
+use serde::Serialize;
+use stardust_xr::schemas::flex::flexbuffers;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+/// Mirrors the server's saved `ClientState` format.
+#[derive(Serialize)]
+struct LaunchInfo {
+	cmdline: Vec<String>,
+	cwd: PathBuf,
+	env: HashMap<String, String>,
+}
+#[derive(Serialize)]
+struct ClientState {
+	launch_info: Option<LaunchInfo>,
+	data: Option<Vec<u8>>,
+	root: Mat4,
+	spatial_anchors: HashMap<String, Mat4>,
+}
+
+#[tokio::test]
+async fn saved_clients_are_relaunched() -> Result<()> {
+	let server = TestServer::start_with(|dir| {
+		let state_dir = dir.join("state").join("stardust");
+		std::fs::create_dir_all(&state_dir)?;
+		let state = ClientState {
+			launch_info: Some(LaunchInfo {
+				cmdline: vec![
+					"/bin/sh".to_string(),
+					"-c".to_string(),
+					"echo \"$STARDUST_STARTUP_TOKEN $STARDUST_INSTANCE $OLD_VAR\" > relaunched"
+						.to_string(),
+				],
+				cwd: dir.to_path_buf(),
+				env: HashMap::from([
+					("OLD_VAR".to_string(), "kept".to_string()),
+					("STARDUST_INSTANCE".to_string(), "stale".to_string()),
+				]),
+			}),
+			data: None,
+			root: Mat4::IDENTITY,
+			spatial_anchors: HashMap::new(),
+		};
+		std::fs::write(state_dir.join("client"), flexbuffers::to_vec(state)?)?;
+		std::fs::write(state_dir.join("garbage"), b"not a client state")?;
+		Ok(())
+	})
+	.await?;
+
+	let output_path = server.dir().join("relaunched");
+	let mut output = None;
+	for _ in 0..200 {
+		if let Ok(contents) = std::fs::read_to_string(&output_path) {
+			if contents.ends_with('\n') {
+				output = Some(contents);
+				break;
+			}
+		}
+		tokio::time::sleep(Duration::from_millis(10)).await;
+	}
+	let Some(output) = output else {
+		bail!("Saved client was never relaunched");
+	};
+	let words: Vec<&str> = output.split_whitespace().collect();
+	let [token, instance, old_var] = words.as_slice() else {
+		bail!("Unexpected relaunched environment: {output}");
+	};
+	assert!(!token.is_empty());
+	assert_eq!(
+		Some(*instance),
+		server.socket_path.file_name().and_then(|n| n.to_str())
+	);
+	assert_eq!(*old_var, "kept");
+
+	let state_dir = server.dir().join("state").join("stardust");
+	assert!(!state_dir.join("client").exists());
+	assert!(!state_dir.join("garbage").exists());
+	Ok(())
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use color_eyre::eyre::Result;
+use common::{assert_vec_approx_eq, TestServer, Transform};
+use serde::Serialize;
+use std::time::Duration;
+
+#[tokio::test]
+async fn create_spatial() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"parent",
+			"/",
+			Transform::from_translation([0.0, 1.0, 0.0]),
+			false,
+		),
+	)?;
+	let transform: Transform = client
+		.method("/spatial/spatial/parent", "get_transform", "/")
+		.await?;
+	assert_vec_approx_eq(transform.translation.unwrap(), [0.0, 1.0, 0.0]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn spatial_parenting() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"parent",
+			"/",
+			Transform::from_translation([0.0, 1.0, 0.0]),
+			false,
+		),
+	)?;
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		(
+			"child",
+			"/spatial/spatial/parent",
+			Transform::from_translation([1.0, 0.0, 0.0]),
+			false,
+		),
+	)?;
+	let transform: Transform = client
+		.method("/spatial/spatial/child", "get_transform", "/")
+		.await?;
+	assert_vec_approx_eq(transform.translation.unwrap(), [1.0, 1.0, 0.0]);
+
+	// moving the parent should carry the child with it
+	client.signal(
+		"/spatial/spatial/parent",
+		"set_local_transform",
+		Transform::from_translation_scale([0.0, 1.0, 0.0], [2.0, 2.0, 2.0]),
+	)?;
+	let transform: Transform = client
+		.method("/spatial/spatial/child", "get_transform", "/")
+		.await?;
+	assert_vec_approx_eq(transform.translation.unwrap(), [2.0, 1.0, 0.0]);
+
+	// unless it's been reparented in place
+	client.signal("/spatial/spatial/child", "set_spatial_parent_in_place", "/")?;
+	client.signal(
+		"/spatial/spatial/parent",
+		"set_local_transform",
+		Transform::from_translation([0.0, 5.0, 0.0]),
+	)?;
+	let transform: Transform = client
+		.method("/spatial/spatial/child", "get_transform", "/")
+		.await?;
+	assert_vec_approx_eq(transform.translation.unwrap(), [2.0, 1.0, 0.0]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn destroy_spatial() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		("doomed", "/", Transform::none(), false),
+	)?;
+	let _: Transform = client
+		.method("/spatial/spatial/doomed", "get_transform", "/")
+		.await?;
+
+	client.signal("/spatial/spatial/doomed", "destroy", ())?;
+	let result: Result<Transform> = client
+		.method("/spatial/spatial/doomed", "get_transform", "/")
+		.await;
+	assert!(result.is_err());
+	Ok(())
+}
+
+#[derive(Serialize)]
+struct AnimateTransformInfo {
+	transform: Transform,
+	duration: f32,
+	easing: u32,
+}
+const EASE_IN_OUT: u32 = 3;
+
+#[tokio::test]
+async fn animate_transform() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		("animated", "/", Transform::none(), false),
+	)?;
+	client.signal(
+		"/spatial/spatial/animated",
+		"animate_transform",
+		AnimateTransformInfo {
+			transform: Transform::from_translation([1.0, 0.0, 0.0]),
+			duration: 2.0,
+			easing: EASE_IN_OUT,
+		},
+	)?;
+
+	// partway there
+	tokio::time::sleep(Duration::from_millis(500)).await;
+	let transform: Transform = client
+		.method("/spatial/spatial/animated", "get_transform", "/")
+		.await?;
+	let x = transform.translation.unwrap().x;
+	assert!(x > 0.0 && x < 1.0, "{x} isn't between the start and end");
+
+	// the rest of the animation plus plenty of slack for a busy machine
+	let completed: bool = client
+		.wait_for_signal_timeout(
+			"/spatial/spatial/animated",
+			"transform_animation_ended",
+			Duration::from_secs(10),
+		)
+		.await?
+		.deserialize()?;
+	assert!(completed);
+	let transform: Transform = client
+		.method("/spatial/spatial/animated", "get_transform", "/")
+		.await?;
+	assert_vec_approx_eq(transform.translation.unwrap(), [1.0, 0.0, 0.0]);
+	Ok(())
+}
+
+#[tokio::test]
+async fn interrupt_transform_animation() -> Result<()> {
+	let server = TestServer::start().await?;
+	let client = server.connect().await?;
+
+	client.signal(
+		"/spatial",
+		"create_spatial",
+		("animated", "/", Transform::none(), false),
+	)?;
+	client.signal(
+		"/spatial/spatial/animated",
+		"animate_transform",
+		AnimateTransformInfo {
+			transform: Transform::from_translation([1.0, 0.0, 0.0]),
+			duration: 60.0,
+			easing: EASE_IN_OUT,
+		},
+	)?;
+	client.signal(
+		"/spatial/spatial/animated",
+		"set_local_transform",
+		Transform::from_translation([0.0, 2.0, 0.0]),
+	)?;
+
+	let completed: bool = client
+		.wait_for_signal("/spatial/spatial/animated", "transform_animation_ended")
+		.await?
+		.deserialize()?;
+	assert!(!completed);
+	// the animation doesn't keep moving it afterwards
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	let transform: Transform = client
+		.method("/spatial/spatial/animated", "get_transform", "/")
+		.await?;
+	assert_vec_approx_eq(transform.translation.unwrap(), [0.0, 2.0, 0.0]);
+	Ok(())
+}